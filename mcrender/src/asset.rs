@@ -1,3 +1,6 @@
+mod coverage;
+pub use coverage::{AssetCoverage, AssetStatus, BlockUsage, CoverageEntry};
+
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Write;
@@ -53,6 +56,8 @@ pub const DEFAULT_BIOME: &str = "minecraft:plains";
 pub struct AssetCache<'s> {
     partials: PartialSpriteCache,
     assets: Mutex<HashMap<AssetInfo<'static>, Option<Arc<Sprite>>>>,
+    /// Desaturated copies of assets, by the bits of their saturation.
+    desaturated: Mutex<HashMap<(AssetInfo<'static>, u32), Arc<Sprite>>>,
     settings: &'s Settings,
 }
 
@@ -70,11 +75,13 @@ impl<'s> AssetCache<'s> {
         Ok(AssetCache {
             partials,
             assets: Mutex::new(HashMap::new()),
+            desaturated: Mutex::new(HashMap::new()),
             settings,
         })
     }
 
-    pub fn get_asset(&self, block: &BlockInfo) -> Option<Arc<Sprite>> {
+    /// Get the cache key for the asset of `block`.
+    fn asset_info<'b>(block: &'b BlockInfo) -> AssetInfo<'b> {
         // Only include biome in the cache key if rendering is biome-dependent
         let biome = if block.render.is_biome_aware() {
             Some(block.biome.clone())
@@ -83,10 +90,14 @@ impl<'s> AssetCache<'s> {
         };
 
         // Don't clone the block state unless absolutely necessary
-        let info = AssetInfo {
+        AssetInfo {
             state: Cow::Borrowed(block.state),
             biome,
-        };
+        }
+    }
+
+    pub fn get_asset(&self, block: &BlockInfo) -> Option<Arc<Sprite>> {
+        let info = Self::asset_info(block);
 
         // TODO: RwLock instead?
         let mut assets = self.assets.lock().unwrap();
@@ -115,6 +126,37 @@ impl<'s> AssetCache<'s> {
         }
     }
 
+    /// As [`Self::get_asset()`], but with the colour saturation of the asset scaled by
+    /// `saturation`.
+    pub fn get_desaturated_asset(&self, block: &BlockInfo, saturation: f32) -> Option<Arc<Sprite>> {
+        let sprite = self.get_asset(block)?;
+        let key = (Self::asset_info(block), saturation.to_bits());
+        let mut desaturated = self.desaturated.lock().unwrap();
+        if let Some(cached) = desaturated.get(&key) {
+            return Some(cached.clone());
+        }
+        let sprite = Arc::new(sprite.desaturated(saturation));
+        let (info, bits) = key;
+        desaturated.insert((info.into_owned(), bits), sprite.clone());
+        Some(sprite)
+    }
+
+    /// Check whether `block` renders as a cube that hides the blocks behind it. Blocks whose
+    /// textures have any transparency, like glass and ice, don't.
+    pub fn is_opaque(&self, block: &BlockInfo) -> bool {
+        self.get_asset(block).is_some_and(|sprite| sprite.opaque)
+    }
+
+    /// Try to create the asset for `block` without caching it, so that any error is returned
+    /// instead of logged. Returns `false` if the block's render rule doesn't draw anything.
+    pub fn check_asset(&self, block: &BlockInfo) -> anyhow::Result<bool> {
+        let info = AssetInfo {
+            state: Cow::Borrowed(block.state),
+            biome: Some(block.biome.clone()),
+        };
+        Ok(self.create_asset(&info, &block.render)?.is_some())
+    }
+
     #[tracing::instrument(skip_all, fields(key = %info))]
     fn create_asset(
         &self,
@@ -139,6 +181,7 @@ impl<'s> AssetCache<'s> {
                 for (aspect, render_mode) in PARTIALS {
                     sprite.add_new_layer(self.partials.get(&texture_name, aspect)?, render_mode);
                 }
+                sprite.opaque = self.partials.is_opaque(&texture_name)?;
                 Ok(Some(sprite))
             }
 
@@ -342,6 +385,8 @@ impl<'s> AssetCache<'s> {
                 return Err(anyhow!("unsupported axis value: {}", axis));
             }
         };
+        sprite.opaque =
+            self.partials.is_opaque(top_texture)? && self.partials.is_opaque(side_texture)?;
         Ok(Some(sprite))
     }
 
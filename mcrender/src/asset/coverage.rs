@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::asset::{AssetCache, DEFAULT_BIOME};
+use crate::settings::{AssetRule, Settings};
+use crate::util::{csv_field, intern_str};
+use crate::world::{BIndex, BlockInfo, BlockState, Chunk, LightLevel, WorldSource};
+
+/// Every distinct block state seen in a world, with the asset rule that matched it and how many
+/// blocks use it.
+#[derive(Debug, Default)]
+pub struct BlockUsage {
+    states: HashMap<BlockState, (Arc<AssetRule>, u64)>,
+}
+
+impl BlockUsage {
+    /// Count the block states used in every chunk of `source`. Chunks that can't be read are
+    /// recorded in the source's [`ErrorReport`](crate::world::ErrorReport) and skipped.
+    pub fn collect(source: &dyn WorldSource, settings: &Settings) -> Self {
+        source.fold_chunks(
+            settings,
+            BlockUsage::default,
+            |usage, chunk| usage.add_chunk(&chunk),
+            |mut a, b| {
+                a.merge(b);
+                a
+            },
+        )
+    }
+
+    pub fn add_chunk(&mut self, chunk: &Chunk) {
+        for section in chunk.sections.iter() {
+            let counts = section.block_state_counts();
+            for ((state, rule), count) in section.block_palette.iter().zip(counts) {
+                if count > 0 {
+                    self.add(state, rule, count);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, state: &BlockState, rule: &Arc<AssetRule>, count: u64) {
+        match self.states.get_mut(state) {
+            Some((_, existing)) => *existing += count,
+            None => {
+                self.states.insert(state.clone(), (rule.clone(), count));
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: BlockUsage) {
+        for (state, (rule, count)) in other.states {
+            self.add(&state, &rule, count);
+        }
+    }
+
+    /// Try to create the asset for every block state, to find out which ones can't be rendered.
+    pub fn check_assets(&self, asset_cache: &AssetCache) -> AssetCoverage {
+        let biome = intern_str(DEFAULT_BIOME);
+        let mut entries: Vec<_> = self
+            .states
+            .iter()
+            .map(|(state, (rule, count))| {
+                let block = BlockInfo {
+                    index: BIndex((0, 0, 0).into()),
+                    state,
+                    biome: biome.clone(),
+                    lighting: LightLevel::new().with_block(0xF).with_sky(0xF),
+                    render: rule.render.clone(),
+                    block_entity: None,
+                };
+                let (status, error) = match asset_cache.check_asset(&block) {
+                    Ok(true) => (AssetStatus::Ok, None),
+                    Ok(false) => (AssetStatus::Nothing, None),
+                    Err(err) => (AssetStatus::Failed, Some(format!("{err:#}"))),
+                };
+                CoverageEntry {
+                    state: state.to_string(),
+                    rule: rule.name.clone(),
+                    status,
+                    error,
+                    count: *count,
+                }
+            })
+            .collect();
+        // Most used first, because those are the most noticeable if they're missing
+        entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.state.cmp(&b.state)));
+        AssetCoverage { entries }
+    }
+}
+
+/// Whether a block state's asset could be created.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, derive_more::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetStatus {
+    #[display("ok")]
+    Ok,
+    /// The rule deliberately doesn't draw anything, e.g. for air.
+    #[display("nothing")]
+    Nothing,
+    /// Creating the asset failed, usually because of a missing texture, so the block won't be
+    /// drawn.
+    #[display("failed")]
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CoverageEntry {
+    pub state: String,
+    /// The name of the matching rule in `asset_rules`.
+    pub rule: String,
+    pub status: AssetStatus,
+    pub error: Option<String>,
+    /// The number of blocks with this state.
+    pub count: u64,
+}
+
+/// The result of [`BlockUsage::check_assets()`].
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AssetCoverage {
+    pub entries: Vec<CoverageEntry>,
+}
+
+impl AssetCoverage {
+    /// Only keep block states whose assets failed.
+    pub fn retain_failed(&mut self) {
+        self.entries
+            .retain(|entry| entry.status == AssetStatus::Failed);
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == AssetStatus::Failed)
+            .count()
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        writeln!(writer, "state,rule,status,count,error")?;
+        for entry in self.entries.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                csv_field(&entry.state),
+                csv_field(&entry.rule),
+                entry.status,
+                entry.count,
+                csv_field(entry.error.as_deref().unwrap_or_default()),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.entries)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::test_settings;
+    use crate::world::{CCoords, HeightRange};
+
+    #[test]
+    fn test_block_usage() {
+        let settings = test_settings();
+        let chunk = Chunk::error_pattern(CCoords((0, 0).into()), HeightRange::OVERWORLD, &settings);
+        let mut usage = BlockUsage::default();
+        usage.add_chunk(&chunk);
+        let mut other = BlockUsage::default();
+        other.add_chunk(&chunk);
+        usage.merge(other);
+
+        let mut counts: Vec<_> = usage
+            .states
+            .iter()
+            .map(|(state, (rule, count))| (state.name.as_str(), rule.name.as_str(), *count))
+            .collect();
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![
+                ("minecraft:air", "no_render", 2 * 3840),
+                ("minecraft:black_concrete", "_default", 2 * 128),
+                ("minecraft:red_concrete", "_default", 2 * 128),
+            ]
+        );
+    }
+}
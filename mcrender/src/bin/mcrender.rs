@@ -1,25 +1,36 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
 use anyhow::{Result, anyhow};
 use clap::Parser;
 use config::FileFormat;
 use image::ImageBuffer;
 use image::imageops::FilterType;
+use notify::{EventKind, RecursiveMode, Watcher};
 use rayon::prelude::*;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 
-use mcrender::asset::AssetCache;
-use mcrender::canvas::Rgb8;
-use mcrender::coords::CoordsXZ;
+use mcrender::asset::{AssetCache, BlockUsage};
+use mcrender::canvas::{ImageBuf, Rgb8, Rgba8};
+use mcrender::coords::{CoordsXZ, CoordsXZY, Vec2D};
 use mcrender::render::sprite::new_sprite_buffer;
-use mcrender::render::{BlockContext, DimensionRenderer, Renderer};
+use mcrender::render::{
+    BlockContext, ChangeTracker, DimensionRenderer, Renderer, TimestampManifest,
+};
 use mcrender::settings::Settings;
 use mcrender::world::{
-    BIndex, BlockInfo, CCoords, ChunkBounds, DimensionID, LightLevelBuilder, RCoords,
+    BCoords, BIndex, BedrockWorld, BlockBox, BlockInfo, BlockPattern, BlockStats, CCoords,
+    ChunkBounds, DimensionID, ErrorStage, ErrorSummary, LightLevelBuilder, LocateQuery, Locations,
+    NamePattern, RCoords, StatsGrouping, StatsOptions, WorldInfo, WorldScan, WorldSource,
+    dump_block, dump_chunk, open_storage, to_json, to_snbt,
 };
 
 #[derive(Debug, clap::Parser)]
@@ -51,6 +62,15 @@ struct GlobalOpts {
     /// Set `assets_path` configuration option
     #[arg(short, long, global = true)]
     assets_path: Option<String>,
+    /// Set `compute_missing_light` configuration option
+    #[arg(long, default_value_t = false, global = true)]
+    compute_light: bool,
+    /// Write a JSON summary of unreadable regions and chunks to this file (`-` for stdout)
+    #[arg(long, global = true)]
+    error_report: Option<PathBuf>,
+    /// Draw chunks that couldn't be read with an error pattern, instead of leaving a hole
+    #[arg(long, default_value_t = false, global = true)]
+    show_errors: bool,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -76,24 +96,156 @@ enum Commands {
         target: PathBuf,
         #[arg(long, value_parser = parse_coords_xz)]
         coords: CoordsXZ,
-        // TODO: dimension
+        /// Dimension to render, e.g. `overworld`, `the_nether` or `mymod:custom`
+        #[arg(short, long, default_value = "overworld")]
+        dimension: DimensionID,
     },
     RenderChunk {
         source: PathBuf,
         target: PathBuf,
         #[arg(long, value_parser = parse_coords_xz)]
         coords: CoordsXZ,
-        // TODO: dimension
+        /// Dimension to render, e.g. `overworld`, `the_nether` or `mymod:custom`
+        #[arg(short, long, default_value = "overworld")]
+        dimension: DimensionID,
     },
     RenderTiles {
         source: PathBuf,
         target: PathBuf,
         #[arg(long)]
         column: Option<i32>,
-        // TODO: dimension
+        /// Render all tiles, instead of only tiles with chunks changed since the last render
+        #[arg(long, default_value_t = false)]
+        full: bool,
+        /// Dimension(s) to render, e.g. `overworld`, `the_nether` or `mymod:custom` [default: overworld]
+        #[arg(short, long)]
+        dimension: Vec<DimensionID>,
+        /// Render every dimension found in the world
+        #[arg(long, default_value_t = false, conflicts_with = "dimension")]
+        all_dimensions: bool,
+        /// Keep running after rendering, and re-render tiles as the game saves chunks
+        #[arg(long, default_value_t = false, conflicts_with = "column")]
+        watch: bool,
+    },
+    /// Summarize a world's regions and chunks, and check them for problems. Exits with an error
+    /// if any problems were found.
+    Scan {
+        source: PathBuf,
+        /// Dimension(s) to scan, e.g. `overworld`, `the_nether` or `mymod:custom` [default: all]
+        #[arg(short, long)]
+        dimension: Vec<DimensionID>,
+        /// Output JSON instead of a human-readable summary
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        /// List every region, not just regions with problems
+        #[arg(long, default_value_t = false)]
+        regions: bool,
+    },
+    /// Count block states, and optionally biomes, in a dimension
+    Stats {
+        source: PathBuf,
+        /// Dimension to count, e.g. `overworld`, `the_nether` or `mymod:custom`
+        #[arg(short, long, default_value = "overworld")]
+        dimension: DimensionID,
+        /// How to group the counts
+        #[arg(long, value_enum, default_value_t = StatsGrouping::Dimension)]
+        by: StatsGrouping,
+        /// Also count blocks by biome
+        #[arg(long, default_value_t = false)]
+        biomes: bool,
+        /// Count blocks by name, ignoring block state properties
+        #[arg(long, default_value_t = false)]
+        names_only: bool,
+        /// Only count these blocks, e.g. `hopper` or `minecraft:diamond_ore`
+        #[arg(short, long, value_name = "NAME")]
+        block: Vec<String>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+        /// Write output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Find the blocks, biomes or entities in a dimension that match a pattern, e.g. spawners,
+    /// `*_ore[...]` blocks or villagers
+    Locate {
+        source: PathBuf,
+        /// Dimension to search, e.g. `overworld`, `the_nether` or `mymod:custom`
+        #[arg(short, long, default_value = "overworld")]
+        dimension: DimensionID,
+        /// Find blocks matching this pattern, e.g. `spawner`, `*_ore` or `chest[type=single]`
+        #[arg(short, long, value_name = "PATTERN")]
+        block: Vec<BlockPattern>,
+        /// Find 4x4x4 biome cells matching this pattern, e.g. `minecraft:deep_dark`
+        #[arg(long, value_name = "PATTERN")]
+        biome: Vec<NamePattern>,
+        /// Find entities matching this pattern, e.g. `villager` (Java worlds only)
+        #[arg(short, long, value_name = "PATTERN")]
+        entity: Vec<NamePattern>,
+        /// Only search inside the box between two corners, given as `x1,y1,z1,x2,y2,z2`
+        #[arg(long, allow_hyphen_values = true)]
+        bbox: Option<BlockBox>,
+        #[arg(long, value_enum, default_value_t = LocateFormat::Csv)]
+        format: LocateFormat,
+        /// Write output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print what's known about a chunk or a block: its NBT, decoded palettes, lighting and the
+    /// asset rule each block state matched
+    Dump {
+        source: PathBuf,
+        /// Dimension to read, e.g. `overworld`, `the_nether` or `mymod:custom`
+        #[arg(short, long, default_value = "overworld")]
+        dimension: DimensionID,
+        /// Dump the chunk at these chunk coordinates, given as `x,z`
+        #[arg(long, value_parser = parse_coords_xz, allow_hyphen_values = true, required_unless_present = "block", conflicts_with = "block")]
+        chunk: Option<CoordsXZ>,
+        /// Dump the block at these block coordinates, given as `x,y,z`
+        #[arg(long, value_parser = parse_coords_xyz, allow_hyphen_values = true)]
+        block: Option<CoordsXZY>,
+        #[arg(long, value_enum, default_value_t = DumpFormat::Snbt)]
+        format: DumpFormat,
+        /// Write output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the block states used in a world, the asset rule each one matched, and whether its
+    /// asset could be created
+    AssetCoverage {
+        source: PathBuf,
+        /// Dimension(s) to check, e.g. `overworld`, `the_nether` or `mymod:custom` [default: all]
+        #[arg(short, long)]
+        dimension: Vec<DimensionID>,
+        /// Only list block states whose asset failed
+        #[arg(long, default_value_t = false)]
+        failed_only: bool,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+        /// Write output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum OutputFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum LocateFormat {
+    Csv,
+    Json,
+    Geojson,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum DumpFormat {
+    Snbt,
+    Json,
+}
+
 fn parse_rgb_u8(s: &str) -> Result<Rgb8, String> {
     u32::from_str_radix(s, 16)
         .map_err(|err| err.to_string())
@@ -107,6 +259,16 @@ fn parse_coords_xz(s: &str) -> Result<CoordsXZ, String> {
     Ok(CoordsXZ::new(x, z))
 }
 
+fn parse_coords_xyz(s: &str) -> Result<CoordsXZY, String> {
+    let [x, y, z] = s
+        .split(',')
+        .map(|raw| i32::from_str(raw).map_err(|err| err.to_string()))
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .map_err(|_| "expected x,y,z format")?;
+    Ok(CoordsXZY::new(x, z, y))
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     tracing_subscriber::fmt()
@@ -126,6 +288,10 @@ fn main() -> Result<()> {
         .set_override_option(
             "background_color",
             cli.global.background.map(|c| u32::from(c)),
+        )?
+        .set_override_option(
+            "compute_missing_light",
+            cli.global.compute_light.then_some(true),
         )?;
     if let Ok(true) = fs::exists("mcrender.toml") {
         if cli.global.no_default_config {
@@ -140,7 +306,7 @@ fn main() -> Result<()> {
         builder = builder.add_source(config::File::new(config_path.as_str(), FileFormat::Toml));
     }
     let config = builder.build()?;
-    let settings = Settings::from_config(config)?;
+    let mut settings = Settings::from_config(config)?;
 
     match &cli.command {
         Commands::AssetPreview {
@@ -169,6 +335,7 @@ fn main() -> Result<()> {
                     .with_sky(0xF)
                     .build(),
                 render: rule.render.clone(),
+                block_entity: None,
             };
             let asset = asset_cache
                 .get_asset(&block_ref)
@@ -195,90 +362,725 @@ fn main() -> Result<()> {
             source,
             target,
             coords,
+            dimension,
         } => {
             let renderer = Renderer::new(&settings)?;
-            let world_info = mcrender::world::WorldInfo::try_from_path(source.clone())?;
+            let world_info = AnyWorld::open(source)?;
             log::debug!("world_info: {:?}", world_info);
             let dim_info = world_info
-                .get_dimension(&DimensionID::Overworld)
-                .ok_or(anyhow!("no such dimension"))?;
+                .get_dimension(dimension)
+                .ok_or(anyhow!("no such dimension: {}", dimension))?;
             let coords = RCoords(*coords);
             let mut dim_renderer =
                 DimensionRenderer::new(dim_info, renderer, ChunkBounds::single_region(coords));
+            dim_renderer.set_show_errors(cli.global.show_errors);
             let image = dim_renderer.render_region(coords)?;
             log::info!("writing output to {:?}", target);
             let output_image = ImageBuffer::from(&image);
             let mut output_file = File::create(target)?;
             output_image.write_to(&mut output_file, image::ImageFormat::Png)?;
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
         }
 
         Commands::RenderChunk {
             source,
             target,
             coords,
+            dimension,
         } => {
             let renderer = Renderer::new(&settings)?;
-            let world_info = mcrender::world::WorldInfo::try_from_path(source.clone())?;
+            let world_info = AnyWorld::open(source)?;
             log::debug!("world_info: {:?}", world_info);
             let dim_info = world_info
-                .get_dimension(&DimensionID::Overworld)
-                .ok_or(anyhow!("no such dimension"))?;
+                .get_dimension(dimension)
+                .ok_or(anyhow!("no such dimension: {}", dimension))?;
             let coords = CCoords(*coords);
             let mut dim_renderer =
                 DimensionRenderer::new(dim_info, renderer, ChunkBounds::single_chunk(coords));
+            dim_renderer.set_show_errors(cli.global.show_errors);
             let image = dim_renderer.render_chunk(coords)?;
             log::info!("writing output to {:?}", target);
             let output_image = ImageBuffer::from(&image);
             let mut output_file = File::create(target)?;
             output_image.write_to(&mut output_file, image::ImageFormat::Png)?;
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
         }
 
         Commands::RenderTiles {
             source,
             target,
             column,
+            full,
+            dimension,
+            all_dimensions,
+            watch,
+        } => {
+            let mut world_info = AnyWorld::open(source)?;
+            log::debug!("world_info: {:?}", world_info);
+            if *watch && !(source.is_dir() && matches!(world_info, AnyWorld::Java(_))) {
+                return Err(anyhow!("--watch needs a Java world directory"));
+            }
+            let dimensions = if *all_dimensions {
+                world_info.dimension_ids().into_iter().cloned().collect()
+            } else if dimension.is_empty() {
+                vec![DimensionID::Overworld]
+            } else {
+                dimension.clone()
+            };
+            // A single dimension is rendered directly into the target, but multiple dimensions
+            // each get their own tile tree, e.g. `<target>/minecraft/the_nether/tiles/...`
+            let separate_dirs = *all_dimensions || dimensions.len() > 1;
+            let mut rendered = Vec::new();
+            for dimension in dimensions.iter() {
+                let dim_info = world_info
+                    .get_dimension(dimension)
+                    .ok_or(anyhow!("no such dimension: {}", dimension))?;
+                let dim_target = if separate_dirs {
+                    dimension
+                        .namespaced_id()
+                        .split([':', '/'])
+                        .fold(target.clone(), |path, component| path.join(component))
+                } else {
+                    target.clone()
+                };
+                log::info!("rendering dimension {} to {:?}", dimension, &dim_target);
+                let manifest = render_tiles(
+                    &settings,
+                    dim_info,
+                    &dim_target,
+                    *column,
+                    *full,
+                    cli.global.show_errors,
+                )?;
+                rendered.push((dimension.clone(), dim_target, manifest));
+            }
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
+            if let (true, AnyWorld::Java(world_info)) = (*watch, &mut world_info) {
+                watch_tiles(&settings, world_info, rendered, cli.global.show_errors)?;
+            }
+        }
+
+        Commands::Scan {
+            source,
+            dimension,
+            json,
+            regions,
         } => {
-            let target_dir = target.join("tiles/0");
-            let renderer = Renderer::new(&settings)?;
             let world_info = mcrender::world::WorldInfo::try_from_path(source.clone())?;
             log::debug!("world_info: {:?}", world_info);
+            for id in dimension.iter() {
+                if world_info.get_dimension(id).is_none() {
+                    return Err(anyhow!("no such dimension: {}", id));
+                }
+            }
+            let dimensions: Vec<_> = dimension.iter().collect();
+            let scan = WorldScan::scan(&world_info, &dimensions, &settings);
+            if *json {
+                serde_json::to_writer_pretty(std::io::stdout().lock(), &scan)?;
+                println!();
+            } else {
+                print_scan(&scan, *regions);
+            }
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
+            let problem_count = scan.problem_count();
+            if problem_count > 0 {
+                return Err(anyhow!("found {} problem(s)", problem_count));
+            }
+        }
+
+        Commands::Stats {
+            source,
+            dimension,
+            by,
+            biomes,
+            names_only,
+            block,
+            format,
+            output,
+        } => {
+            let world_info = AnyWorld::open(source)?;
+            log::debug!("world_info: {:?}", world_info);
             let dim_info = world_info
-                .get_dimension(&DimensionID::Overworld)
-                .ok_or(anyhow!("no such dimension"))?;
-            let dim_renderer = DimensionRenderer::new(dim_info, renderer, Default::default());
-            // TODO: make blank-tile.png using background color
-            let col_range = match column {
-                Some(col) => *col..=*col,
-                None => dim_renderer.col_range(),
+                .get_dimension(dimension)
+                .ok_or(anyhow!("no such dimension: {}", dimension))?;
+            let options = StatsOptions {
+                grouping: *by,
+                biomes: *biomes,
+                names_only: *names_only,
+                blocks: block.clone(),
             };
-            col_range.into_par_iter().for_each(|col| {
-                // TODO: share a renderer but using RwLock (instead of Mutex) and less lock holding
-                //      during asset generation so there's less contention in AssetCache
-                let renderer = Renderer::new(&settings).unwrap();
-                let mut dim_renderer =
-                    DimensionRenderer::new(dim_info, renderer, Default::default());
-                dim_renderer
-                    .render_map_column(col, |coords, image| {
-                        let tile_target = target_dir.join(format!("{}/{}.png", coords.0, coords.1));
-                        let tile_target_dir = tile_target.parent().unwrap();
-                        log::info!(
-                            "writing tile ({}, {}) to {:?}",
-                            coords.0,
-                            coords.1,
-                            &tile_target
-                        );
-                        fs::create_dir_all(&tile_target_dir).unwrap();
-                        let output_image = ImageBuffer::from(image);
-                        let mut output_file = File::create(tile_target).unwrap();
-                        output_image
-                            .write_to(&mut output_file, image::ImageFormat::Png)
-                            .unwrap();
-                        true
-                    })
-                    .unwrap();
-            });
+            // Count blocks by their full block state, not just the properties used for rendering
+            settings.keep_block_properties = true;
+            let stats = BlockStats::collect(dim_info, &settings, options);
+            let writer = output_writer(output.as_deref())?;
+            match format {
+                OutputFormat::Csv => stats.write_csv(writer)?,
+                OutputFormat::Json => stats.write_json(writer)?,
+            }
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
+        }
+
+        Commands::Locate {
+            source,
+            dimension,
+            block,
+            biome,
+            entity,
+            bbox,
+            format,
+            output,
+        } => {
+            let query = LocateQuery {
+                blocks: block.clone(),
+                biomes: biome.clone(),
+                entities: entity.clone(),
+                bounds: *bbox,
+            };
+            if query.is_empty() {
+                return Err(anyhow!(
+                    "nothing to locate: use --block, --biome or --entity"
+                ));
+            }
+            let world_info = AnyWorld::open(source)?;
+            log::debug!("world_info: {:?}", world_info);
+            let dim_info = world_info
+                .get_dimension(dimension)
+                .ok_or(anyhow!("no such dimension: {}", dimension))?;
+            let mut locations = if query.blocks.is_empty() && query.biomes.is_empty() {
+                Locations::default()
+            } else {
+                Locations::collect(dim_info, &settings, &query)
+            };
+            if !query.entities.is_empty() {
+                let AnyWorld::Java(java_world) = &world_info else {
+                    return Err(anyhow!("entities can only be located in Java worlds"));
+                };
+                let java_dim = java_world
+                    .get_dimension(dimension)
+                    .ok_or(anyhow!("no such dimension: {}", dimension))?;
+                locations.add_entities(java_dim, &query);
+            }
+            log::info!("found {} location(s)", locations.locations.len());
+            let writer = output_writer(output.as_deref())?;
+            match format {
+                LocateFormat::Csv => locations.write_csv(writer)?,
+                LocateFormat::Json => locations.write_json(writer)?,
+                LocateFormat::Geojson => locations.write_geojson(writer)?,
+            }
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
+        }
+
+        Commands::Dump {
+            source,
+            dimension,
+            chunk,
+            block,
+            format,
+            output,
+        } => {
+            let world_info = AnyWorld::open(source)?;
+            log::debug!("world_info: {:?}", world_info);
+            let dim_info = world_info
+                .get_dimension(dimension)
+                .ok_or(anyhow!("no such dimension: {}", dimension))?;
+            let dump = match (chunk, block) {
+                (_, Some(block)) => dump_block(dim_info, BCoords(*block), &settings)?,
+                (Some(chunk), None) => dump_chunk(dim_info, CCoords(*chunk), &settings)?,
+                (None, None) => unreachable!(),
+            };
+            let mut writer = output_writer(output.as_deref())?;
+            match format {
+                DumpFormat::Snbt => writeln!(writer, "{}", to_snbt(&dump))?,
+                DumpFormat::Json => {
+                    serde_json::to_writer_pretty(&mut writer, &to_json(&dump))?;
+                    writeln!(writer)?;
+                }
+            }
+        }
+
+        Commands::AssetCoverage {
+            source,
+            dimension,
+            failed_only,
+            format,
+            output,
+        } => {
+            let asset_cache = AssetCache::new(&settings)?;
+            let world_info = AnyWorld::open(source)?;
+            log::debug!("world_info: {:?}", world_info);
+            let dimensions = if dimension.is_empty() {
+                world_info.dimension_ids().into_iter().cloned().collect()
+            } else {
+                dimension.clone()
+            };
+            let mut usage = BlockUsage::default();
+            for dimension in dimensions.iter() {
+                let dim_info = world_info
+                    .get_dimension(dimension)
+                    .ok_or(anyhow!("no such dimension: {}", dimension))?;
+                log::info!("collecting block states in dimension {}", dimension);
+                usage.merge(BlockUsage::collect(dim_info, &settings));
+            }
+            let mut coverage = usage.check_assets(&asset_cache);
+            log::info!(
+                "{} of {} block states failed",
+                coverage.failed_count(),
+                coverage.entries.len()
+            );
+            if *failed_only {
+                coverage.retain_failed();
+            }
+            let writer = output_writer(output.as_deref())?;
+            match format {
+                OutputFormat::Csv => coverage.write_csv(writer)?,
+                OutputFormat::Json => coverage.write_json(writer)?,
+            }
+            report_errors(
+                world_info.error_summary(),
+                cli.global.error_report.as_deref(),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Open `path` for writing output, or stdout if there's no path.
+fn output_writer(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    match path {
+        Some(path) => {
+            log::info!("writing output to {:?}", path);
+            Ok(Box::new(BufWriter::new(File::create(path)?)))
+        }
+        None => Ok(Box::new(std::io::stdout().lock())),
+    }
+}
+
+/// Print the results of [`WorldScan::scan()`] in a human-readable form.
+fn print_scan(scan: &WorldScan, all_regions: bool) {
+    fn counts<K: std::fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+        counts
+            .iter()
+            .map(|(key, count)| format!("{key} ({count})"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    match &scan.name {
+        Some(name) => println!("world: {:?} at {:?}", name, scan.path),
+        None => println!("world: {:?} (no level.dat)", scan.path),
+    }
+    if let Some(data_version) = scan.data_version {
+        println!("data version: {data_version}");
+    }
+    for dimension in scan.dimensions.iter() {
+        println!();
+        println!(
+            "{}: {} regions, {} chunks, y {}..{}",
+            dimension.dimension,
+            dimension.regions.len(),
+            dimension.stats.chunk_count,
+            dimension.height_range.min_y,
+            dimension.height_range.max_y(),
+        );
+        println!("  status: {}", counts(&dimension.stats.statuses));
+        println!(
+            "  data versions: {}",
+            counts(&dimension.stats.data_versions)
+        );
+        println!("  compression: {}", counts(&dimension.stats.compression));
+        for error in dimension.errors.iter() {
+            println!("  {error}");
+        }
+        for region in dimension.regions.iter() {
+            if !all_regions && region.problem_count() == 0 {
+                continue;
+            }
+            println!(
+                "  region {},{} ({}, {} bytes): {} chunks, {} problems",
+                region.coords.0,
+                region.coords.1,
+                region.format,
+                region.file_size,
+                region.stats.chunk_count,
+                region.problem_count(),
+            );
+            for problem in region.sector_problems.iter() {
+                println!("    {problem}");
+            }
+            for error in region.errors.iter() {
+                println!("    {error}");
+            }
+        }
+        println!("  {} problems", dimension.problem_count());
+    }
+}
+
+/// A Java or Bedrock Edition world, for the commands that can read either.
+#[derive(Debug)]
+enum AnyWorld {
+    Java(WorldInfo),
+    Bedrock(BedrockWorld),
+}
+
+impl AnyWorld {
+    /// Open the world at `path`, detecting which edition it's from.
+    fn open(path: &Path) -> Result<Self> {
+        let (storage, root) = open_storage(path)?;
+        if BedrockWorld::is_bedrock_world(storage.as_ref(), &root) {
+            Ok(Self::Bedrock(BedrockWorld::try_from_storage(
+                storage, root,
+            )?))
+        } else {
+            Ok(Self::Java(WorldInfo::try_from_storage(storage, root)?))
         }
     }
 
+    fn get_dimension(&self, id: &DimensionID) -> Option<&dyn WorldSource> {
+        match self {
+            Self::Java(world) => world.get_dimension(id).map(|dim| dim as &dyn WorldSource),
+            Self::Bedrock(world) => world.get_dimension(id).map(|dim| dim as &dyn WorldSource),
+        }
+    }
+
+    fn dimension_ids(&self) -> Vec<&DimensionID> {
+        match self {
+            Self::Java(world) => world.dimension_ids(),
+            Self::Bedrock(world) => world.dimension_ids(),
+        }
+    }
+
+    fn error_summary(&self) -> ErrorSummary {
+        match self {
+            Self::Java(world) => world.error_summary(),
+            Self::Bedrock(world) => world.error_summary(),
+        }
+    }
+}
+
+/// Log a summary of the errors found while reading the world, and write the full report to
+/// `report_path` as JSON if requested.
+fn report_errors(summary: ErrorSummary, report_path: Option<&Path>) -> Result<()> {
+    if summary.error_count > 0 {
+        let mut by_stage = BTreeMap::<ErrorStage, usize>::new();
+        for error in summary.errors.iter() {
+            *by_stage.entry(error.error.stage).or_default() += 1;
+        }
+        for (stage, count) in by_stage {
+            log::warn!("{} {} error(s)", count, stage);
+        }
+    }
+    match report_path {
+        None => {}
+        Some(path) if path == Path::new("-") => {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &summary)?;
+            println!();
+        }
+        Some(path) => {
+            log::info!("writing error report to {:?}", path);
+            serde_json::to_writer_pretty(File::create(path)?, &summary)?;
+        }
+    }
     Ok(())
 }
+
+/// Render the map tiles for a single dimension, read from `source`, into `target`. Returns the
+/// chunk timestamps the tiles are now up to date with.
+fn render_tiles(
+    settings: &Settings,
+    source: &dyn WorldSource,
+    target: &Path,
+    column: Option<i32>,
+    full: bool,
+    show_errors: bool,
+) -> Result<TimestampManifest> {
+    let manifest_path = target.join("manifest.dat");
+    let mut manifest = TimestampManifest::from_source(source)?;
+
+    // Unless told otherwise, only render tiles affected by chunks that changed since the last full
+    // render
+    let mut dirty_tiles: Option<BTreeMap<i32, BTreeSet<i32>>> = None;
+    let mut previous = None;
+    if !full && column.is_none() {
+        match TimestampManifest::load(&manifest_path) {
+            Ok(previous_manifest) => {
+                let changed_chunks = manifest.changed_chunks(&previous_manifest);
+                log::info!("{} chunks changed since last render", changed_chunks.len());
+                let mut tiles = BTreeMap::<i32, BTreeSet<i32>>::new();
+                for coords in changed_chunks {
+                    for tile in DimensionRenderer::tiles_for_chunk(
+                        source.height_range(),
+                        coords,
+                        settings.compute_missing_light,
+                    ) {
+                        tiles.entry(tile.0).or_default().insert(tile.1);
+                    }
+                }
+                dirty_tiles = Some(tiles);
+                previous = Some(previous_manifest);
+            }
+            Err(err) => {
+                log::info!("rendering all tiles, no previous manifest: {err}");
+            }
+        }
+    }
+
+    write_tiles(
+        settings,
+        source,
+        target,
+        column,
+        dirty_tiles.as_ref(),
+        show_errors,
+    )?;
+
+    // Only record the manifest if the whole map was brought up-to-date, except for the chunks that
+    // couldn't be read, which need rendering again next time
+    if column.is_none() {
+        manifest.keep_previous(&previous.unwrap_or_default(), &source.errors().errors());
+        log::info!("writing manifest to {:?}", &manifest_path);
+        fs::create_dir_all(target)?;
+        manifest.save(&manifest_path)?;
+    }
+    Ok(manifest)
+}
+
+/// Render tiles from `source` into `target`: only the tiles in `dirty_tiles` (columns to rows) if
+/// given, otherwise all tiles in `column` or the whole map.
+fn write_tiles(
+    settings: &Settings,
+    source: &dyn WorldSource,
+    target: &Path,
+    column: Option<i32>,
+    dirty_tiles: Option<&BTreeMap<i32, BTreeSet<i32>>>,
+    show_errors: bool,
+) -> Result<()> {
+    let target_dir = target.join("tiles/0");
+    let dim_renderer = DimensionRenderer::new(source, Renderer::new(settings)?, Default::default());
+
+    // TODO: make blank-tile.png using background color
+    let col_range = match column {
+        Some(col) => col..=col,
+        None => dim_renderer.col_range(),
+    };
+    col_range.into_par_iter().try_for_each(|col| {
+        let rows = match dirty_tiles {
+            None => None,
+            Some(tiles) => match tiles.get(&col) {
+                Some(rows) => Some(rows),
+                // Nothing to render in this column
+                None => return Ok(()),
+            },
+        };
+        // TODO: share a renderer but using RwLock (instead of Mutex) and less lock holding
+        //      during asset generation so there's less contention in AssetCache
+        let renderer = Renderer::new(settings)?;
+        let mut dim_renderer = DimensionRenderer::new(source, renderer, Default::default());
+        dim_renderer.set_show_errors(show_errors);
+        // The error that stopped the column being rendered, if a tile couldn't be written
+        let write_error = Cell::new(None);
+        let write_tile = |coords: Vec2D<i32>, image: &ImageBuf<Rgba8, &[u8]>| {
+            if let Some(rows) = rows
+                && !rows.contains(&coords.1)
+            {
+                // Tile is unchanged
+                return true;
+            }
+            let tile_target = target_dir.join(format!("{}/{}.png", coords.0, coords.1));
+            log::info!(
+                "writing tile ({}, {}) to {:?}",
+                coords.0,
+                coords.1,
+                &tile_target
+            );
+            match write_tile_image(&tile_target, image) {
+                Ok(()) => true,
+                Err(err) => {
+                    write_error.set(Some(err.context(format!("writing {:?}", tile_target))));
+                    false
+                }
+            }
+        };
+        match rows {
+            None => dim_renderer.render_map_column(col, write_tile),
+            Some(rows) => {
+                let row_range = *rows.first().unwrap()..=*rows.last().unwrap();
+                dim_renderer.render_map_tiles(col, row_range, write_tile)
+            }
+        }?;
+        write_error.into_inner().map_or(Ok(()), Err)
+    })
+}
+
+/// Write the tile `image` to `path` as a PNG, creating its directory if necessary.
+fn write_tile_image(path: &Path, image: &ImageBuf<Rgba8, &[u8]>) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let output_image = ImageBuffer::from(image);
+    let mut output_file = File::create(path)?;
+    output_image.write_to(&mut output_file, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+/// How long to wait for more changes after a region file changes, since the game saves many
+/// regions at once.
+const WATCH_SETTLE_TIME: Duration = Duration::from_secs(2);
+/// How long to wait before checking regions with chunks that couldn't be read again.
+const WATCH_RETRY_TIME: Duration = Duration::from_secs(5);
+
+/// Watch the region directories of each dimension in `targets` (dimension, tile directory and
+/// the timestamps its tiles are up to date with), and re-render the tiles affected by chunks that
+/// the game saves. Runs until interrupted.
+fn watch_tiles(
+    settings: &Settings,
+    world_info: &mut WorldInfo,
+    targets: Vec<(DimensionID, PathBuf, TimestampManifest)>,
+    show_errors: bool,
+) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mut trackers = BTreeMap::new();
+    let mut region_dirs = BTreeMap::new();
+    for (id, target, manifest) in targets {
+        let dim_info = world_info
+            .get_dimension(&id)
+            .ok_or(anyhow!("no such dimension: {}", id))?;
+        let region_dir = dim_info.path.join("region");
+        log::info!("watching {:?} for changes", &region_dir);
+        watcher.watch(&region_dir, RecursiveMode::NonRecursive)?;
+        region_dirs.insert(region_dir, id.clone());
+        trackers.insert(id, (target, ChangeTracker::new(manifest)));
+    }
+
+    loop {
+        // Wait for a region file to change, or for a retry to be due, then give the game time to
+        // finish saving
+        let retrying = trackers.values().any(|(_, tracker)| {
+            !tracker.pending_regions().is_empty() || tracker.ready_chunks().next().is_some()
+        });
+        let mut events = Vec::new();
+        let first = if retrying {
+            receiver.recv_timeout(WATCH_RETRY_TIME)
+        } else {
+            receiver.recv().map_err(RecvTimeoutError::from)
+        };
+        match first {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("file watcher stopped")),
+        }
+        if !events.is_empty() {
+            let deadline = Instant::now() + WATCH_SETTLE_TIME;
+            while let Ok(event) =
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                events.push(event);
+            }
+        }
+        let mut changed_paths = BTreeMap::<DimensionID, BTreeSet<PathBuf>>::new();
+        for event in events {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("error watching for changes: {err}");
+                    continue;
+                }
+            };
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                continue;
+            }
+            for path in event.paths {
+                if let Some(id) = path.parent().and_then(|dir| region_dirs.get(dir)) {
+                    changed_paths.entry(id.clone()).or_default().insert(path);
+                }
+            }
+        }
+
+        for (id, (target, tracker)) in trackers.iter_mut() {
+            let dim_info = world_info.dimensions.get_mut(id).unwrap();
+            let mut regions = BTreeSet::new();
+            for path in changed_paths.remove(id).unwrap_or_default() {
+                regions.extend(dim_info.refresh_region(&path));
+            }
+            for region_coords in tracker.pending_regions() {
+                // Reopen the region, in case it was still being written when it was last read
+                if let Some(path) = dim_info.get_region(region_coords).map(|r| r.path.clone()) {
+                    dim_info.refresh_region(&path);
+                }
+                regions.insert(region_coords);
+            }
+
+            for region_coords in regions {
+                if let Err(err) = tracker.update_region(dim_info, region_coords, settings) {
+                    log::warn!("failed to read region {region_coords}: {err}");
+                }
+            }
+
+            // Tiles that show a chunk that isn't readable yet wait until it is, and so do the
+            // ready chunks on them
+            let height_range = dim_info.height_range;
+            let compute_light = settings.compute_missing_light;
+            let waiting: BTreeSet<Vec2D<i32>> = tracker
+                .pending_chunks()
+                .flat_map(|coords| {
+                    DimensionRenderer::tiles_for_chunk(height_range, coords, compute_light)
+                })
+                .collect();
+            let mut dirty_tiles = BTreeMap::<i32, BTreeSet<i32>>::new();
+            let mut rendered = Vec::new();
+            for coords in tracker.ready_chunks() {
+                let tiles = DimensionRenderer::tiles_for_chunk(height_range, coords, compute_light);
+                if tiles.iter().any(|tile| waiting.contains(tile)) {
+                    continue;
+                }
+                for tile in tiles {
+                    dirty_tiles.entry(tile.0).or_default().insert(tile.1);
+                }
+                rendered.push(coords);
+            }
+            if rendered.is_empty() {
+                continue;
+            }
+            log::info!("{} chunks changed in dimension {}", rendered.len(), id);
+            // The chunks stay ready if their tiles can't be written, to be tried again later
+            if let Err(err) = write_tiles(
+                settings,
+                &*dim_info,
+                target,
+                None,
+                Some(&dirty_tiles),
+                show_errors,
+            ) {
+                log::warn!("failed to render tiles for dimension {id}: {err:#}");
+                continue;
+            }
+            for coords in rendered {
+                tracker.set_rendered(coords);
+            }
+            // Saved again after the next render if it can't be saved now
+            if let Err(err) = tracker.manifest().save(&target.join("manifest.dat")) {
+                log::warn!("failed to save manifest for dimension {id}: {err:#}");
+            }
+        }
+    }
+}
@@ -176,6 +176,18 @@ impl Rgba<u8> {
             f32::from(self[3]) / 255.0,
         ])
     }
+
+    /// Blend the colour towards its luma, leaving `saturation` (from 0 to 1) of the original
+    /// colour.
+    pub fn desaturate(&mut self, saturation: f32) {
+        let luma =
+            0.299 * f32::from(self[0]) + 0.587 * f32::from(self[1]) + 0.114 * f32::from(self[2]);
+        for c in &mut self[..3] {
+            *c = (luma + (f32::from(*c) - luma) * saturation)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
 }
 
 impl Rgba<f32> {
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::anyhow;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::world::{
+    CCoords, CIndex, ErrorStage, RCoords, REGION_CHUNK_COUNT, WorldError, WorldSource,
+};
+
+const MANIFEST_MAGIC: &[u8; 8] = b"MCRTS\x00\x00\x01";
+
+/// Chunk modification timestamps for a whole dimension, as recorded in the region headers at the
+/// time of a render. Comparing against the manifest from a previous render gives the set of chunks
+/// that need to be re-rendered.
+#[derive(Clone, Debug, Default)]
+pub struct TimestampManifest {
+    regions: BTreeMap<RCoords, Box<[u32; REGION_CHUNK_COUNT]>>,
+}
+
+impl TimestampManifest {
+    /// Collect the current chunk timestamps for every region in `source`.
+    pub fn from_source(source: &dyn WorldSource) -> anyhow::Result<Self> {
+        let mut regions = BTreeMap::new();
+        for region_coords in source.region_coords() {
+            if let Some(timestamps) = source.get_region_timestamps(region_coords)? {
+                regions.insert(region_coords, Box::new(timestamps));
+            }
+        }
+        Ok(Self { regions })
+    }
+
+    /// Read a manifest previously written by [`Self::save()`].
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let mut reader = flate2::read::GzDecoder::new(BufReader::new(file));
+        let mut magic = [0u8; MANIFEST_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MANIFEST_MAGIC {
+            return Err(anyhow!("not a timestamp manifest: {:?}", path));
+        }
+        let region_count = reader.read_u32::<BigEndian>()?;
+        let mut regions = BTreeMap::new();
+        for _ in 0..region_count {
+            let x = reader.read_i32::<BigEndian>()?;
+            let z = reader.read_i32::<BigEndian>()?;
+            let mut timestamps = Box::new([0u32; REGION_CHUNK_COUNT]);
+            reader.read_u32_into::<BigEndian>(&mut timestamps[..])?;
+            regions.insert(RCoords((x, z).into()), timestamps);
+        }
+        Ok(Self { regions })
+    }
+
+    /// Write the manifest to `path`, replacing any existing file.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path)?;
+        let mut writer =
+            flate2::write::GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
+        writer.write_all(MANIFEST_MAGIC)?;
+        writer.write_u32::<BigEndian>(self.regions.len() as u32)?;
+        for (region_coords, timestamps) in self.regions.iter() {
+            writer.write_i32::<BigEndian>(region_coords.x())?;
+            writer.write_i32::<BigEndian>(region_coords.z())?;
+            for &timestamp in timestamps.iter() {
+                writer.write_u32::<BigEndian>(timestamp)?;
+            }
+        }
+        writer.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Get the last modification time of the chunk at `coords`, or 0 if the chunk doesn't exist.
+    pub fn get(&self, coords: CCoords) -> u32 {
+        let (region_coords, chunk_index) = coords.to_region_coords();
+        self.regions
+            .get(&region_coords)
+            .map(|timestamps| timestamps[chunk_index.to_flat_index()])
+            .unwrap_or(0)
+    }
+
+    /// Get the coordinates of all chunks that have a different timestamp in `previous`, including
+    /// chunks that only exist in one of the manifests.
+    pub fn changed_chunks(&self, previous: &TimestampManifest) -> Vec<CCoords> {
+        let mut all_regions: Vec<RCoords> = self
+            .regions
+            .keys()
+            .chain(previous.regions.keys())
+            .copied()
+            .collect();
+        all_regions.sort();
+        all_regions.dedup();
+        all_regions
+            .into_iter()
+            .flat_map(|region_coords| {
+                let current = self.regions.get(&region_coords).map(|t| &**t);
+                previous.changed_in_region(region_coords, current)
+            })
+            .collect()
+    }
+
+    /// Get the coordinates of the chunks in the region at `region_coords` whose timestamps in
+    /// `timestamps` differ from this manifest. `None` means the region doesn't exist (any more).
+    pub fn changed_in_region(
+        &self,
+        region_coords: RCoords,
+        timestamps: Option<&[u32; REGION_CHUNK_COUNT]>,
+    ) -> Vec<CCoords> {
+        const EMPTY: [u32; REGION_CHUNK_COUNT] = [0; REGION_CHUNK_COUNT];
+        let current = timestamps.unwrap_or(&EMPTY);
+        let before = self.regions.get(&region_coords).map_or(&EMPTY, |t| &**t);
+        current
+            .iter()
+            .zip(before.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| CIndex::from_flat_index(i).to_chunk_coords(region_coords))
+            .collect()
+    }
+
+    /// Set the timestamp of the chunk at `coords`, adding its region if necessary.
+    pub fn set(&mut self, coords: CCoords, timestamp: u32) {
+        let (region_coords, chunk_index) = coords.to_region_coords();
+        let timestamps = self
+            .regions
+            .entry(region_coords)
+            .or_insert_with(|| Box::new([0; REGION_CHUNK_COUNT]));
+        timestamps[chunk_index.to_flat_index()] = timestamp;
+        if timestamps.iter().all(|&t| t == 0) {
+            self.regions.remove(&region_coords);
+        }
+    }
+
+    /// Put back the timestamps from `previous` for the chunks and regions that had `errors`, so
+    /// that they're still seen as changed, and rendered again once they can be read.
+    pub fn keep_previous(&mut self, previous: &TimestampManifest, errors: &[WorldError]) {
+        for error in errors {
+            // Entities aren't rendered
+            if error.stage == ErrorStage::Entities {
+                continue;
+            }
+            match (error.chunk, error.region) {
+                (Some(chunk), _) => {
+                    let coords = CCoords(chunk.into());
+                    self.set(coords, previous.get(coords));
+                }
+                (None, Some(region)) => {
+                    let region_coords = RCoords(region.into());
+                    match previous.regions.get(&region_coords) {
+                        Some(timestamps) => self.regions.insert(region_coords, timestamps.clone()),
+                        None => self.regions.remove(&region_coords),
+                    };
+                }
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_chunks() {
+        let mut previous = TimestampManifest::default();
+        let mut timestamps = Box::new([0u32; REGION_CHUNK_COUNT]);
+        timestamps[0] = 100;
+        timestamps[33] = 100;
+        previous
+            .regions
+            .insert(RCoords((0, 0).into()), timestamps.clone());
+        previous
+            .regions
+            .insert(RCoords((5, 5).into()), timestamps.clone());
+
+        let mut current = previous.clone();
+        // Modified chunk
+        current.regions.get_mut(&RCoords((0, 0).into())).unwrap()[33] = 200;
+        // Removed region
+        current.regions.remove(&RCoords((5, 5).into()));
+        // New region
+        current.regions.insert(RCoords((-1, 0).into()), timestamps);
+
+        assert_eq!(current.get(CCoords((1, 1).into())), 200);
+        assert_eq!(current.get(CCoords((-32, 0).into())), 100);
+        assert_eq!(current.get(CCoords((2, 2).into())), 0);
+        assert_eq!(
+            current.changed_chunks(&previous),
+            vec![
+                CCoords((-32, 0).into()),
+                CCoords((-31, 1).into()),
+                CCoords((1, 1).into()),
+                CCoords((160, 160).into()),
+                CCoords((161, 161).into()),
+            ]
+        );
+        assert!(current.changed_chunks(&current).is_empty());
+
+        current.set(CCoords((1, 1).into()), 100);
+        current.set(CCoords((-32, 0).into()), 0);
+        current.set(CCoords((-31, 1).into()), 0);
+        assert_eq!(current.get(CCoords((1, 1).into())), 100);
+        assert!(!current.regions.contains_key(&RCoords((-1, 0).into())));
+    }
+
+    #[test]
+    fn test_keep_previous() {
+        let mut previous = TimestampManifest::default();
+        previous.set(CCoords((0, 0).into()), 100);
+        previous.set(CCoords((40, 0).into()), 100);
+        let mut current = TimestampManifest::default();
+        for coords in [(0, 0), (1, 0), (2, 0), (40, 0), (41, 0)] {
+            current.set(CCoords(coords.into()), 200);
+        }
+        let error = |stage, chunk| WorldError {
+            stage,
+            region: None,
+            chunk,
+            path: None,
+            offset: None,
+            cause: "invalid".to_owned(),
+        };
+        current.keep_previous(
+            &previous,
+            &[
+                error(ErrorStage::ChunkParse, Some((0, 0))),
+                error(ErrorStage::ChunkRead, Some((1, 0))),
+                error(ErrorStage::Entities, Some((2, 0))),
+                WorldError {
+                    region: Some((1, 0)),
+                    ..error(ErrorStage::Region, None)
+                },
+            ],
+        );
+        assert_eq!(current.get(CCoords((0, 0).into())), 100);
+        // Chunks that weren't in the previous manifest are still changed next time
+        assert_eq!(current.get(CCoords((1, 0).into())), 0);
+        assert_eq!(current.get(CCoords((2, 0).into())), 200);
+        assert_eq!(current.get(CCoords((40, 0).into())), 100);
+        assert_eq!(current.get(CCoords((41, 0).into())), 0);
+    }
+}
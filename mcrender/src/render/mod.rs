@@ -1,6 +1,13 @@
+mod manifest;
 pub mod sprite;
 pub mod texture;
+mod watch;
 
+pub use manifest::TimestampManifest;
+pub use watch::{ChangeTracker, MAX_ATTEMPTS};
+
+use std::cmp::{max, min};
+use std::collections::BTreeSet;
 use std::ops::RangeInclusive;
 
 use anyhow::anyhow;
@@ -10,8 +17,8 @@ use crate::canvas::{ImageBuf, ImageMut, MultiplyOverlay, Pixel, Rgb8, Rgba8};
 use crate::coords::{CoordsXZ, Vec2D};
 use crate::settings::Settings;
 use crate::world::{
-    BlockInfo, CCoords, CHUNK_SIZE, Chunk, ChunkBounds, ChunkCache, DimensionInfo, RCoords,
-    REGION_SIZE, Section, WORLD_HEIGHT,
+    BIndex, BlockInfo, CCoords, CHUNK_SIZE, Chunk, ChunkBounds, ChunkCache, HeightRange, RCoords,
+    REGION_SIZE, Section, WorldSource,
 };
 
 /// Get the image width required to render an `x`-by-`z` area of blocks (regardless of how tall).
@@ -46,12 +53,30 @@ pub const SECTION_RENDER_HEIGHT: usize = render_height(
 
 /// The image width required to fully render a chunk. The same as for a section.
 pub const CHUNK_RENDER_WIDTH: usize = SECTION_RENDER_WIDTH;
-/// The image height required to fully render a chunk (16x16x384 blocks).
-pub const CHUNK_RENDER_HEIGHT: usize = render_height(
-    CHUNK_SIZE as usize,
-    CHUNK_SIZE as usize,
-    WORLD_HEIGHT as usize,
-);
+
+/// Get the image height required to fully render a chunk in a world with `height_range`.
+pub const fn chunk_render_height(height_range: HeightRange) -> usize {
+    render_height(
+        CHUNK_SIZE as usize,
+        CHUNK_SIZE as usize,
+        height_range.height as usize,
+    )
+}
+
+/// Get the height of the buffer that map tiles are rendered through in a world with
+/// `height_range`: enough for a whole chunk, wherever it starts within a tile.
+const fn tile_buffer_height(height_range: HeightRange) -> usize {
+    chunk_render_height(height_range) + 3 * (SECTION_RENDER_HEIGHT / 4)
+}
+
+/// Get the vertical offset, within the image for a chunk rendered for `height_range`, of the
+/// image for the section whose lowest block is at `base_y`.
+const fn section_render_offset(height_range: HeightRange, base_y: i32) -> isize {
+    // The top section is at the top of the chunk image, and each section below it is offset by the
+    // height of a section's vertical faces
+    let sections_from_top = (height_range.max_y() - base_y) / CHUNK_SIZE as i32 - 1;
+    sections_from_top as isize * (SECTION_RENDER_HEIGHT as isize / 2)
+}
 
 /// Within the space required to render a chunk section, the offset at which a sprite for the
 /// block at `(0, 0, 0)` (west-north-bottom) in section-relative block coordinates (block index)
@@ -135,10 +160,13 @@ impl<'s> Renderer<'s> {
         Ok(())
     }
 
+    /// Render `chunk` with the top of the chunk image at `(x, y)`, where the image is sized for
+    /// `height_range` (see [`chunk_render_height()`]).
     #[tracing::instrument(level = "debug", skip_all, fields(coords = %chunk.coords))]
     pub fn render_chunk_at<I>(
         &self,
         chunk: &Chunk,
+        height_range: HeightRange,
         output: &mut I,
         x: isize,
         y: isize,
@@ -147,10 +175,12 @@ impl<'s> Renderer<'s> {
         I: ImageMut,
         [I::Pixel]: MultiplyOverlay<Rgb8, [Rgba8]>,
     {
-        for (i, section) in chunk.sections.iter().enumerate() {
-            let y_offset =
-                CHUNK_RENDER_HEIGHT - SECTION_RENDER_HEIGHT - (i * SECTION_RENDER_HEIGHT / 2);
-            self.render_section_at(section, output, x, y + y_offset as isize)?;
+        for section in chunk.sections.iter() {
+            if !height_range.contains_section(section.base.y()) {
+                continue;
+            }
+            let y_offset = section_render_offset(height_range, section.base.y());
+            self.render_section_at(section, output, x, y + y_offset)?;
         }
         Ok(())
     }
@@ -158,6 +188,7 @@ impl<'s> Renderer<'s> {
     fn render_section_context_at<'c, I>(
         &self,
         section_context: &SectionContext<'c>,
+        saturation: Option<f32>,
         output: &mut I,
         x: isize,
         y: isize,
@@ -166,8 +197,15 @@ impl<'s> Renderer<'s> {
         I: ImageMut,
         [I::Pixel]: MultiplyOverlay<Rgb8, [Rgba8]>,
     {
+        let cover = self.section_cover(section_context);
+        if cover.is_section_hidden() {
+            return Ok(());
+        }
         for block_context in section_context.iter_blocks() {
             let block = &block_context.block;
+            if cover.is_block_hidden(block.index) {
+                continue;
+            }
             // Calculate where the sprite for the block would render
             let start = SECTION_ORIGIN
                 + BLOCK_OFFSET_X * block.index.x() as isize
@@ -184,7 +222,11 @@ impl<'s> Renderer<'s> {
                 continue;
             }
             // Try to get a sprite to render for the block
-            let Some(asset) = self.asset_cache.get_asset(&block) else {
+            let asset = match saturation {
+                Some(saturation) => self.asset_cache.get_desaturated_asset(block, saturation),
+                None => self.asset_cache.get_asset(block),
+            };
+            let Some(asset) = asset else {
                 continue;
             };
             // Render the sprite into the correct position
@@ -193,10 +235,19 @@ impl<'s> Renderer<'s> {
         Ok(())
     }
 
+    /// Find which blocks of `section_context` are hidden behind opaque blocks.
+    fn section_cover<'a, 'c>(
+        &self,
+        section_context: &'a SectionContext<'c>,
+    ) -> SectionCover<'a, 'c> {
+        SectionCover::new(section_context, |block| self.asset_cache.is_opaque(block))
+    }
+
     #[tracing::instrument(level = "debug", skip_all, fields(coords = %chunk_context.chunk.coords))]
     fn render_chunk_context_at<'c, I>(
         &self,
         chunk_context: &ChunkContext<'c>,
+        height_range: HeightRange,
         output: &mut I,
         x: isize,
         y: isize,
@@ -205,10 +256,14 @@ impl<'s> Renderer<'s> {
         I: ImageMut,
         [I::Pixel]: MultiplyOverlay<Rgb8, [Rgba8]>,
     {
-        for (i, section_context) in chunk_context.iter_sections().enumerate() {
-            let y_offset =
-                CHUNK_RENDER_HEIGHT - SECTION_RENDER_HEIGHT - (i * SECTION_RENDER_HEIGHT / 2);
-            self.render_section_context_at(&section_context, output, x, y + y_offset as isize)?;
+        let saturation = self.settings.chunk_status.saturation(chunk_context.chunk);
+        for section_context in chunk_context.iter_sections() {
+            let base_y = section_context.section.base.y();
+            if !height_range.contains_section(base_y) {
+                continue;
+            }
+            let y_offset = section_render_offset(height_range, base_y);
+            self.render_section_context_at(&section_context, saturation, output, x, y + y_offset)?;
         }
         Ok(())
     }
@@ -217,28 +272,41 @@ impl<'s> Renderer<'s> {
 pub struct DimensionRenderer<'i, 's> {
     chunk_cache: ChunkCache<'i, 's>,
     renderer: Renderer<'s>,
+    height_range: HeightRange,
+    chunk_render_height: usize,
+    tile_buffer_height: usize,
     col_range: RangeInclusive<i32>,
     row_range: RangeInclusive<i32>,
 }
 
 impl<'i, 's> DimensionRenderer<'i, 's> {
-    pub fn new(dim_info: &'i DimensionInfo, renderer: Renderer<'s>, bounds: ChunkBounds) -> Self {
-        let min_chunk = dim_info.min_region_coords().to_chunk_coords();
-        let max_chunk = dim_info.max_region_coords().to_chunk_coords();
+    pub fn new(source: &'i dyn WorldSource, renderer: Renderer<'s>, bounds: ChunkBounds) -> Self {
+        let height_range = source.height_range();
+        let chunk_render_height = chunk_render_height(height_range);
+        let min_chunk = source.min_region_coords().to_chunk_coords();
+        let max_chunk = source.max_region_coords().to_chunk_coords();
         let min_row = (min_chunk.x() + min_chunk.z()) / 4;
         let max_row = (max_chunk.x() + max_chunk.z()) / 4
-            + (CHUNK_RENDER_HEIGHT / SECTION_RENDER_HEIGHT) as i32;
+            + (chunk_render_height / SECTION_RENDER_HEIGHT) as i32;
         let min_col = (min_chunk.x() - max_chunk.z()) / 2;
         let max_col = (max_chunk.x() - min_chunk.z()) / 2;
 
         Self {
-            chunk_cache: ChunkCache::new(dim_info, renderer.settings, bounds, 100),
+            chunk_cache: ChunkCache::new(source, renderer.settings, bounds, 100),
             renderer,
+            height_range,
+            chunk_render_height,
+            tile_buffer_height: tile_buffer_height(height_range),
             col_range: min_col..=max_col,
             row_range: min_row..=max_row,
         }
     }
 
+    /// Set whether chunks that couldn't be read are drawn with an error pattern.
+    pub fn set_show_errors(&mut self, show_errors: bool) {
+        self.chunk_cache.set_show_errors(show_errors);
+    }
+
     pub fn col_range(&self) -> RangeInclusive<i32> {
         self.col_range.clone()
     }
@@ -253,8 +321,6 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
     }
 
     const TILE_BUFFER_WIDTH: usize = CHUNK_RENDER_WIDTH;
-    const TILE_BUFFER_HEIGHT: usize = CHUNK_RENDER_HEIGHT + 3 * (SECTION_RENDER_HEIGHT / 4);
-    const TILE_BUFFER_LEN_PIXELS: usize = Self::TILE_BUFFER_WIDTH * Self::TILE_BUFFER_HEIGHT;
     const TILE_BUFFER_SPLIT_PIXELS: usize = Self::TILE_BUFFER_WIDTH * SECTION_RENDER_HEIGHT;
     const TILE_BUFFER_SPLIT_CHANNELS: usize =
         Self::TILE_BUFFER_SPLIT_PIXELS * <Rgba8 as Pixel>::CHANNELS;
@@ -268,19 +334,91 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
         CoordsXZ::new(1, 2),
     ];
 
+    /// The number of consecutive tiles down a column that a single chunk can contribute to, in a
+    /// world with `height_range`.
+    fn tile_buffer_rows(height_range: HeightRange) -> i32 {
+        tile_buffer_height(height_range).div_ceil(SECTION_RENDER_HEIGHT) as i32
+    }
+
+    /// Get the `(col, row)` coordinates of every tile whose rendering depends on the chunk at
+    /// `coords` in a world with `height_range`, either because the chunk is drawn in the tile or
+    /// because it provides context (e.g. lighting) for a neighbouring chunk that is. With
+    /// `compute_light`, the light of every chunk around it can depend on it too (see
+    /// [`Chunk::compute_light()`](crate::world::Chunk::compute_light)).
+    pub fn tiles_for_chunk(
+        height_range: HeightRange,
+        coords: CCoords,
+        compute_light: bool,
+    ) -> BTreeSet<Vec2D<i32>> {
+        let tile_buffer_rows = Self::tile_buffer_rows(height_range);
+        let reach = if compute_light { 1 } else { 0 };
+        let mut rendered = BTreeSet::new();
+        for dz in -reach..=reach {
+            for dx in -reach..=reach {
+                let lit = CCoords(coords.0 + (dx, dz).into());
+                // The chunk itself, and the chunks that use it as their south or east neighbour
+                rendered.extend([
+                    lit,
+                    CCoords((lit.x(), lit.z() - 1).into()),
+                    CCoords((lit.x() - 1, lit.z()).into()),
+                ]);
+            }
+        }
+        let mut tiles = BTreeSet::new();
+        for chunk_coords in rendered {
+            for offset in Self::TILE_RENDER_CHUNK_OFFSETS.iter().copied() {
+                // Find the tile anchor that would have rendered this chunk at this offset, if any
+                let anchor = chunk_coords.0 - offset;
+                let sum = anchor.x() + anchor.z();
+                if sum.rem_euclid(4) != 0 {
+                    continue;
+                }
+                let row = sum / 4;
+                let col = (anchor.x() - anchor.z()) / 2;
+                for row in row..row + tile_buffer_rows {
+                    tiles.insert(Vec2D(col, row));
+                }
+            }
+        }
+        tiles
+    }
+
     #[tracing::instrument(level = "debug", skip_all, fields(col = %col))]
     pub fn render_map_column<F>(&mut self, col: i32, f: F) -> anyhow::Result<()>
+    where
+        F: Fn(Vec2D<i32>, &ImageBuf<Rgba8, &[u8]>) -> bool,
+    {
+        self.render_map_tiles(col, self.row_range(), f)
+    }
+
+    /// As [`Self::render_map_column()`], but only produces the tiles in `rows`, skipping as much
+    /// rendering as possible for rows outside that range.
+    #[tracing::instrument(level = "debug", skip_all, fields(col = %col, rows = ?rows))]
+    pub fn render_map_tiles<F>(
+        &mut self,
+        col: i32,
+        rows: RangeInclusive<i32>,
+        f: F,
+    ) -> anyhow::Result<()>
     where
         F: Fn(Vec2D<i32>, &ImageBuf<Rgba8, &[u8]>) -> bool,
     {
         let background = self.renderer.settings.background_color.to_rgba();
         let mut buffer = ImageBuf::<Rgba8>::from_pixel(
             Self::TILE_BUFFER_WIDTH,
-            Self::TILE_BUFFER_HEIGHT,
+            self.tile_buffer_height,
             background,
         );
+        let buffer_len_pixels = Self::TILE_BUFFER_WIDTH * self.tile_buffer_height;
 
-        for row in self.row_range() {
+        // Start early enough that all chunks that overlap the first requested tile are rendered
+        let first_row = max(
+            *self.row_range.start(),
+            rows.start() - (Self::tile_buffer_rows(self.height_range) - 1),
+        );
+        let last_row = min(*self.row_range.end(), *rows.end());
+
+        for row in first_row..=last_row {
             // Figure out the chunk coords of the next 6 chunks that need to be rendered
             // to cover the next tile down the column, and render them if they exist
             let anchor = CoordsXZ::new(2 * row + col, 2 * row - col);
@@ -300,6 +438,7 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
                 };
                 self.renderer.render_chunk_context_at(
                     &chunk_context,
+                    self.height_range,
                     &mut buffer,
                     image_offset.0,
                     image_offset.1,
@@ -310,24 +449,26 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
             }
 
             // TODO: optimise out tiles that don't show anything
-            // Create tile image from top section of buffer
-            let image = ImageBuf::from_raw(
-                Self::TILE_BUFFER_WIDTH,
-                SECTION_RENDER_HEIGHT,
-                &buffer.channels()[..Self::TILE_BUFFER_SPLIT_CHANNELS],
-            )
-            .unwrap();
-            // Pass the tile to the callback
-            let keep_rendering = f((col, row).into(), &image);
-            if !keep_rendering {
-                // Stop rendering if the callback said they're done
-                break;
+            if rows.contains(&row) {
+                // Create tile image from top section of buffer
+                let image = ImageBuf::from_raw(
+                    Self::TILE_BUFFER_WIDTH,
+                    SECTION_RENDER_HEIGHT,
+                    &buffer.channels()[..Self::TILE_BUFFER_SPLIT_CHANNELS],
+                )
+                .unwrap();
+                // Pass the tile to the callback
+                let keep_rendering = f((col, row).into(), &image);
+                if !keep_rendering {
+                    // Stop rendering if the callback said they're done
+                    break;
+                }
             }
             // Shift the buffer up to prepare for next tile down
             buffer
                 .channels_mut()
                 .copy_within(Self::TILE_BUFFER_SPLIT_CHANNELS.., 0);
-            buffer.pixels_mut()[Self::TILE_BUFFER_LEN_PIXELS - Self::TILE_BUFFER_SPLIT_PIXELS..]
+            buffer.pixels_mut()[buffer_len_pixels - Self::TILE_BUFFER_SPLIT_PIXELS..]
                 .fill(background);
         }
 
@@ -337,11 +478,6 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
     const REGION_SIZE_BLOCKS: usize = (REGION_SIZE * CHUNK_SIZE) as usize;
     const REGION_RENDER_WIDTH: usize =
         render_width(Self::REGION_SIZE_BLOCKS, Self::REGION_SIZE_BLOCKS);
-    const REGION_RENDER_HEIGHT: usize = render_height(
-        Self::REGION_SIZE_BLOCKS,
-        Self::REGION_SIZE_BLOCKS,
-        WORLD_HEIGHT as usize,
-    );
     const REGION_ORIGIN: Vec2D<isize> = Vec2D(
         Self::REGION_RENDER_WIDTH as isize / 2 - CHUNK_RENDER_WIDTH as isize / 2,
         0,
@@ -351,7 +487,11 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
     pub fn render_region(&mut self, coords: RCoords) -> anyhow::Result<ImageBuf<Rgba8>> {
         let mut output = ImageBuf::<Rgba8, Vec<_>>::from_pixel(
             Self::REGION_RENDER_WIDTH,
-            Self::REGION_RENDER_HEIGHT,
+            render_height(
+                Self::REGION_SIZE_BLOCKS,
+                Self::REGION_SIZE_BLOCKS,
+                self.height_range.height as usize,
+            ),
             self.renderer.settings.background_color.to_rgba(),
         );
         let base = coords.to_chunk_coords();
@@ -372,6 +512,7 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
                 };
                 self.renderer.render_chunk_context_at(
                     &chunk_context,
+                    self.height_range,
                     &mut output,
                     image_offset.0,
                     image_offset.1,
@@ -387,7 +528,7 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
     pub fn render_chunk(&mut self, coords: CCoords) -> anyhow::Result<ImageBuf<Rgba8>> {
         let mut output = ImageBuf::<Rgba8, Vec<_>>::from_pixel(
             CHUNK_RENDER_WIDTH,
-            CHUNK_RENDER_HEIGHT,
+            self.chunk_render_height,
             self.renderer.settings.background_color.to_rgba(),
         );
         let chunk = self
@@ -401,8 +542,13 @@ impl<'i, 's> DimensionRenderer<'i, 's> {
             south: south.as_ref().map(|c| c.as_ref()),
             east: east.as_ref().map(|c| c.as_ref()),
         };
-        self.renderer
-            .render_chunk_context_at(&chunk_context, &mut output, 0, 0)?;
+        self.renderer.render_chunk_context_at(
+            &chunk_context,
+            self.height_range,
+            &mut output,
+            0,
+            0,
+        )?;
         // self.renderer.render_chunk_at(&chunk, &mut output, 0, 0)?;
         Ok(output)
     }
@@ -415,15 +561,25 @@ struct ChunkContext<'c> {
 }
 
 impl<'c> ChunkContext<'c> {
+    /// Iterate over the sections that might have something to render, skipping those above the
+    /// highest block.
     fn iter_sections(&self) -> impl Iterator<Item = SectionContext<'c>> {
-        self.chunk.sections.iter().enumerate().map(|(i, section)| {
-            let south = self.south.map(|chunk| &chunk.sections[i]);
-            let east = self.east.map(|chunk| &chunk.sections[i]);
-            let up = if i + 1 < self.chunk.sections.len() {
-                Some(&self.chunk.sections[i + 1])
-            } else {
-                None
-            };
+        let surface_y = self
+            .chunk
+            .heightmaps
+            .world_surface
+            .as_ref()
+            .map_or(i32::MAX, |heightmap| heightmap.max());
+        let sections = self
+            .chunk
+            .sections
+            .iter()
+            .take_while(move |section| section.base.y() < surface_y);
+        sections.map(|section| {
+            let base_y = section.base.y();
+            let south = self.south.and_then(|chunk| chunk.get_section(base_y));
+            let east = self.east.and_then(|chunk| chunk.get_section(base_y));
+            let up = self.chunk.get_section(base_y + CHUNK_SIZE as i32);
             SectionContext {
                 section,
                 south,
@@ -495,3 +651,227 @@ impl<'c> BlockContext<'c> {
         }
     }
 }
+
+/// Which blocks of a section can't be seen in the isometric view. A block is hidden when the three
+/// faces that would be visible (top, south and east) are all against blocks that render as opaque
+/// cubes, which are drawn over it.
+struct SectionCover<'a, 'c> {
+    context: &'a SectionContext<'c>,
+    /// Whether each palette entry of the section, and of the sections above, south and east of
+    /// it, renders as an opaque cube. Empty if there's no such section.
+    section: Vec<bool>,
+    up: Vec<bool>,
+    south: Vec<bool>,
+    east: Vec<bool>,
+}
+
+impl<'a, 'c> SectionCover<'a, 'c> {
+    /// Find which blocks in `context` are hidden, using `is_opaque` to check whether a block
+    /// renders as an opaque cube.
+    fn new(context: &'a SectionContext<'c>, is_opaque: impl Fn(&BlockInfo) -> bool) -> Self {
+        let palette_opaque = |section: Option<&Section>| -> Vec<bool> {
+            let Some(section) = section else {
+                return Vec::new();
+            };
+            let biome = section.biome_palette[0].clone();
+            section
+                .block_palette
+                .iter()
+                .map(|(state, rule)| {
+                    is_opaque(&BlockInfo {
+                        index: Default::default(),
+                        state,
+                        biome: biome.clone(),
+                        lighting: Default::default(),
+                        render: rule.render.clone(),
+                        block_entity: None,
+                    })
+                })
+                .collect()
+        };
+        Self {
+            context,
+            section: palette_opaque(Some(context.section)),
+            up: palette_opaque(context.up),
+            south: palette_opaque(context.south),
+            east: palette_opaque(context.east),
+        }
+    }
+
+    /// Check whether the whole section is hidden, because it and its neighbours are entirely
+    /// opaque cubes.
+    fn is_section_hidden(&self) -> bool {
+        [&self.section, &self.up, &self.south, &self.east]
+            .into_iter()
+            .all(|opaque| !opaque.is_empty() && opaque.iter().all(|&opaque| opaque))
+    }
+
+    fn is_block_hidden(&self, index: BIndex) -> bool {
+        let is_opaque = |section: Option<&Section>, opaque: &[bool], index: BIndex| {
+            section.is_some_and(|section| opaque[section.palette_index(index)])
+        };
+        let context = self.context;
+        let (x, z, y) = (index.x(), index.z(), index.y());
+        let up = if y + 1 < CHUNK_SIZE {
+            is_opaque(Some(context.section), &self.section, index.up())
+        } else {
+            is_opaque(context.up, &self.up, BIndex((x, z, 0).into()))
+        };
+        let south = || {
+            if z + 1 < CHUNK_SIZE {
+                is_opaque(Some(context.section), &self.section, index.south())
+            } else {
+                is_opaque(context.south, &self.south, BIndex((x, 0, y).into()))
+            }
+        };
+        let east = || {
+            if x + 1 < CHUNK_SIZE {
+                is_opaque(Some(context.section), &self.section, index.east())
+            } else {
+                is_opaque(context.east, &self.east, BIndex((0, z, y).into()))
+            }
+        };
+        up && south() && east()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::util::intern_str;
+    use crate::world::{BlockState, ChunkBuilder, MemorySource, SectionBuilder};
+
+    /// Write a block texture, with a transparent hole in the middle unless it's `opaque`.
+    fn write_texture(dir: &Path, name: &str, opaque: bool) {
+        let texture = image::RgbaImage::from_fn(16, 16, |x, y| {
+            let hole = !opaque && (4..12).contains(&x) && (4..12).contains(&y);
+            image::Rgba([128, 128, 128, if hole { 0 } else { 255 }])
+        });
+        texture.save(dir.join(format!("{name}.png"))).unwrap();
+    }
+
+    /// Create an assets directory with textures for stone and glass, and settings that use it.
+    fn test_assets() -> (tempfile::TempDir, Settings) {
+        let assets_dir = tempfile::tempdir().unwrap();
+        std::fs::write(assets_dir.path().join(".mcassetsroot"), "").unwrap();
+        let texture_dir = assets_dir.path().join("minecraft/textures/block");
+        std::fs::create_dir_all(&texture_dir).unwrap();
+        write_texture(&texture_dir, "stone", true);
+        write_texture(&texture_dir, "glass", false);
+        let config = Settings::config_builder(false)
+            .set_override("assets_path", assets_dir.path().to_str().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        (assets_dir, Settings::from_config(config).unwrap())
+    }
+
+    #[test]
+    fn test_desaturated_asset() {
+        let (_assets_dir, settings) = test_assets();
+        let asset_cache = AssetCache::new(&settings).unwrap();
+        let state = BlockState::new(intern_str("minecraft:stone"));
+        let block = BlockInfo {
+            index: Default::default(),
+            state: &state,
+            biome: intern_str("minecraft:plains"),
+            lighting: Default::default(),
+            render: settings.asset_rules.get_rule(&state.name).render.clone(),
+            block_entity: None,
+        };
+        let desaturated = asset_cache.get_desaturated_asset(&block, 0.5).unwrap();
+        assert!(desaturated.opaque);
+        assert!(Arc::ptr_eq(
+            &desaturated,
+            &asset_cache.get_desaturated_asset(&block, 0.5).unwrap()
+        ));
+        let other = asset_cache.get_desaturated_asset(&block, 0.25).unwrap();
+        assert!(!Arc::ptr_eq(&desaturated, &other));
+        assert!(!Arc::ptr_eq(
+            &desaturated,
+            &asset_cache.get_asset(&block).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_tiles_for_chunk() {
+        let height_range = HeightRange::OVERWORLD;
+        let coords = CCoords((0, 0).into());
+        let tiles = DimensionRenderer::tiles_for_chunk(height_range, coords, false);
+        assert!(tiles.contains(&Vec2D(0, 0)));
+        // With computed light, the chunk also changes the tiles of the chunks to its south and
+        // east, through their light
+        let lit_tiles = DimensionRenderer::tiles_for_chunk(height_range, coords, true);
+        let south_east = CCoords((1, 1).into());
+        let south_east_tiles = DimensionRenderer::tiles_for_chunk(height_range, south_east, false);
+        assert!(!tiles.is_superset(&south_east_tiles));
+        assert!(lit_tiles.is_superset(&south_east_tiles));
+        assert!(lit_tiles.is_superset(&tiles));
+    }
+
+    #[test]
+    fn test_section_cover() {
+        let (_assets_dir, settings) = test_assets();
+        let renderer = Renderer::new(&settings).unwrap();
+
+        let height_range = HeightRange::new(0, 64);
+        let load = |x, z, glass: Option<(u32, i32, u32)>| {
+            let coords = CCoords((x, z).into());
+            let mut builder = ChunkBuilder::new(coords, height_range);
+            // Whole sections, so that air isn't left in their palettes
+            for y in (0..64).step_by(CHUNK_SIZE as usize) {
+                *builder.section_mut(y) = SectionBuilder::new(
+                    BlockState::new(intern_str("minecraft:stone")),
+                    intern_str("minecraft:plains"),
+                );
+            }
+            if let Some((x, y, z)) = glass {
+                builder.set_block(x, y, z, BlockState::new(intern_str("minecraft:glass")));
+            }
+            let mut source = MemorySource::new(height_range);
+            source.insert_chunk(coords, builder.to_nbt().unwrap(), 100);
+            source.load_chunk(coords, &settings).unwrap().unwrap()
+        };
+        let chunk = load(0, 0, Some((5, 36, 5)));
+        let south = load(0, 1, None);
+        let east = load(1, 0, None);
+        let chunk_context = ChunkContext {
+            chunk: &chunk,
+            south: Some(&south),
+            east: Some(&east),
+        };
+        let sections: Vec<_> = chunk_context.iter_sections().collect();
+        assert_eq!(sections.len(), 4);
+
+        // Surrounded by opaque blocks on every visible side
+        // Glass doesn't hide the blocks behind it
+        let cover = renderer.section_cover(&sections[2]);
+        assert!(!cover.is_section_hidden());
+        assert!(cover.is_block_hidden(BIndex((5, 5, 2).into())));
+        assert!(!cover.is_block_hidden(BIndex((5, 5, 3).into())));
+        assert!(!cover.is_block_hidden(BIndex((5, 4, 4).into())));
+        assert!(!cover.is_block_hidden(BIndex((4, 5, 4).into())));
+
+        // Nothing above the top section
+        let cover = renderer.section_cover(&sections[3]);
+        assert!(!cover.is_section_hidden());
+        assert!(cover.is_block_hidden(BIndex((5, 5, 14).into())));
+        assert!(!cover.is_block_hidden(BIndex((5, 5, 15).into())));
+
+        // Without neighbouring chunks, the south and east edges are visible
+        let chunk_context = ChunkContext {
+            chunk: &chunk,
+            south: None,
+            east: None,
+        };
+        let section = chunk_context.iter_sections().next().unwrap();
+        let cover = renderer.section_cover(&section);
+        assert!(!cover.is_section_hidden());
+        assert!(!cover.is_block_hidden(BIndex((15, 5, 5).into())));
+        assert!(!cover.is_block_hidden(BIndex((5, 15, 5).into())));
+        assert!(cover.is_block_hidden(BIndex((14, 14, 5).into())));
+    }
+}
@@ -10,15 +10,23 @@ use crate::canvas::{ImageBuf, ImageMut, Multiply, MultiplyOverlay, Pixel, Rgb, R
 use crate::render::BlockContext;
 use crate::render::texture::TextureCache;
 
-pub struct Sprite(pub Vec<SpriteLayer>);
+pub struct Sprite {
+    pub layers: Vec<SpriteLayer>,
+    /// Whether the sprite is a cube without any transparent pixels, which hides whatever is
+    /// behind it.
+    pub opaque: bool,
+}
 
 impl Sprite {
     pub fn new() -> Sprite {
-        Sprite(Vec::new())
+        Self::with_capacity(0)
     }
 
     pub fn with_capacity(capacity: usize) -> Sprite {
-        Sprite(Vec::with_capacity(capacity))
+        Sprite {
+            layers: Vec::with_capacity(capacity),
+            opaque: false,
+        }
     }
 
     pub fn add_new_layer<B: Into<Arc<SpriteBuffer>>>(
@@ -26,18 +34,37 @@ impl Sprite {
         buffer: B,
         render_mode: RenderMode,
     ) {
-        self.0.push(SpriteLayer {
+        self.layers.push(SpriteLayer {
             buffer: buffer.into(),
             render_mode,
         });
     }
 
+    /// Make a copy of the sprite with its colour saturation scaled by `saturation`.
+    pub fn desaturated(&self, saturation: f32) -> Sprite {
+        let layers = self.layers.iter().map(|layer| {
+            let mut buffer = (*layer.buffer).clone();
+            buffer
+                .pixels_mut()
+                .iter_mut()
+                .for_each(|p| p.desaturate(saturation));
+            SpriteLayer {
+                buffer: Arc::new(buffer),
+                render_mode: layer.render_mode,
+            }
+        });
+        Sprite {
+            layers: layers.collect(),
+            opaque: self.opaque,
+        }
+    }
+
     pub fn render_at<'c, I>(&self, output: &mut I, x: isize, y: isize, context: &BlockContext<'c>)
     where
         I: ImageMut,
         [I::Pixel]: MultiplyOverlay<Rgb8, [Rgba8]>,
     {
-        for layer in self.0.iter() {
+        for layer in self.layers.iter() {
             layer.render_at(output, x, y, context);
         }
     }
@@ -357,6 +384,15 @@ impl PartialSpriteCache {
         self.get_tinted(name, aspect, None)
     }
 
+    /// Check whether texture `name` has no transparent or translucent pixels.
+    pub fn is_opaque(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .textures
+            .get(name)?
+            .pixels()
+            .all(|pixel| pixel[3] == 255))
+    }
+
     pub fn get_tinted(
         &self,
         name: &str,
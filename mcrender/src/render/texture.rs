@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use image::GenericImageView;
 use parking_lot::RwLock;
 
@@ -27,7 +28,9 @@ impl TextureCache {
 
         // Read the texture from the file, but don't hold the lock while we do so
         let texture_path = self.path.join(format!("{name}.png"));
-        let original_texture = image::open(&texture_path)?.to_rgba8();
+        let original_texture = image::open(&texture_path)
+            .map_err(|err| anyhow!("failed to load texture {:?}: {}", name, err))?
+            .to_rgba8();
         let texture = original_texture.view(0, 0, 16, 16).to_image();
 
         // Get the write lock
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use crate::render::TimestampManifest;
+use crate::settings::Settings;
+use crate::world::{CCoords, RCoords, WorldSource};
+
+/// How many times a changed chunk (or region header) that can't be read is checked before giving
+/// up on waiting for it.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Tracks which chunks of a dimension need to be re-rendered as the game saves it.
+///
+/// A region file can be read while the game is part way through writing it, so a changed chunk
+/// might not be readable yet. Such chunks keep their old timestamp in the manifest, so they're
+/// still seen as changed next time the region is checked, until they can be read or have failed
+/// [`MAX_ATTEMPTS`] times, at which point they're rendered anyway. Chunks that are ready only get
+/// their new timestamp in the manifest once they've been rendered.
+#[derive(Debug)]
+pub struct ChangeTracker {
+    manifest: TimestampManifest,
+    /// Changed chunks that are ready to render, with their new timestamps.
+    ready: BTreeMap<CCoords, u32>,
+    /// Changed chunks that couldn't be read, with the number of failed attempts.
+    pending: BTreeMap<CCoords, u32>,
+    /// Regions whose header couldn't be read, with the number of failed attempts.
+    pending_regions: BTreeMap<RCoords, u32>,
+}
+
+impl ChangeTracker {
+    /// Start tracking changes since `manifest` was recorded.
+    pub fn new(manifest: TimestampManifest) -> Self {
+        Self {
+            manifest,
+            ready: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            pending_regions: BTreeMap::new(),
+        }
+    }
+
+    /// The timestamps of every chunk that has been rendered.
+    pub fn manifest(&self) -> &TimestampManifest {
+        &self.manifest
+    }
+
+    /// Get the regions that are waiting to be checked again, because they or some of their chunks
+    /// couldn't be read.
+    pub fn pending_regions(&self) -> Vec<RCoords> {
+        let mut regions: Vec<_> = self
+            .pending
+            .keys()
+            .map(|coords| coords.to_region_coords().0)
+            .chain(self.pending_regions.keys().copied())
+            .collect();
+        regions.sort();
+        regions.dedup();
+        regions
+    }
+
+    /// Check the region at `region_coords` for chunks that changed since they were last rendered.
+    /// Returns the chunks that became ready to render: changed chunks that could be read, or were
+    /// removed. Chunks that can't be read yet, or all chunks if the region header can't be read
+    /// yet, are left for a later call.
+    pub fn update_region(
+        &mut self,
+        source: &dyn WorldSource,
+        region_coords: RCoords,
+        settings: &Settings,
+    ) -> anyhow::Result<Vec<CCoords>> {
+        let timestamps = match source.get_region_timestamps(region_coords) {
+            Ok(timestamps) => {
+                self.pending_regions.remove(&region_coords);
+                timestamps
+            }
+            Err(err) => {
+                let attempts = self.pending_regions.entry(region_coords).or_default();
+                *attempts += 1;
+                if *attempts < MAX_ATTEMPTS {
+                    log::debug!("region {region_coords} isn't readable yet: {err}");
+                    return Ok(Vec::new());
+                }
+                self.pending_regions.remove(&region_coords);
+                return Err(err);
+            }
+        };
+        let changed = self
+            .manifest
+            .changed_in_region(region_coords, timestamps.as_ref());
+        let mut ready = Vec::new();
+        for &coords in changed.iter() {
+            let (_, chunk_index) = coords.to_region_coords();
+            let timestamp = timestamps.map_or(0, |t| t[chunk_index.to_flat_index()]);
+            if self.ready.get(&coords) == Some(&timestamp) {
+                continue;
+            }
+            if let Err((stage, err)) = source.load_chunk(coords, settings) {
+                let attempts = self.pending.entry(coords).or_default();
+                *attempts += 1;
+                if *attempts < MAX_ATTEMPTS {
+                    log::debug!("chunk {coords} isn't readable yet ({stage}): {err}");
+                    continue;
+                }
+                // Probably really broken, rather than part way through being written
+                source.report_chunk_error(coords, stage, &err);
+            }
+            self.pending.remove(&coords);
+            self.ready.insert(coords, timestamp);
+            ready.push(coords);
+        }
+        // A pending chunk that's no longer changed has been rewritten with its old timestamp
+        self.pending.retain(|coords, _| {
+            coords.to_region_coords().0 != region_coords || changed.contains(coords)
+        });
+        Ok(ready)
+    }
+
+    /// Get the chunks that are ready to render, but haven't been rendered yet.
+    pub fn ready_chunks(&self) -> impl Iterator<Item = CCoords> + '_ {
+        self.ready.keys().copied()
+    }
+
+    /// Record that the chunk at `coords` has been rendered, so its new timestamp is saved in the
+    /// manifest.
+    pub fn set_rendered(&mut self, coords: CCoords) {
+        if let Some(timestamp) = self.ready.remove(&coords) {
+            self.manifest.set(coords, timestamp);
+        }
+    }
+
+    /// Get the chunks that have changed, but haven't been readable yet.
+    pub fn pending_chunks(&self) -> impl Iterator<Item = CCoords> + '_ {
+        self.pending.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::test_settings;
+    use crate::world::{ChunkBuilder, HeightRange, MemorySource};
+
+    #[test]
+    fn test_change_tracker() {
+        let settings = test_settings();
+        let height_range = HeightRange::OVERWORLD;
+        let good = |x, z| {
+            ChunkBuilder::new(CCoords((x, z).into()), height_range)
+                .to_nbt()
+                .unwrap()
+        };
+        let mut source = MemorySource::new(height_range);
+        source.insert_chunk(CCoords((0, 0).into()), good(0, 0), 100);
+        let region = RCoords((0, 0).into());
+        let mut tracker = ChangeTracker::new(TimestampManifest::default());
+        assert_eq!(
+            tracker.update_region(&source, region, &settings).unwrap(),
+            vec![CCoords((0, 0).into())]
+        );
+        assert!(
+            tracker
+                .update_region(&source, region, &settings)
+                .unwrap()
+                .is_empty()
+        );
+        // The timestamp is only saved once the chunk has been rendered
+        assert_eq!(tracker.manifest().get(CCoords((0, 0).into())), 0);
+        tracker.set_rendered(CCoords((0, 0).into()));
+        assert_eq!(tracker.manifest().get(CCoords((0, 0).into())), 100);
+        assert_eq!(tracker.ready_chunks().count(), 0);
+
+        // A chunk that's half written is retried, until it's readable
+        source.insert_chunk(CCoords((1, 0).into()), b"\x0a\x00".to_vec(), 200);
+        assert!(
+            tracker
+                .update_region(&source, region, &settings)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            tracker.pending_chunks().collect::<Vec<_>>(),
+            vec![CCoords((1, 0).into())]
+        );
+        assert_eq!(tracker.pending_regions(), vec![region]);
+        assert_eq!(tracker.manifest().get(CCoords((1, 0).into())), 0);
+        source.insert_chunk(CCoords((1, 0).into()), good(1, 0), 200);
+        assert_eq!(
+            tracker.update_region(&source, region, &settings).unwrap(),
+            vec![CCoords((1, 0).into())]
+        );
+        assert_eq!(tracker.pending_chunks().count(), 0);
+        assert_eq!(
+            tracker.ready_chunks().collect::<Vec<_>>(),
+            vec![CCoords((1, 0).into())]
+        );
+        tracker.set_rendered(CCoords((1, 0).into()));
+        assert_eq!(tracker.manifest().get(CCoords((1, 0).into())), 200);
+
+        // A chunk that never becomes readable is eventually rendered anyway
+        source.insert_chunk(CCoords((2, 0).into()), b"garbage".to_vec(), 300);
+        for _ in 1..MAX_ATTEMPTS {
+            assert!(
+                tracker
+                    .update_region(&source, region, &settings)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+        assert_eq!(
+            tracker.update_region(&source, region, &settings).unwrap(),
+            vec![CCoords((2, 0).into())]
+        );
+        assert_eq!(source.errors().len(), 1);
+        tracker.set_rendered(CCoords((2, 0).into()));
+
+        // Removed chunks need rendering too
+        source.remove_chunk(CCoords((0, 0).into()));
+        assert_eq!(
+            tracker.update_region(&source, region, &settings).unwrap(),
+            vec![CCoords((0, 0).into())]
+        );
+        assert!(tracker.pending_regions().is_empty());
+    }
+}
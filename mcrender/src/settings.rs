@@ -11,7 +11,7 @@ use serde::{Deserialize, Deserializer};
 use crate::canvas::Rgb;
 use crate::proplist::PropList;
 use crate::util::intern_str;
-use crate::world::BlockState;
+use crate::world::{BlockState, Chunk};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
@@ -52,6 +52,21 @@ pub enum AssetRenderSpec {
 }
 
 impl AssetRenderSpec {
+    /// Get the name of the render type, as it's written in the settings.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            AssetRenderSpec::Nothing => "nothing",
+            AssetRenderSpec::SolidUniform { .. } => "solid_uniform",
+            AssetRenderSpec::SolidTopSide { .. } => "solid_top_side",
+            AssetRenderSpec::Leaves { .. } => "leaves",
+            AssetRenderSpec::Plant { .. } => "plant",
+            AssetRenderSpec::Crop { .. } => "crop",
+            AssetRenderSpec::Grass { .. } => "grass",
+            AssetRenderSpec::Vine { .. } => "vine",
+            AssetRenderSpec::Water { .. } => "water",
+        }
+    }
+
     pub fn is_biome_aware(&self) -> bool {
         match self {
             // Optional tint_color
@@ -137,8 +152,13 @@ impl AssetStringBuilder {
 }
 
 #[derive(derive_more::Debug, Deserialize)]
-#[debug("AssetRule {{\n    render: {render:?},\n    properties: {properties:?},\n}}")]
+#[debug(
+    "AssetRule {{\n    name: {name:?},\n    render: {render:?},\n    properties: {properties:?},\n}}"
+)]
 pub struct AssetRule {
+    /// The name of the rule in the configuration, e.g. `_default`.
+    #[serde(skip)]
+    pub name: String,
     pub render: Arc<AssetRenderSpec>,
     #[serde(default)]
     pub properties: BTreeSet<String>,
@@ -216,11 +236,14 @@ impl<'de> Deserialize<'de> for AssetRules {
         let Some(raw_default) = raw.remove("_default") else {
             return Err(serde::de::Error::missing_field("_default"));
         };
-        let default = Arc::new(raw_default.rule);
+        let mut default = raw_default.rule;
+        default.name = "_default".to_owned();
+        let default = Arc::new(default);
         let mut rules = BTreeMap::new();
         for (rule_name, raw_rule) in raw.into_iter() {
-            let names = raw_rule.names.unwrap_or_else(|| vec![rule_name]);
+            let names = raw_rule.names.unwrap_or_else(|| vec![rule_name.clone()]);
             let mut rule = raw_rule.rule;
+            rule.name = rule_name;
             // Include default properties in the properties allowed by the rule
             rule.properties.extend(default.properties.iter().cloned());
             let rule = Arc::new(rule);
@@ -286,6 +309,36 @@ impl<'de> Deserialize<'de> for ColorMap {
     }
 }
 
+/// Which chunks are rendered, depending on how far through world generation they are.
+#[derive(Debug, Deserialize)]
+pub struct ChunkStatusFilter {
+    /// Generation statuses of the chunks to render besides those that are fully generated, e.g.
+    /// `minecraft:features`, or `*` for every chunk.
+    #[serde(deserialize_with = "deserialize_status_names")]
+    pub render: BTreeSet<String>,
+    /// The colour saturation of chunks that aren't fully generated, from 0 (greyscale) to 1
+    /// (unchanged).
+    pub partial_saturation: f32,
+}
+
+impl ChunkStatusFilter {
+    /// Check whether `chunk` should be rendered.
+    pub fn includes(&self, chunk: &Chunk) -> bool {
+        chunk.fully_generated
+            || self.render.contains("*")
+            || chunk
+                .status
+                .as_ref()
+                .is_some_and(|status| self.render.contains(status.as_str()))
+    }
+
+    /// Get the colour saturation to draw `chunk` with, or `None` if it's drawn unchanged.
+    pub fn saturation(&self, chunk: &Chunk) -> Option<f32> {
+        (!chunk.fully_generated && self.partial_saturation < 1.0)
+            .then(|| self.partial_saturation.max(0.0))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub assets_path: PathBuf,
@@ -293,6 +346,15 @@ pub struct Settings {
     pub background_color: Rgb<u8>,
     pub asset_rules: AssetRules,
     pub biome_colors: BTreeMap<String, ColorMap>,
+    pub chunk_status: ChunkStatusFilter,
+    /// Whether to compute the light for chunks saved without it, rather than drawing them lit
+    /// as if they were open to the sky.
+    #[serde(default)]
+    pub compute_missing_light: bool,
+    /// Whether to keep every block state property when reading chunks, rather than only those
+    /// used by the block's asset rule. Set for counting blocks, not for rendering.
+    #[serde(skip)]
+    pub keep_block_properties: bool,
 }
 
 impl Settings {
@@ -311,6 +373,25 @@ impl Settings {
     }
 }
 
+/// Deserialize a list of chunk generation statuses, adding the `minecraft:` namespace to those
+/// without one.
+fn deserialize_status_names<'de, D>(deserializer: D) -> Result<BTreeSet<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let names = Vec::<String>::deserialize(deserializer)?;
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            if name == "*" || name.contains(':') {
+                name
+            } else {
+                format!("minecraft:{name}")
+            }
+        })
+        .collect())
+}
+
 fn deserialize_rgb_u8<'de, D>(deserializer: D) -> Result<Rgb<u8>, D::Error>
 where
     D: Deserializer<'de>,
@@ -1,5 +1,6 @@
 use arcstr::ArcStr;
 use parking_lot::RwLock;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::OnceLock;
 
@@ -34,3 +35,23 @@ static INTERNER: OnceLock<Interner> = OnceLock::new();
 pub fn intern_str<S: AsRef<str>>(s: S) -> ArcStr {
     INTERNER.get_or_init(Default::default).get(s)
 }
+
+/// Quote a field for a CSV file, if necessary.
+pub fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\n']) {
+        Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("minecraft:stone"), "minecraft:stone");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,928 @@
+/*
+LevelDB notes:
+
+- Bedrock uses Mojang's fork of LevelDB, which adds zlib compression for table blocks (type 2 with
+  a zlib header, type 4 raw deflate). Snappy (type 1) isn't used.
+- `CURRENT` names the `MANIFEST-*` file, which is a log of version edits listing the live table
+  files (`*.ldb`, or `*.sst` in older databases) and the current log file. Tables and logs not
+  listed there are left over from compactions and are ignored.
+- A log file is a sequence of 32 KiB blocks. Each record has a 7 byte header (CRC, little-endian
+  u16 length, type) and may be split across blocks as first/middle/last fragments. Records in the
+  data log are write batches; records in the manifest are version edits.
+- A table ends with a 48 byte footer pointing to the index block, whose entries point to the data
+  blocks. Block entries share key prefixes with the previous entry, and table keys have an 8 byte
+  trailer with the sequence number and whether the entry is a value or a deletion.
+- This reader never writes anything, so it can read a world while the game has it open, and from
+  archives. Opening the database only reads the log files and the index block of each table, and
+  keys are looked up in the tables when they're needed. Tables can overlap, so a key is looked up
+  in every table whose key range includes it, and the newest entry (by sequence number) wins.
+  CRCs aren't checked.
+ */
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use byteorder::{LittleEndian, ReadBytesExt};
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::world::{ReadSeek, WorldStorage};
+
+const LOG_BLOCK_SIZE: usize = 32768;
+const LOG_HEADER_SIZE: usize = 7;
+const LOG_FULL: u8 = 1;
+const LOG_FIRST: u8 = 2;
+const LOG_MIDDLE: u8 = 3;
+const LOG_LAST: u8 = 4;
+
+const TABLE_FOOTER_SIZE: usize = 48;
+const TABLE_MAGIC: u64 = 0xdb4775248b80fb57;
+/// Compression type byte and CRC after each table block.
+const BLOCK_TRAILER_SIZE: usize = 5;
+
+const BLOCK_NONE: u8 = 0;
+const BLOCK_SNAPPY: u8 = 1;
+const BLOCK_ZLIB: u8 = 2;
+const BLOCK_ZLIB_RAW: u8 = 4;
+
+const TYPE_DELETION: u8 = 0;
+const TYPE_VALUE: u8 = 1;
+/// Sequence numbers are stored in the top 56 bits of a table key's trailer.
+const MAX_SEQUENCE: u64 = (1 << 56) - 1;
+
+/// Number of decompressed table blocks to keep in memory.
+const BLOCK_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct BlockHandle {
+    offset: u64,
+    size: u64,
+}
+
+impl BlockHandle {
+    fn decode(data: &mut &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            offset: read_varint(data)?,
+            size: read_varint(data)?,
+        })
+    }
+}
+
+/// The newest entry for a key that's been found so far.
+#[derive(Clone, Debug)]
+struct Entry {
+    sequence: u64,
+    /// The key's value, or `None` if it was deleted.
+    value: Option<Vec<u8>>,
+}
+
+/// A table file, with its index of data blocks.
+#[derive(derive_more::Debug)]
+#[debug("Table({:?})", path)]
+struct Table {
+    path: PathBuf,
+    /// For each data block in key order, a key at least as large as any key in the block and
+    /// smaller than any key in the next block, and where the block is.
+    index: Vec<(Vec<u8>, BlockHandle)>,
+    /// The smallest user key in the table.
+    first_key: Vec<u8>,
+    reader: Mutex<Box<dyn ReadSeek>>,
+}
+
+impl Table {
+    fn open(storage: &dyn WorldStorage, path: PathBuf) -> anyhow::Result<Self> {
+        let len = storage.file_len(&path)?;
+        if len < TABLE_FOOTER_SIZE as u64 {
+            return Err(anyhow!("table too short"));
+        }
+        let mut reader = storage.open(&path)?;
+        reader.seek(SeekFrom::Start(len - TABLE_FOOTER_SIZE as u64))?;
+        let mut footer = [0u8; TABLE_FOOTER_SIZE];
+        reader.read_exact(&mut footer)?;
+        let magic = (&footer[TABLE_FOOTER_SIZE - 8..]).read_u64::<LittleEndian>()?;
+        if magic != TABLE_MAGIC {
+            return Err(anyhow!("not a table file"));
+        }
+        let mut handles = &footer[..];
+        let _metaindex = BlockHandle::decode(&mut handles)?;
+        let index_handle = BlockHandle::decode(&mut handles)?;
+
+        let index_data = read_block(&mut reader, index_handle)?;
+        let mut index = Vec::new();
+        for item in BlockIter::new(&index_data)? {
+            let (key, mut value) = item?;
+            index.push((key, BlockHandle::decode(&mut value)?));
+        }
+        let first_key = match index.first() {
+            Some((_, handle)) => {
+                let data = read_block(&mut reader, *handle)?;
+                let (internal_key, _) = BlockIter::new(&data)?
+                    .next()
+                    .ok_or_else(|| anyhow!("empty data block"))??;
+                split_internal_key(&internal_key)?.0.to_vec()
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            index,
+            first_key,
+            reader: Mutex::new(reader),
+        })
+    }
+
+    /// Could the table have an entry for user key `key`?
+    fn may_contain(&self, key: &[u8]) -> bool {
+        let Some((last_key, _)) = self.index.last() else {
+            return false;
+        };
+        key >= self.first_key.as_slice() && key <= user_key(last_key)
+    }
+
+    fn read_block(&self, handle: BlockHandle) -> anyhow::Result<Vec<u8>> {
+        read_block(&mut *self.reader.lock(), handle)
+    }
+}
+
+/// Decompressed table blocks, by table index and block offset.
+type BlockCache = LruCache<(usize, u64), Arc<Vec<u8>>>;
+
+/// A read-only LevelDB database.
+#[derive(derive_more::Debug)]
+pub struct LevelDb {
+    tables: Vec<Table>,
+    /// The newest entry for each key in the log files, which haven't been written to tables yet.
+    #[debug(ignore)]
+    log_entries: BTreeMap<Vec<u8>, Entry>,
+    #[debug(ignore)]
+    blocks: Mutex<BlockCache>,
+}
+
+impl LevelDb {
+    /// Open the database in directory `dir` of `storage`.
+    pub fn open(storage: Arc<dyn WorldStorage>, dir: &Path) -> anyhow::Result<Self> {
+        let (table_paths, logs) = find_live_files(storage.as_ref(), dir)?;
+        let mut tables = Vec::with_capacity(table_paths.len());
+        for path in table_paths {
+            let table = Table::open(storage.as_ref(), path.clone())
+                .map_err(|err| anyhow!("failed to read {:?}: {err}", path))?;
+            tables.push(table);
+        }
+        let mut db = Self {
+            tables,
+            log_entries: BTreeMap::new(),
+            blocks: Mutex::new(LruCache::new(BLOCK_CACHE_SIZE)),
+        };
+        for log in logs.iter() {
+            let data = read_file(storage.as_ref(), log)?;
+            for record in read_log_records(&data) {
+                db.load_write_batch(&record)
+                    .map_err(|err| anyhow!("failed to read {:?}: {err}", log))?;
+            }
+        }
+        Ok(db)
+    }
+
+    /// Iterate over every key that has a value, in order, with the sequence number of its latest
+    /// change. This reads every block of every table, without keeping them.
+    pub fn keys(&self) -> impl Iterator<Item = anyhow::Result<(Vec<u8>, u64)>> + '_ {
+        let log_keys = self.log_entries.iter().map(|(key, entry)| {
+            let value_type = if entry.value.is_some() {
+                TYPE_VALUE
+            } else {
+                TYPE_DELETION
+            };
+            Ok(internal_key(key, entry.sequence, value_type))
+        });
+        let mut sources: Vec<Box<dyn Iterator<Item = anyhow::Result<Vec<u8>>> + '_>> =
+            vec![Box::new(log_keys)];
+        for table in self.tables.iter() {
+            sources.push(Box::new(TableKeys::new(table)));
+        }
+        MergedKeys::new(sources)
+    }
+
+    /// Get the value for `key`, if it has one.
+    pub fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut newest = self.log_entries.get(key).cloned();
+        for table in 0..self.tables.len() {
+            if let Some(entry) = self.find_in_table(table, key)?
+                && newest
+                    .as_ref()
+                    .is_none_or(|newest| entry.sequence > newest.sequence)
+            {
+                newest = Some(entry);
+            }
+        }
+        Ok(newest.and_then(|entry| entry.value))
+    }
+
+    /// Find the newest entry for `key` in `tables[table]`.
+    fn find_in_table(&self, table: usize, key: &[u8]) -> anyhow::Result<Option<Entry>> {
+        let table_info = &self.tables[table];
+        if !table_info.may_contain(key) {
+            return Ok(None);
+        }
+        // Entries for the same user key are ordered newest first, so this is before all of them
+        let target = internal_key(key, MAX_SEQUENCE, TYPE_VALUE);
+        let first_block = table_info
+            .index
+            .partition_point(|(last_key, _)| compare_internal_keys(last_key, &target).is_lt());
+        // The first entry after the target is normally in the first block, but can be at the
+        // start of the next one
+        for (_, handle) in table_info.index[first_block..].iter() {
+            let data = self
+                .read_block(table, *handle)
+                .map_err(|err| anyhow!("failed to read {:?}: {err}", table_info.path))?;
+            for item in BlockIter::new(&data)? {
+                let (internal_key, value) = item?;
+                if compare_internal_keys(&internal_key, &target).is_lt() {
+                    continue;
+                }
+                let (user_key, sequence, value_type) = split_internal_key(&internal_key)?;
+                if user_key != key {
+                    return Ok(None);
+                }
+                return Ok(Some(Entry {
+                    sequence,
+                    value: (value_type == TYPE_VALUE).then(|| value.to_vec()),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn load_write_batch(&mut self, mut data: &[u8]) -> anyhow::Result<()> {
+        let first_sequence = data.read_u64::<LittleEndian>()?;
+        let count = data.read_u32::<LittleEndian>()?;
+        for sequence in first_sequence..first_sequence + count as u64 {
+            let value_type = data.read_u8()?;
+            let key = read_slice(&mut data)?;
+            let value = match value_type {
+                TYPE_VALUE => Some(read_slice(&mut data)?.to_vec()),
+                TYPE_DELETION => None,
+                _ => return Err(anyhow!("unknown write batch record type: {}", value_type)),
+            };
+            match self.log_entries.get_mut(key) {
+                Some(existing) if existing.sequence >= sequence => {}
+                Some(existing) => *existing = Entry { sequence, value },
+                None => {
+                    self.log_entries
+                        .insert(key.to_vec(), Entry { sequence, value });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_block(&self, table: usize, block: BlockHandle) -> anyhow::Result<Arc<Vec<u8>>> {
+        if let Some(data) = self.blocks.lock().get(&(table, block.offset)) {
+            return Ok(data.clone());
+        }
+        let data = Arc::new(self.tables[table].read_block(block)?);
+        self.blocks.lock().put((table, block.offset), data.clone());
+        Ok(data)
+    }
+}
+
+/// Iterator over the internal keys of a table, reading one data block at a time.
+struct TableKeys<'a> {
+    table: &'a Table,
+    next_block: usize,
+    keys: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl<'a> TableKeys<'a> {
+    fn new(table: &'a Table) -> Self {
+        Self {
+            table,
+            next_block: 0,
+            keys: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Iterator for TableKeys<'_> {
+    type Item = anyhow::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(key) = self.keys.next() {
+                return Some(Ok(key));
+            }
+            let (_, handle) = self.table.index.get(self.next_block)?;
+            self.next_block += 1;
+            let keys = self.table.read_block(*handle).and_then(|data| {
+                BlockIter::new(&data)?
+                    .map(|item| item.map(|(key, _)| key))
+                    .collect::<anyhow::Result<Vec<_>>>()
+            });
+            match keys {
+                Ok(keys) => self.keys = keys.into_iter(),
+                Err(err) => {
+                    // Don't keep reading a table that's corrupt
+                    self.next_block = self.table.index.len();
+                    return Some(Err(anyhow!("failed to read {:?}: {err}", self.table.path)));
+                }
+            }
+        }
+    }
+}
+
+/// An internal key from one of the sources merged by [`MergedKeys`], ordered so that the smallest
+/// key comes first in a [`BinaryHeap`].
+#[derive(Eq, PartialEq)]
+struct MergeItem {
+    key: Vec<u8>,
+    source: usize,
+}
+
+impl Ord for MergeItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_internal_keys(&self.key, &other.key).then(self.source.cmp(&other.source))
+    }
+}
+
+impl PartialOrd for MergeItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Merges sorted streams of internal keys into the user keys that have a value, with the sequence
+/// number of their newest entry.
+struct MergedKeys<'a> {
+    sources: Vec<Box<dyn Iterator<Item = anyhow::Result<Vec<u8>>> + 'a>>,
+    heads: BinaryHeap<Reverse<MergeItem>>,
+    /// Sources that still need their next key added to `heads`.
+    pending: Vec<usize>,
+    last_user_key: Option<Vec<u8>>,
+}
+
+impl<'a> MergedKeys<'a> {
+    fn new(sources: Vec<Box<dyn Iterator<Item = anyhow::Result<Vec<u8>>> + 'a>>) -> Self {
+        Self {
+            pending: (0..sources.len()).collect(),
+            sources,
+            heads: BinaryHeap::new(),
+            last_user_key: None,
+        }
+    }
+}
+
+impl Iterator for MergedKeys<'_> {
+    type Item = anyhow::Result<(Vec<u8>, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some(source) = self.pending.pop() {
+                match self.sources[source].next() {
+                    Some(Ok(key)) => self.heads.push(Reverse(MergeItem { key, source })),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {}
+                }
+            }
+            let Reverse(MergeItem { key, source }) = self.heads.pop()?;
+            self.pending.push(source);
+            let (user_key, sequence, value_type) = match split_internal_key(&key) {
+                Ok(parts) => parts,
+                Err(err) => return Some(Err(err)),
+            };
+            // Only the newest entry for each key counts, and that comes first
+            if self.last_user_key.as_deref() == Some(user_key) {
+                continue;
+            }
+            self.last_user_key = Some(user_key.to_vec());
+            if value_type == TYPE_VALUE {
+                return Some(Ok((user_key.to_vec(), sequence)));
+            }
+        }
+    }
+}
+
+/// Find the table and log files that make up the current version of the database. If there's no
+/// manifest, every table and log file is used.
+fn find_live_files(
+    storage: &dyn WorldStorage,
+    dir: &Path,
+) -> anyhow::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut tables = BTreeMap::new();
+    let mut logs = BTreeMap::new();
+    for path in storage.read_dir(dir)? {
+        let Some((number, extension)) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split_once('.'))
+            .and_then(|(number, extension)| Some((number.parse::<u64>().ok()?, extension)))
+        else {
+            continue;
+        };
+        match extension {
+            "ldb" | "sst" => {
+                tables.insert(number, path);
+            }
+            "log" => {
+                logs.insert(number, path);
+            }
+            _ => {}
+        }
+    }
+
+    let current_path = dir.join("CURRENT");
+    if !storage.is_file(&current_path) {
+        log::warn!("no CURRENT file in {:?}, reading all tables and logs", dir);
+        return Ok((tables.into_values().collect(), logs.into_values().collect()));
+    }
+    let current = String::from_utf8(read_file(storage, &current_path)?)?;
+    let manifest_path = dir.join(current.trim());
+    let manifest = read_file(storage, &manifest_path)?;
+    let mut live_tables = HashSet::new();
+    let mut log_number = 0;
+    let mut prev_log_number = None;
+    for record in read_log_records(&manifest) {
+        let edit = VersionEdit::decode(&record)
+            .map_err(|err| anyhow!("failed to read {:?}: {err}", manifest_path))?;
+        log_number = edit.log_number.unwrap_or(log_number);
+        prev_log_number = edit.prev_log_number.or(prev_log_number);
+        for number in edit.deleted_files {
+            live_tables.remove(&number);
+        }
+        live_tables.extend(edit.new_files);
+    }
+
+    let mut live_table_paths = Vec::new();
+    for number in live_tables {
+        let path = tables
+            .remove(&number)
+            .ok_or_else(|| anyhow!("missing table file {number:06}.ldb"))?;
+        live_table_paths.push(path);
+    }
+    live_table_paths.sort();
+    let live_log_paths = logs
+        .into_iter()
+        .filter(|(number, _)| *number >= log_number || Some(*number) == prev_log_number)
+        .map(|(_, path)| path)
+        .collect();
+    Ok((live_table_paths, live_log_paths))
+}
+
+/// The parts of a manifest record that matter for finding live files.
+#[derive(Debug, Default)]
+struct VersionEdit {
+    log_number: Option<u64>,
+    prev_log_number: Option<u64>,
+    new_files: Vec<u64>,
+    deleted_files: Vec<u64>,
+}
+
+impl VersionEdit {
+    fn decode(mut data: &[u8]) -> anyhow::Result<Self> {
+        let mut edit = Self::default();
+        while !data.is_empty() {
+            match read_varint(&mut data)? {
+                // Comparator name
+                1 => {
+                    read_slice(&mut data)?;
+                }
+                2 => edit.log_number = Some(read_varint(&mut data)?),
+                // Next file number, last sequence number
+                3 | 4 => {
+                    read_varint(&mut data)?;
+                }
+                // Compaction pointer: level, internal key
+                5 => {
+                    read_varint(&mut data)?;
+                    read_slice(&mut data)?;
+                }
+                // Deleted file: level, number
+                6 => {
+                    read_varint(&mut data)?;
+                    edit.deleted_files.push(read_varint(&mut data)?);
+                }
+                // New file: level, number, size, smallest key, largest key
+                7 => {
+                    read_varint(&mut data)?;
+                    edit.new_files.push(read_varint(&mut data)?);
+                    read_varint(&mut data)?;
+                    read_slice(&mut data)?;
+                    read_slice(&mut data)?;
+                }
+                9 => edit.prev_log_number = Some(read_varint(&mut data)?),
+                tag => return Err(anyhow!("unknown version edit tag: {}", tag)),
+            }
+        }
+        Ok(edit)
+    }
+}
+
+fn read_file(storage: &dyn WorldStorage, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    storage.open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Split a log file into its records. A truncated record at the end (e.g. from a crash while
+/// writing) is ignored.
+fn read_log_records(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut records = Vec::new();
+    let mut pending: Option<Vec<u8>> = None;
+    for block in data.chunks(LOG_BLOCK_SIZE) {
+        let mut rest = block;
+        while rest.len() >= LOG_HEADER_SIZE {
+            let len = u16::from_le_bytes([rest[4], rest[5]]) as usize;
+            let record_type = rest[6];
+            if record_type == 0 && len == 0 {
+                // Preallocated space that was never written
+                break;
+            }
+            let Some(fragment) = rest.get(LOG_HEADER_SIZE..LOG_HEADER_SIZE + len) else {
+                return records;
+            };
+            rest = &rest[LOG_HEADER_SIZE + len..];
+            match record_type {
+                LOG_FULL => records.push(fragment.to_vec()),
+                LOG_FIRST => pending = Some(fragment.to_vec()),
+                LOG_MIDDLE | LOG_LAST => {
+                    let Some(mut record) = pending.take() else {
+                        continue;
+                    };
+                    record.extend_from_slice(fragment);
+                    if record_type == LOG_LAST {
+                        records.push(record);
+                    } else {
+                        pending = Some(record);
+                    }
+                }
+                _ => return records,
+            }
+        }
+    }
+    records
+}
+
+/// Read and decompress a table block.
+fn read_block<R: Read + Seek>(reader: &mut R, handle: BlockHandle) -> anyhow::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(handle.offset))?;
+    let mut raw = vec![0u8; handle.size as usize + BLOCK_TRAILER_SIZE];
+    reader.read_exact(&mut raw)?;
+    let compression = raw[handle.size as usize];
+    raw.truncate(handle.size as usize);
+    let mut data = Vec::new();
+    match compression {
+        BLOCK_NONE => return Ok(raw),
+        BLOCK_ZLIB => {
+            flate2::read::ZlibDecoder::new(&raw[..]).read_to_end(&mut data)?;
+        }
+        BLOCK_ZLIB_RAW => {
+            flate2::read::DeflateDecoder::new(&raw[..]).read_to_end(&mut data)?;
+        }
+        BLOCK_SNAPPY => return Err(anyhow!("snappy compressed blocks are not supported")),
+        _ => return Err(anyhow!("unknown block compression: {}", compression)),
+    }
+    Ok(data)
+}
+
+/// Iterator over the key/value entries in a table block.
+struct BlockIter<'a> {
+    data: &'a [u8],
+    key: Vec<u8>,
+}
+
+impl<'a> BlockIter<'a> {
+    fn new(block: &'a [u8]) -> anyhow::Result<Self> {
+        // The block ends with the offsets of restart points (where keys are stored in full),
+        // which are only needed for seeking
+        let restart_count = block
+            .len()
+            .checked_sub(4)
+            .map(|i| u32::from_le_bytes(block[i..].try_into().unwrap()) as usize)
+            .ok_or_else(|| anyhow!("block too short"))?;
+        let end = (restart_count + 1)
+            .checked_mul(4)
+            .and_then(|trailer| block.len().checked_sub(trailer))
+            .ok_or_else(|| anyhow!("block too short"))?;
+        Ok(Self {
+            data: &block[..end],
+            key: Vec::new(),
+        })
+    }
+
+    fn read_entry(&mut self) -> anyhow::Result<(Vec<u8>, &'a [u8])> {
+        let shared = read_varint(&mut self.data)? as usize;
+        let non_shared = read_varint(&mut self.data)? as usize;
+        let value_len = read_varint(&mut self.data)? as usize;
+        if shared > self.key.len() || non_shared + value_len > self.data.len() {
+            return Err(anyhow!("corrupt block entry"));
+        }
+        self.key.truncate(shared);
+        self.key.extend_from_slice(&self.data[..non_shared]);
+        let value = &self.data[non_shared..non_shared + value_len];
+        self.data = &self.data[non_shared + value_len..];
+        Ok((self.key.clone(), value))
+    }
+}
+
+impl<'a> Iterator for BlockIter<'a> {
+    type Item = anyhow::Result<(Vec<u8>, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let entry = self.read_entry();
+        if entry.is_err() {
+            // Don't keep returning errors for the rest of the block
+            self.data = &[];
+        }
+        Some(entry)
+    }
+}
+
+/// Build a table key from a user key, sequence number and value type.
+fn internal_key(user_key: &[u8], sequence: u64, value_type: u8) -> Vec<u8> {
+    let mut key = Vec::with_capacity(user_key.len() + 8);
+    key.extend_from_slice(user_key);
+    key.extend_from_slice(&(sequence << 8 | value_type as u64).to_le_bytes());
+    key
+}
+
+/// Get the user key part of a table key.
+fn user_key(key: &[u8]) -> &[u8] {
+    &key[..key.len().saturating_sub(8)]
+}
+
+/// Compare table keys the way LevelDB orders them: by user key, and then newest first.
+fn compare_internal_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let trailer = |key: &[u8]| {
+        key.get(key.len().saturating_sub(8)..)
+            .and_then(|trailer| trailer.try_into().ok())
+            .map_or(0, u64::from_le_bytes)
+    };
+    user_key(a)
+        .cmp(user_key(b))
+        .then_with(|| trailer(b).cmp(&trailer(a)))
+}
+
+/// Split a table key into the user key, sequence number and value type.
+fn split_internal_key(key: &[u8]) -> anyhow::Result<(&[u8], u64, u8)> {
+    let split = key
+        .len()
+        .checked_sub(8)
+        .ok_or_else(|| anyhow!("table key too short"))?;
+    let trailer = u64::from_le_bytes(key[split..].try_into().unwrap());
+    Ok((&key[..split], trailer >> 8, trailer as u8))
+}
+
+fn read_varint(data: &mut &[u8]) -> anyhow::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = data.read_u8()?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("varint too long"))
+}
+
+/// Read a varint length-prefixed slice.
+fn read_slice<'a>(data: &mut &'a [u8]) -> anyhow::Result<&'a [u8]> {
+    let len = read_varint(data)? as usize;
+    if len > data.len() {
+        return Err(anyhow!("slice extends past end of data"));
+    }
+    let (slice, rest) = data.split_at(len);
+    *data = rest;
+    Ok(slice)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    pub(in crate::world::bedrock) fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            output.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        output.push(value as u8);
+    }
+
+    /// `(key, value)` records written together, where a value of `None` is a deletion.
+    pub(in crate::world::bedrock) type Batch<'a> = Vec<(&'a [u8], Option<&'a [u8]>)>;
+
+    /// Encode `batches` of `(key, value)` records as a log file, starting at sequence number 1.
+    pub(in crate::world::bedrock) fn encode_log(batches: &[Batch]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut sequence = 1u64;
+        for batch in batches {
+            let mut record = Vec::new();
+            record.extend_from_slice(&sequence.to_le_bytes());
+            record.extend_from_slice(&(batch.len() as u32).to_le_bytes());
+            for (key, value) in batch {
+                record.push(value.map_or(TYPE_DELETION, |_| TYPE_VALUE));
+                write_varint(&mut record, key.len() as u64);
+                record.extend_from_slice(key);
+                if let Some(value) = value {
+                    write_varint(&mut record, value.len() as u64);
+                    record.extend_from_slice(value);
+                }
+            }
+            sequence += batch.len() as u64;
+
+            // Split the record into fragments at block boundaries
+            let mut rest = &record[..];
+            let mut first = true;
+            loop {
+                let space = LOG_BLOCK_SIZE - output.len() % LOG_BLOCK_SIZE;
+                if space < LOG_HEADER_SIZE {
+                    output.resize(output.len() + space, 0);
+                    continue;
+                }
+                let len = rest.len().min(space - LOG_HEADER_SIZE);
+                let last = len == rest.len();
+                let record_type = match (first, last) {
+                    (true, true) => LOG_FULL,
+                    (true, false) => LOG_FIRST,
+                    (false, false) => LOG_MIDDLE,
+                    (false, true) => LOG_LAST,
+                };
+                output.extend_from_slice(&[0; 4]);
+                output.extend_from_slice(&(len as u16).to_le_bytes());
+                output.push(record_type);
+                output.extend_from_slice(&rest[..len]);
+                rest = &rest[len..];
+                first = false;
+                if last {
+                    break;
+                }
+            }
+        }
+        output
+    }
+
+    /// `(key, sequence, value)` of a table entry.
+    type TableEntry<'a> = (&'a [u8], u64, Option<&'a [u8]>);
+
+    /// Encode an uncompressed table with two entries in each data block.
+    fn encode_table(entries: &[TableEntry]) -> Vec<u8> {
+        fn encode_block(entries: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+            let mut block = Vec::new();
+            for (key, value) in entries {
+                write_varint(&mut block, 0);
+                write_varint(&mut block, key.len() as u64);
+                write_varint(&mut block, value.len() as u64);
+                block.extend_from_slice(key);
+                block.extend_from_slice(value);
+            }
+            // A single restart point at the start
+            block.extend_from_slice(&0u32.to_le_bytes());
+            block.extend_from_slice(&1u32.to_le_bytes());
+            block
+        }
+
+        let data_entries: Vec<_> = entries
+            .iter()
+            .map(|(key, sequence, value)| {
+                let value_type = if value.is_some() {
+                    TYPE_VALUE
+                } else {
+                    TYPE_DELETION
+                };
+                let mut internal_key = key.to_vec();
+                internal_key.extend_from_slice(&(sequence << 8 | value_type as u64).to_le_bytes());
+                (internal_key, value.unwrap_or_default().to_vec())
+            })
+            .collect();
+        // Two entries per data block, so that lookups have to pick the right block
+        let mut table = Vec::new();
+        let mut index_entries = Vec::new();
+        for block_entries in data_entries.chunks(2) {
+            let mut handle = Vec::new();
+            write_varint(&mut handle, table.len() as u64);
+            let block = encode_block(block_entries);
+            write_varint(&mut handle, block.len() as u64);
+            table.extend_from_slice(&block);
+            table.extend_from_slice(&[BLOCK_NONE, 0, 0, 0, 0]);
+            index_entries.push((block_entries.last().unwrap().0.clone(), handle));
+        }
+
+        let index_offset = table.len() as u64;
+        let index = encode_block(&index_entries);
+        let index_size = index.len() as u64;
+        table.extend_from_slice(&index);
+        table.extend_from_slice(&[BLOCK_NONE, 0, 0, 0, 0]);
+
+        let mut footer = Vec::new();
+        // Empty metaindex block handle
+        write_varint(&mut footer, 0);
+        write_varint(&mut footer, 0);
+        write_varint(&mut footer, index_offset);
+        write_varint(&mut footer, index_size);
+        footer.resize(TABLE_FOOTER_SIZE - 8, 0);
+        footer.extend_from_slice(&TABLE_MAGIC.to_le_bytes());
+        table.extend_from_slice(&footer);
+        table
+    }
+
+    #[test]
+    fn test_read_log_records() {
+        let big = vec![7u8; LOG_BLOCK_SIZE * 2];
+        let log = encode_log(&[
+            vec![(b"a", Some(b"1"))],
+            vec![(b"b", Some(&big)), (b"a", None)],
+        ]);
+        let records = read_log_records(&log);
+        assert_eq!(records.len(), 2);
+        // Truncating the log drops the incomplete record
+        let records = read_log_records(&log[..LOG_BLOCK_SIZE + 100]);
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_level_db() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("000005.ldb"),
+            encode_table(&[
+                (b"apple", 3, Some(b"red")),
+                (b"banana", 4, Some(b"yellow")),
+                (b"cherry", 5, None),
+            ]),
+        )
+        .unwrap();
+        // A newer table that overlaps the first one
+        std::fs::write(
+            dir.join("000006.ldb"),
+            encode_table(&[
+                (b"apple", 1, Some(b"unripe")),
+                (b"banana", 2, Some(b"green")),
+                (b"grape", 6, Some(b"purple")),
+            ]),
+        )
+        .unwrap();
+        // Sequence numbers in the log start at 1, so are older than the table except for the last
+        std::fs::write(
+            dir.join("000007.log"),
+            encode_log(&[
+                vec![(b"apple", Some(b"green")), (b"cherry", Some(b"dark"))],
+                vec![(b"date", Some(b"brown"))],
+                vec![(b"egg", Some(b"white")), (b"fig", Some(b"purple"))],
+                vec![(b"banana", None)],
+            ]),
+        )
+        .unwrap();
+
+        let db = LevelDb::open(Arc::new(crate::world::FileSystem), dir).unwrap();
+        assert_eq!(db.get(b"apple").unwrap().as_deref(), Some(&b"red"[..]));
+        assert_eq!(db.get(b"date").unwrap().as_deref(), Some(&b"brown"[..]));
+        assert_eq!(db.get(b"cherry").unwrap(), None);
+        assert_eq!(db.get(b"banana").unwrap(), None);
+        assert_eq!(db.get(b"grape").unwrap().as_deref(), Some(&b"purple"[..]));
+        assert_eq!(db.get(b"aardvark").unwrap(), None);
+        assert_eq!(db.get(b"blueberry").unwrap(), None);
+        assert_eq!(db.get(b"zucchini").unwrap(), None);
+        let keys: Vec<_> = db.keys().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                (b"apple".to_vec(), 3),
+                (b"date".to_vec(), 3),
+                (b"egg".to_vec(), 4),
+                (b"fig".to_vec(), 5),
+                (b"grape".to_vec(), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_edit() {
+        let mut record = Vec::new();
+        write_varint(&mut record, 1);
+        write_varint(&mut record, 3);
+        record.extend_from_slice(b"cmp");
+        write_varint(&mut record, 2);
+        write_varint(&mut record, 12);
+        write_varint(&mut record, 7);
+        for value in [0, 10, 2048] {
+            write_varint(&mut record, value);
+        }
+        write_varint(&mut record, 1);
+        record.push(b'a');
+        write_varint(&mut record, 1);
+        record.push(b'z');
+        write_varint(&mut record, 6);
+        write_varint(&mut record, 1);
+        write_varint(&mut record, 4);
+        let edit = VersionEdit::decode(&record).unwrap();
+        assert_eq!(edit.log_number, Some(12));
+        assert_eq!(edit.new_files, vec![10]);
+        assert_eq!(edit.deleted_files, vec![4]);
+    }
+}
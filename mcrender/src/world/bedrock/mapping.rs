@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use fastnbt::Value;
+
+use crate::util::intern_str;
+use crate::world::BlockState;
+use crate::world::legacy;
+use crate::world::numeric::parse_block_state;
+
+static BLOCK_NAMES: OnceLock<BlockNameTable> = OnceLock::new();
+
+/// Block face order of the `facing_direction` state.
+const FACING_DIRECTIONS: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+/// Horizontal order of the `direction` state.
+const DIRECTIONS: [&str; 4] = ["south", "west", "north", "east"];
+/// Horizontal order of the `weirdo_direction` state of stairs.
+const WEIRDO_DIRECTIONS: [&str; 4] = ["east", "west", "south", "north"];
+/// Java rail shapes by `rail_direction`.
+const RAIL_SHAPES: [&str; 10] = [
+    "north_south",
+    "east_west",
+    "ascending_east",
+    "ascending_west",
+    "ascending_north",
+    "ascending_south",
+    "south_east",
+    "south_west",
+    "north_west",
+    "north_east",
+];
+
+#[derive(Debug)]
+struct BlockMapping {
+    /// Bedrock states that must match, in the string form returned by `state_value()`.
+    conditions: Vec<(String, String)>,
+    state: BlockState,
+}
+
+/// Mapping from Bedrock block names and states to Java block states, for blocks that aren't just
+/// converted property by property.
+#[derive(Debug)]
+struct BlockNameTable {
+    /// Mappings by Bedrock name without namespace, most specific first.
+    mappings: HashMap<String, Vec<BlockMapping>>,
+}
+
+impl BlockNameTable {
+    fn builtin() -> &'static Self {
+        BLOCK_NAMES.get_or_init(|| {
+            Self::parse(include_str!("bedrock_blocks.txt")).expect("invalid Bedrock block table")
+        })
+    }
+
+    /// Parse a mapping table, with one `BEDROCK_NAME[STATE=VALUE,...] JAVA_NAME[PROPERTY=VALUE,...]`
+    /// entry per line.
+    fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut mappings: HashMap<String, Vec<BlockMapping>> = HashMap::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, state) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("invalid Bedrock block mapping: {:?}", line))?;
+            let key = parse_block_state(key)?;
+            let conditions = key
+                .properties
+                .iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect();
+            mappings
+                .entry(key.short_name().to_owned())
+                .or_default()
+                .push(BlockMapping {
+                    conditions,
+                    state: parse_block_state(state.trim())?,
+                });
+        }
+        for entries in mappings.values_mut() {
+            entries.sort_by_key(|mapping| std::cmp::Reverse(mapping.conditions.len()));
+        }
+        Ok(Self { mappings })
+    }
+
+    fn get(&self, name: &str, states: &[(String, String)]) -> Option<&BlockMapping> {
+        self.mappings.get(name)?.iter().find(|mapping| {
+            mapping
+                .conditions
+                .iter()
+                .all(|condition| states.contains(condition))
+        })
+    }
+}
+
+/// Convert a Bedrock block with `name` and `states` (from its palette entry) to a Java block state.
+pub(super) fn convert_block(name: &str, states: Option<&HashMap<String, Value>>) -> BlockState {
+    let short_name = name.strip_prefix("minecraft:").unwrap_or(name);
+    let mut states: Vec<(String, String)> = states
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((key.clone(), state_value(value)?)))
+        .collect();
+    states.sort();
+
+    let mut state = match BlockNameTable::builtin().get(short_name, &states) {
+        Some(mapping) => {
+            // The states that selected the mapping are already represented by the Java block
+            states.retain(|state| !mapping.conditions.contains(state));
+            mapping.state.clone()
+        }
+        None if name.contains(':') => BlockState::new(intern_str(name)),
+        None => BlockState::new(intern_str(format!("minecraft:{name}"))),
+    };
+    for (key, value) in states {
+        convert_state(&mut state, &key, &value);
+    }
+    state
+}
+
+/// Get the string form of a state value. Bedrock stores booleans as bytes.
+fn state_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Byte(v) => Some((*v != 0).to_string()),
+        Value::Short(v) => Some(v.to_string()),
+        Value::Int(v) => Some(v.to_string()),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Add the Java equivalent of Bedrock state `key` to `state`. States that don't have a known
+/// equivalent keep their name, which is often the same as the Java property.
+fn convert_state(state: &mut BlockState, key: &str, value: &str) {
+    let index = |values: &[&'static str]| {
+        value
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| values.get(i).copied())
+    };
+    let bool_choice = |if_true: &'static str, if_false: &'static str| {
+        if value == "true" { if_true } else { if_false }
+    };
+    let (property, value) = match key {
+        "pillar_axis" => ("axis", value),
+        "minecraft:cardinal_direction" | "minecraft:facing_direction" | "minecraft:block_face" => {
+            ("facing", value)
+        }
+        "facing_direction" => ("facing", index(&FACING_DIRECTIONS).unwrap_or(value)),
+        "direction" => ("facing", index(&DIRECTIONS).unwrap_or(value)),
+        "weirdo_direction" => ("facing", index(&WEIRDO_DIRECTIONS).unwrap_or(value)),
+        "rail_direction" => ("shape", index(&RAIL_SHAPES).unwrap_or(value)),
+        "upper_block_bit" => ("half", bool_choice("upper", "lower")),
+        "upside_down_bit" => ("half", bool_choice("top", "bottom")),
+        "top_slot_bit" => ("type", bool_choice("top", "bottom")),
+        "door_hinge_bit" => ("hinge", bool_choice("right", "left")),
+        "minecraft:vertical_half" if state.name.ends_with("_slab") => ("type", value),
+        "minecraft:vertical_half" => ("half", value),
+        "growth" => ("age", value),
+        "redstone_signal" => ("power", value),
+        "liquid_depth" => ("level", value),
+        "height" => {
+            // Snow layers count from 0 rather than 1
+            let layers = value.parse::<u32>().map_or(1, |v| v + 1);
+            state
+                .properties
+                .insert("layers", layers.to_string().as_str());
+            return;
+        }
+        "torch_facing_direction" => {
+            // Wall torches are separate blocks in Java, facing away from the wall they're on
+            let facing = match value {
+                "west" => "east",
+                "east" => "west",
+                "north" => "south",
+                "south" => "north",
+                _ => return,
+            };
+            let wall_name = state.name.replace("torch", "wall_torch");
+            state.name = intern_str(wall_name);
+            state.properties.insert("facing", facing);
+            return;
+        }
+        _ => {
+            let key = key.strip_prefix("minecraft:").unwrap_or(key);
+            (key.strip_suffix("_bit").unwrap_or(key), value)
+        }
+    };
+    state.properties.insert(property, value);
+}
+
+/// Get the Java name of numeric Bedrock biome `id`. Most IDs below 40 and the "mutated" variants
+/// from 129 are the same as Java's old numeric IDs, but the rest were assigned differently.
+pub(super) fn biome_name(id: i32) -> Option<&'static str> {
+    let name = match id {
+        0..=39 | 129..=167 => return legacy::biome_name(id),
+        40 | 41 => "minecraft:warm_ocean",
+        42 => "minecraft:lukewarm_ocean",
+        43 => "minecraft:deep_lukewarm_ocean",
+        44 => "minecraft:cold_ocean",
+        45 => "minecraft:deep_cold_ocean",
+        46 => "minecraft:frozen_ocean",
+        47 => "minecraft:deep_frozen_ocean",
+        48 | 49 => "minecraft:bamboo_jungle",
+        178 => "minecraft:soul_sand_valley",
+        179 => "minecraft:crimson_forest",
+        180 => "minecraft:warped_forest",
+        181 => "minecraft:basalt_deltas",
+        182 => "minecraft:jagged_peaks",
+        183 => "minecraft:frozen_peaks",
+        184 => "minecraft:snowy_slopes",
+        185 => "minecraft:grove",
+        186 => "minecraft:meadow",
+        187 => "minecraft:lush_caves",
+        188 => "minecraft:dripstone_caves",
+        189 => "minecraft:stony_peaks",
+        190 => "minecraft:deep_dark",
+        191 => "minecraft:mangrove_swamp",
+        192 => "minecraft:cherry_grove",
+        193 => "minecraft:pale_garden",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(name: &str, states: &[(&str, Value)]) -> String {
+        let states: HashMap<String, Value> = states
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        convert_block(name, Some(&states)).to_string()
+    }
+
+    #[test]
+    fn test_convert_block() {
+        assert_eq!(convert("minecraft:stone", &[]), "minecraft:stone");
+        assert_eq!(convert("minecraft:grass", &[]), "minecraft:grass_block");
+        assert_eq!(
+            convert(
+                "minecraft:wool",
+                &[("color", Value::String("silver".into()))]
+            ),
+            "minecraft:light_gray_wool"
+        );
+        assert_eq!(
+            convert(
+                "minecraft:log",
+                &[
+                    ("old_log_type", Value::String("birch".into())),
+                    ("pillar_axis", Value::String("x".into())),
+                ]
+            ),
+            "minecraft:birch_log{axis=x}"
+        );
+        assert_eq!(
+            convert(
+                "minecraft:wood",
+                &[
+                    ("wood_type", Value::String("acacia".into())),
+                    ("stripped_bit", Value::Byte(1)),
+                ]
+            ),
+            "minecraft:stripped_acacia_wood"
+        );
+        assert_eq!(
+            convert(
+                "minecraft:oak_stairs",
+                &[
+                    ("upside_down_bit", Value::Byte(0)),
+                    ("weirdo_direction", Value::Int(3)),
+                ]
+            ),
+            "minecraft:oak_stairs{facing=north;half=bottom}"
+        );
+        assert_eq!(
+            convert("minecraft:snow_layer", &[("height", Value::Int(2))]),
+            "minecraft:snow{layers=3}"
+        );
+        assert_eq!(
+            convert(
+                "minecraft:torch",
+                &[("torch_facing_direction", Value::String("west".into()))]
+            ),
+            "minecraft:wall_torch{facing=east}"
+        );
+        assert_eq!(
+            convert(
+                "minecraft:lit_furnace",
+                &[(
+                    "minecraft:cardinal_direction",
+                    Value::String("south".into())
+                )]
+            ),
+            "minecraft:furnace{facing=south;lit=true}"
+        );
+    }
+
+    #[test]
+    fn test_biome_name() {
+        assert_eq!(biome_name(1), Some("minecraft:plains"));
+        assert_eq!(biome_name(44), Some("minecraft:cold_ocean"));
+        assert_eq!(biome_name(186), Some("minecraft:meadow"));
+        assert_eq!(biome_name(1000), None);
+    }
+}
@@ -0,0 +1,811 @@
+/*
+Bedrock Edition world format notes:
+
+- Chunks are stored in a LevelDB database in `db/`, with a record per chunk per kind of data. Keys
+  are the chunk X and Z (little-endian i32), the dimension (i32, omitted for the overworld), a tag
+  byte, and for sub-chunks the section Y (i8). Other keys (players, villages, maps...) are strings.
+- `SubChunkPrefix` records hold a 16x16x16 section as one or more block storages: a header byte
+  (bits per block in the top 7 bits), indexes packed into little-endian u32 words without spanning,
+  then the palette as little-endian NBT compounds `{name, states, version}`. Block indexes are in
+  XZY order (Y varies fastest), unlike Java's YZX. The second storage, if any, holds water for
+  waterlogged blocks. Sub-chunk versions before 8 use numeric block IDs and aren't supported.
+- Biomes are numeric IDs. `Data3D` (1.18+) has a heightmap, then a storage per section from the
+  bottom of the world (Y -64 in the overworld), with one ID per block and i32 palette entries; a
+  header of 0xFF means "same as the section below". Older `Data2D` has one byte per column.
+- `FinalizedState` says whether the chunk has been populated. Light isn't stored at all, so chunks
+  get full sky light.
+- `level.dat` is little-endian NBT after an 8 byte header, with many of the same fields as Java.
+- Block names and states are mostly the same as Java since the two editions were aligned, but some
+  blocks are still named differently or use a variant state. `mapping.rs` handles those.
+ */
+
+mod leveldb;
+mod mapping;
+mod nbt;
+pub use leveldb::LevelDb;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use byteorder::{LittleEndian, ReadBytesExt};
+use fastnbt::Value;
+
+use crate::asset::DEFAULT_BIOME;
+use crate::settings::Settings;
+use crate::util::intern_str;
+use crate::world::{
+    BCoords, BIndex, BlockData, BlockState, CCoords, CHUNK_SIZE, CIndex, Chunk, DimensionError,
+    DimensionID, ErrorReport, ErrorStage, ErrorSummary, HeightRange, Heightmaps, LevelInfo,
+    RCoords, REGION_CHUNK_COUNT, RawChunk, SECTION_BLOCK_COUNT, Section, SkyLightData, WorldSource,
+    WorldStorage, apply_sky_light, convert_block_state, open_storage,
+};
+
+const TAG_DATA_3D: u8 = 0x2B;
+const TAG_VERSION: u8 = 0x2C;
+const TAG_DATA_2D: u8 = 0x2D;
+const TAG_SUB_CHUNK: u8 = 0x2F;
+const TAG_FINALIZED_STATE: u8 = 0x36;
+const TAG_LEGACY_VERSION: u8 = 0x76;
+
+/// `FinalizedState` of a chunk that has been fully populated.
+const FINALIZED_DONE: i32 = 2;
+
+/// Storage header meaning "the same biomes as the section below".
+const BIOME_STORAGE_COPY: u8 = 0xFF;
+
+/// The Nether is only 128 blocks high in Bedrock.
+const NETHER_HEIGHT: HeightRange = HeightRange::new(0, 128);
+
+/// A Bedrock Edition world.
+#[derive(Debug)]
+pub struct BedrockWorld {
+    pub path: PathBuf,
+    /// Where the world's files are stored.
+    pub storage: Arc<dyn WorldStorage>,
+    /// World metadata, if the world has a readable `level.dat`.
+    pub level: Option<LevelInfo>,
+    pub dimensions: HashMap<DimensionID, BedrockDimension>,
+}
+
+impl BedrockWorld {
+    /// Does the world whose root directory is at `path` in `storage` look like a Bedrock world?
+    pub fn is_bedrock_world(storage: &dyn WorldStorage, path: &Path) -> bool {
+        storage.is_dir(&path.join("db"))
+    }
+
+    /// Find the world at `path`, which can be a world directory or an archive containing one. See
+    /// [`open_storage()`].
+    pub fn try_from_path(path: PathBuf) -> anyhow::Result<Self> {
+        let (storage, path) = open_storage(&path)?;
+        Self::try_from_storage(storage, path)
+    }
+
+    /// Open the world whose root directory is at `path` in `storage`.
+    pub fn try_from_storage(storage: Arc<dyn WorldStorage>, path: PathBuf) -> anyhow::Result<Self> {
+        let level_path = path.join("level.dat");
+        let level = if storage.is_file(&level_path) {
+            read_level_dat(storage.as_ref(), &level_path)
+                .inspect_err(|err| log::warn!("failed to read {:?}: {err}", &level_path))
+                .ok()
+        } else {
+            None
+        };
+
+        let db = Arc::new(LevelDb::open(storage.clone(), &path.join("db"))?);
+
+        // Find every chunk with blocks, and the newest change to any of its records
+        let mut chunks: HashMap<i32, BTreeMap<CCoords, u64>> = HashMap::new();
+        let mut with_blocks: HashSet<(i32, CCoords)> = HashSet::new();
+        let mut min_sub_chunk: HashMap<i32, i8> = HashMap::new();
+        for item in db.keys() {
+            let (key, sequence) = item?;
+            let Some(key) = ChunkKey::parse(&key) else {
+                continue;
+            };
+            let latest = chunks
+                .entry(key.dimension)
+                .or_default()
+                .entry(key.coords)
+                .or_default();
+            *latest = (*latest).max(sequence);
+            if matches!(key.tag, TAG_SUB_CHUNK | TAG_VERSION | TAG_LEGACY_VERSION) {
+                with_blocks.insert((key.dimension, key.coords));
+            }
+            if let Some(y) = key.sub_chunk {
+                let min_y = min_sub_chunk.entry(key.dimension).or_insert(y);
+                *min_y = (*min_y).min(y);
+            }
+        }
+
+        let mut dimensions = HashMap::new();
+        for (index, mut chunks) in chunks {
+            chunks.retain(|coords, _| with_blocks.contains(&(index, *coords)));
+            let (id, height_range) = match index {
+                0 if min_sub_chunk.get(&index).is_some_and(|&y| y < 0) => {
+                    (DimensionID::Overworld, HeightRange::OVERWORLD)
+                }
+                0 => (DimensionID::Overworld, HeightRange::LEGACY),
+                1 => (DimensionID::Nether, NETHER_HEIGHT),
+                2 => (DimensionID::TheEnd, HeightRange::LEGACY),
+                _ => continue,
+            };
+            if chunks.is_empty() {
+                continue;
+            }
+            dimensions.insert(
+                id.clone(),
+                BedrockDimension {
+                    id,
+                    index,
+                    height_range,
+                    chunks,
+                    db: db.clone(),
+                    errors: ErrorReport::default(),
+                },
+            );
+        }
+        if dimensions.is_empty() {
+            return Err(anyhow!("No dimensions found"));
+        }
+        Ok(Self {
+            path,
+            storage,
+            level,
+            dimensions,
+        })
+    }
+
+    pub fn get_dimension(&self, id: &DimensionID) -> Option<&BedrockDimension> {
+        self.dimensions.get(id)
+    }
+
+    /// Collect the errors recorded for all dimensions so far.
+    pub fn error_summary(&self) -> ErrorSummary {
+        let mut summary = ErrorSummary::default();
+        for id in self.dimension_ids() {
+            for error in self.dimensions[id].errors.errors() {
+                summary.errors.push(DimensionError {
+                    dimension: id.namespaced_id().to_owned(),
+                    error,
+                });
+            }
+        }
+        summary.error_count = summary.errors.len();
+        summary
+    }
+
+    /// Get the IDs of all dimensions found in the world, in a consistent order.
+    pub fn dimension_ids(&self) -> Vec<&DimensionID> {
+        let mut ids: Vec<_> = self.dimensions.keys().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// Read Bedrock's `level.dat`: an 8 byte header (format version and length) followed by
+/// little-endian NBT.
+fn read_level_dat(storage: &dyn WorldStorage, path: &Path) -> anyhow::Result<LevelInfo> {
+    let mut raw = Vec::new();
+    storage.open(path)?.read_to_end(&mut raw)?;
+    let mut data = raw
+        .get(8..)
+        .ok_or_else(|| anyhow!("level.dat is too short"))?;
+    let compound = Value::Compound(nbt::read_compound(&mut data)?);
+    let level_data = fastnbt::from_value(&compound)?;
+    Ok(LevelInfo::from_nbt(level_data))
+}
+
+/// A dimension of a Bedrock world.
+#[derive(derive_more::Debug)]
+pub struct BedrockDimension {
+    pub id: DimensionID,
+    /// The dimension's number in chunk keys.
+    index: i32,
+    pub height_range: HeightRange,
+    /// The chunks that have blocks, with the LevelDB sequence number of their latest change.
+    #[debug(ignore)]
+    chunks: BTreeMap<CCoords, u64>,
+    #[debug(ignore)]
+    db: Arc<LevelDb>,
+    /// Problems found while reading the dimension.
+    pub errors: ErrorReport,
+}
+
+impl BedrockDimension {
+    fn get_record(
+        &self,
+        coords: CCoords,
+        tag: u8,
+        sub_chunk: Option<i8>,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let key = ChunkKey {
+            coords,
+            dimension: self.index,
+            tag,
+            sub_chunk,
+        };
+        self.db.get(&key.encode())
+    }
+
+    /// Read the records for the chunk at `coords` from the database.
+    fn read_chunk(&self, coords: CCoords) -> anyhow::Result<ChunkRecords> {
+        let first_section = self.height_range.min_y.div_euclid(CHUNK_SIZE as i32);
+        let mut sub_chunks = Vec::new();
+        for y in first_section..first_section + self.height_range.section_count() as i32 {
+            if let Some(data) = self.get_record(coords, TAG_SUB_CHUNK, Some(y as i8))? {
+                sub_chunks.push((y as i8, data));
+            }
+        }
+        Ok(ChunkRecords {
+            sub_chunks,
+            data_3d: self.get_record(coords, TAG_DATA_3D, None)?,
+            data_2d: self.get_record(coords, TAG_DATA_2D, None)?,
+            finalized_state: self.get_record(coords, TAG_FINALIZED_STATE, None)?,
+        })
+    }
+
+    /// Convert a chunk's records to a [`Chunk`].
+    fn parse_chunk(
+        &self,
+        coords: CCoords,
+        records: ChunkRecords,
+        settings: &Settings,
+    ) -> anyhow::Result<Chunk> {
+        // Chunks from before `FinalizedState` existed were always finished
+        let fully_generated = match records.finalized_state {
+            Some(data) => (&data[..]).read_i32::<LittleEndian>()? >= FINALIZED_DONE,
+            None => true,
+        };
+        let biomes = if let Some(data) = records.data_3d {
+            let base_section = match self.id {
+                DimensionID::Overworld => HeightRange::OVERWORLD.min_y / CHUNK_SIZE as i32,
+                _ => 0,
+            };
+            ChunkBiomes::Sections(base_section, read_biomes_3d(&data)?)
+        } else if let Some(data) = records.data_2d {
+            ChunkBiomes::Columns(
+                data.get(512..512 + 256)
+                    .ok_or_else(|| anyhow!("Data2D record is too short"))?
+                    .iter()
+                    .map(|&id| id as i32)
+                    .collect(),
+            )
+        } else {
+            ChunkBiomes::Columns(Vec::new())
+        };
+
+        let mut chunk = Chunk {
+            coords,
+            sections: Vec::with_capacity(records.sub_chunks.len()),
+            fully_generated,
+            status: None,
+            missing_light: false,
+            heightmaps: Heightmaps::default(),
+        };
+        let mut sky_light_data: SkyLightData = Vec::with_capacity(records.sub_chunks.len());
+        for (key_y, data) in records.sub_chunks {
+            let (section_y, storages) =
+                read_sub_chunk(&data, key_y).map_err(|err| anyhow!("sub-chunk {key_y}: {err}"))?;
+            let Some(blocks) = storages.first() else {
+                continue;
+            };
+            let mut section = build_section(coords, section_y, blocks, storages.get(1), settings);
+            apply_biomes(&mut section, &biomes, section_y);
+            chunk.sections.push(section);
+            sky_light_data.push(None);
+        }
+        apply_sky_light(&mut chunk, sky_light_data);
+        Ok(chunk)
+    }
+}
+
+impl WorldSource for BedrockDimension {
+    fn region_coords(&self) -> Vec<RCoords> {
+        let mut region_coords: Vec<_> = self
+            .chunks
+            .keys()
+            .map(|coords| coords.to_region_coords().0)
+            .collect();
+        region_coords.sort();
+        region_coords.dedup();
+        region_coords
+    }
+
+    fn height_range(&self) -> HeightRange {
+        self.height_range
+    }
+
+    fn errors(&self) -> &ErrorReport {
+        &self.errors
+    }
+
+    fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>> {
+        if !self.chunks.contains_key(&chunk_coords) {
+            return Ok(None);
+        }
+        Err(anyhow!("Bedrock chunks aren't stored as NBT"))
+    }
+
+    fn load_chunk(
+        &self,
+        chunk_coords: CCoords,
+        settings: &Settings,
+    ) -> Result<Option<Chunk>, (ErrorStage, anyhow::Error)> {
+        if !self.chunks.contains_key(&chunk_coords) {
+            return Ok(None);
+        }
+        let records = self
+            .read_chunk(chunk_coords)
+            .map_err(|err| (ErrorStage::ChunkRead, err))?;
+        self.parse_chunk(chunk_coords, records, settings)
+            .map(Some)
+            .map_err(|err| (ErrorStage::ChunkParse, err))
+    }
+
+    /// Bedrock doesn't record modification times, so this uses the sequence number of the latest
+    /// change to each chunk. These only need to differ when a chunk has changed, and sequence
+    /// numbers too large for a timestamp are clamped rather than wrapped, so they never go back.
+    fn get_region_timestamps(
+        &self,
+        region_coords: RCoords,
+    ) -> anyhow::Result<Option<[u32; REGION_CHUNK_COUNT]>> {
+        let mut timestamps = [0; REGION_CHUNK_COUNT];
+        let mut found = false;
+        for (i, timestamp) in timestamps.iter_mut().enumerate() {
+            let coords = CIndex::from_flat_index(i).to_chunk_coords(region_coords);
+            if let Some(&sequence) = self.chunks.get(&coords) {
+                *timestamp = u32::try_from(sequence).unwrap_or(u32::MAX).max(1);
+                found = true;
+            }
+        }
+        Ok(found.then_some(timestamps))
+    }
+}
+
+/// The key of a chunk record in the database.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct ChunkKey {
+    coords: CCoords,
+    dimension: i32,
+    tag: u8,
+    sub_chunk: Option<i8>,
+}
+
+impl ChunkKey {
+    /// Parse a database key, returning `None` if it isn't a chunk record that's used here.
+    fn parse(key: &[u8]) -> Option<Self> {
+        let mut data = key;
+        let x = data.read_i32::<LittleEndian>().ok()?;
+        let z = data.read_i32::<LittleEndian>().ok()?;
+        let dimension = match data.len() {
+            1 | 2 => 0,
+            5 | 6 => data
+                .read_i32::<LittleEndian>()
+                .ok()
+                .filter(|&d| d == 1 || d == 2)?,
+            _ => return None,
+        };
+        let tag = data.read_u8().ok()?;
+        let sub_chunk = match (tag, data.len()) {
+            (TAG_SUB_CHUNK, 1) => Some(data.read_i8().ok()?),
+            (
+                TAG_DATA_3D | TAG_VERSION | TAG_DATA_2D | TAG_FINALIZED_STATE | TAG_LEGACY_VERSION,
+                0,
+            ) => None,
+            _ => return None,
+        };
+        Some(Self {
+            coords: CCoords((x, z).into()),
+            dimension,
+            tag,
+            sub_chunk,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut key = Vec::with_capacity(14);
+        key.extend_from_slice(&self.coords.x().to_le_bytes());
+        key.extend_from_slice(&self.coords.z().to_le_bytes());
+        if self.dimension != 0 {
+            key.extend_from_slice(&self.dimension.to_le_bytes());
+        }
+        key.push(self.tag);
+        if let Some(y) = self.sub_chunk {
+            key.push(y as u8);
+        }
+        key
+    }
+}
+
+/// The records of a chunk that are needed to build a [`Chunk`].
+struct ChunkRecords {
+    /// Sub-chunk data by section Y, in order.
+    sub_chunks: Vec<(i8, Vec<u8>)>,
+    data_3d: Option<Vec<u8>>,
+    data_2d: Option<Vec<u8>>,
+    finalized_state: Option<Vec<u8>>,
+}
+
+/// A block storage from a sub-chunk.
+struct BlockStorage {
+    /// Palette index of each block, in Bedrock's XZY order.
+    indexes: Vec<u16>,
+    palette: Vec<HashMap<String, Value>>,
+}
+
+/// Read a `SubChunkPrefix` record, returning the section Y and its block storages. `key_y` is the
+/// section Y from the record's key, for versions that don't include it.
+fn read_sub_chunk(mut data: &[u8], key_y: i8) -> anyhow::Result<(i8, Vec<BlockStorage>)> {
+    let version = data.read_u8()?;
+    let (storage_count, section_y) = match version {
+        1 => (1, key_y),
+        8 => (data.read_u8()?, key_y),
+        9 => (data.read_u8()?, data.read_i8()?),
+        _ => return Err(anyhow!("unsupported sub-chunk version {}", version)),
+    };
+    let mut storages = Vec::with_capacity(storage_count as usize);
+    for _ in 0..storage_count {
+        let header = data.read_u8()?;
+        if header & 1 != 0 {
+            return Err(anyhow!("block storage uses runtime IDs"));
+        }
+        let indexes = read_packed_indexes(&mut data, header >> 1)?;
+        let palette_len = if header >> 1 == 0 {
+            1
+        } else {
+            data.read_i32::<LittleEndian>()?.max(0) as usize
+        };
+        let palette = (0..palette_len)
+            .map(|_| nbt::read_compound(&mut data))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if indexes.iter().any(|&i| i as usize >= palette.len()) {
+            return Err(anyhow!("block index out of range of palette"));
+        }
+        storages.push(BlockStorage { indexes, palette });
+    }
+    Ok((section_y, storages))
+}
+
+/// Read the indexes of a block or biome storage with `bits` per value. Values are packed into
+/// little-endian u32 words without spanning across words.
+fn read_packed_indexes(data: &mut &[u8], bits: u8) -> anyhow::Result<Vec<u16>> {
+    if bits == 0 {
+        return Ok(vec![0; SECTION_BLOCK_COUNT]);
+    }
+    if !matches!(bits, 1..=6 | 8 | 16) {
+        return Err(anyhow!("invalid bits per value: {}", bits));
+    }
+    let per_word = 32 / bits as usize;
+    let word_count = SECTION_BLOCK_COUNT.div_ceil(per_word);
+    let mask = (1u32 << bits) - 1;
+    let mut indexes = Vec::with_capacity(SECTION_BLOCK_COUNT);
+    for _ in 0..word_count {
+        let word = data.read_u32::<LittleEndian>()?;
+        for i in 0..per_word {
+            indexes.push((word >> (i * bits as usize) & mask) as u16);
+        }
+    }
+    indexes.truncate(SECTION_BLOCK_COUNT);
+    Ok(indexes)
+}
+
+/// Biome IDs of a chunk.
+enum ChunkBiomes {
+    /// One ID per block for each section, from the section at the given Y upwards.
+    Sections(i32, Vec<Vec<i32>>),
+    /// One ID per column, indexed by `z * 16 + x`.
+    Columns(Vec<i32>),
+}
+
+/// Read the biome storages from a `Data3D` record.
+fn read_biomes_3d(data: &[u8]) -> anyhow::Result<Vec<Vec<i32>>> {
+    let mut data = data
+        .get(512..)
+        .ok_or_else(|| anyhow!("Data3D record is too short"))?;
+    let mut sections: Vec<Vec<i32>> = Vec::new();
+    while let Ok(header) = data.read_u8() {
+        if header == BIOME_STORAGE_COPY {
+            let previous = sections
+                .last()
+                .cloned()
+                .ok_or_else(|| anyhow!("first biome storage refers to previous"))?;
+            sections.push(previous);
+            continue;
+        }
+        let bits = header >> 1;
+        let indexes = read_packed_indexes(&mut data, bits)?;
+        let palette_len = if bits == 0 {
+            1
+        } else {
+            data.read_i32::<LittleEndian>()?.max(0) as usize
+        };
+        let palette = (0..palette_len)
+            .map(|_| data.read_i32::<LittleEndian>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids = indexes
+            .iter()
+            .map(|&i| palette.get(i as usize).copied().unwrap_or(-1))
+            .collect();
+        sections.push(ids);
+    }
+    Ok(sections)
+}
+
+/// Get the index into Bedrock's XZY-ordered arrays for block `index`.
+fn bedrock_index(index: BIndex) -> usize {
+    ((index.x() << 8) | (index.z() << 4) | index.y()) as usize
+}
+
+/// Build a section from its first block storage, and the second storage (if any) for water in
+/// waterlogged blocks.
+fn build_section(
+    coords: CCoords,
+    section_y: i8,
+    blocks: &BlockStorage,
+    liquids: Option<&BlockStorage>,
+    settings: &Settings,
+) -> Section {
+    let states: Vec<BlockState> = blocks.palette.iter().map(convert_palette_entry).collect();
+    let liquid_is_water: Vec<bool> = liquids
+        .map(|liquids| {
+            liquids
+                .palette
+                .iter()
+                .map(|entry| convert_palette_entry(entry).name == "minecraft:water")
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Palette of (block index, waterlogged) pairs
+    let mut palette_keys: Vec<(u16, bool)> = Vec::new();
+    let mut palette_indexes: HashMap<(u16, bool), u16> = HashMap::new();
+    let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+    for (i, data) in block_data.iter_mut().enumerate() {
+        let source = bedrock_index(BIndex::from_flat_index(i));
+        let waterlogged =
+            liquids.is_some_and(|liquids| liquid_is_water[liquids.indexes[source] as usize]);
+        let key = (blocks.indexes[source], waterlogged);
+        let index = *palette_indexes.entry(key).or_insert_with(|| {
+            palette_keys.push(key);
+            (palette_keys.len() - 1) as u16
+        });
+        data.set_state_index(index);
+    }
+    let block_palette = palette_keys
+        .into_iter()
+        .map(|(index, waterlogged)| {
+            let state = &states[index as usize];
+            let state = if !waterlogged {
+                state.clone()
+            } else if state.name == "minecraft:air" {
+                BlockState::new(intern_str("minecraft:water"))
+            } else {
+                state.clone().with_property("waterlogged", "true")
+            };
+            convert_block_state(state, settings)
+        })
+        .collect();
+
+    Section {
+        base: BCoords(
+            (
+                coords.x() * CHUNK_SIZE as i32,
+                coords.z() * CHUNK_SIZE as i32,
+                section_y as i32 * CHUNK_SIZE as i32,
+            )
+                .into(),
+        ),
+        block_data,
+        block_palette,
+        biome_palette: Vec::new(),
+        block_entities: HashMap::new(),
+    }
+}
+
+fn convert_palette_entry(entry: &HashMap<String, Value>) -> BlockState {
+    let name = match entry.get("name") {
+        Some(Value::String(name)) => name.as_str(),
+        _ => "minecraft:air",
+    };
+    let states = match entry.get("states") {
+        Some(Value::Compound(states)) => Some(states),
+        _ => None,
+    };
+    mapping::convert_block(name, states)
+}
+
+/// Set the biome index of each block in `section` and its biome palette.
+fn apply_biomes(section: &mut Section, biomes: &ChunkBiomes, section_y: i8) {
+    let mut palette_ids: Vec<Option<i32>> = Vec::new();
+    for (i, data) in section.block_data.iter_mut().enumerate() {
+        let index = BIndex::from_flat_index(i);
+        let id = match biomes {
+            ChunkBiomes::Sections(base, sections) => {
+                // Sections above the last storage have the same biomes as it
+                let offset = usize::try_from(section_y as i32 - base).ok();
+                offset
+                    .and_then(|offset| sections.get(offset).or(sections.last()))
+                    .map(|ids| ids[bedrock_index(index)])
+            }
+            ChunkBiomes::Columns(ids) => ids
+                .get((index.z() * CHUNK_SIZE + index.x()) as usize)
+                .copied(),
+        };
+        let palette_index = match palette_ids.iter().position(|&p| p == id) {
+            Some(palette_index) => palette_index,
+            None => {
+                palette_ids.push(id);
+                palette_ids.len() - 1
+            }
+        };
+        data.set_biome_index(palette_index as u8);
+    }
+    section.biome_palette = palette_ids
+        .into_iter()
+        .map(|id| intern_str(id.and_then(mapping::biome_name).unwrap_or(DEFAULT_BIOME)))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::leveldb::tests::encode_log;
+    use super::nbt::tests::encode_compound;
+    use super::*;
+    use crate::world::FileSystem;
+    use crate::world::tests::test_settings;
+
+    fn palette_entry(name: &str, states: &[(&str, Value)]) -> Vec<u8> {
+        let states = Value::Compound(
+            states
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        );
+        encode_compound(&[
+            ("name", &Value::String(name.to_owned())),
+            ("states", &states),
+            ("version", &Value::Int(18100737)),
+        ])
+    }
+
+    /// Encode a version 9 sub-chunk with a storage of stone and oak stairs (at x=1, z=2, y=3), and
+    /// a storage with water at the stairs.
+    fn encode_sub_chunk(y: i8) -> Vec<u8> {
+        let stairs = (1 << 8) | (2 << 4) | 3;
+        let mut data = vec![9, 2, y as u8];
+        for entries in [
+            [
+                palette_entry("minecraft:stone", &[]),
+                palette_entry(
+                    "minecraft:oak_stairs",
+                    &[
+                        ("weirdo_direction", Value::Int(2)),
+                        ("upside_down_bit", Value::Byte(0)),
+                    ],
+                ),
+            ],
+            [
+                palette_entry("minecraft:air", &[]),
+                palette_entry("minecraft:water", &[("liquid_depth", Value::Int(0))]),
+            ],
+        ] {
+            // 1 bit per block
+            data.push(1 << 1);
+            let mut words = vec![0u32; SECTION_BLOCK_COUNT / 32];
+            words[stairs / 32] |= 1 << (stairs % 32);
+            for word in words {
+                data.extend_from_slice(&word.to_le_bytes());
+            }
+            data.extend_from_slice(&2i32.to_le_bytes());
+            for entry in entries {
+                data.extend_from_slice(&entry);
+            }
+        }
+        data
+    }
+
+    fn encode_data_3d() -> Vec<u8> {
+        let mut data = vec![0; 512];
+        // Section -4 is all cherry grove, the rest copies it
+        data.push(0);
+        data.extend_from_slice(&192i32.to_le_bytes());
+        data.extend(std::iter::repeat_n(BIOME_STORAGE_COPY, 23));
+        data
+    }
+
+    #[test]
+    fn test_chunk_key() {
+        let key = ChunkKey {
+            coords: CCoords((-1, 2).into()),
+            dimension: 1,
+            tag: TAG_SUB_CHUNK,
+            sub_chunk: Some(-3),
+        };
+        assert_eq!(key.encode().len(), 14);
+        assert_eq!(ChunkKey::parse(&key.encode()), Some(key));
+        assert_eq!(ChunkKey::parse(b"BiomeData"), None);
+        assert_eq!(ChunkKey::parse(b"~local_player"), None);
+    }
+
+    #[test]
+    fn test_bedrock_world() {
+        let chunk_key = |x, z, tag, sub_chunk| {
+            ChunkKey {
+                coords: CCoords((x, z).into()),
+                dimension: 0,
+                tag,
+                sub_chunk,
+            }
+            .encode()
+        };
+        let records = [
+            (chunk_key(0, -1, TAG_VERSION, None), vec![40]),
+            (
+                chunk_key(0, -1, TAG_SUB_CHUNK, Some(-2)),
+                encode_sub_chunk(-2),
+            ),
+            (chunk_key(0, -1, TAG_DATA_3D, None), encode_data_3d()),
+            (
+                chunk_key(0, -1, TAG_FINALIZED_STATE, None),
+                2i32.to_le_bytes().to_vec(),
+            ),
+            (b"~local_player".to_vec(), vec![1, 2, 3]),
+        ];
+        let batch: Vec<_> = records
+            .iter()
+            .map(|(key, value)| (&key[..], Some(&value[..])))
+            .collect();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("db")).unwrap();
+        std::fs::write(dir.join("db/000003.log"), encode_log(&[batch])).unwrap();
+        let mut level_dat = vec![0; 8];
+        level_dat.extend_from_slice(&encode_compound(&[
+            ("LevelName", &Value::String("Bedrock Test".to_owned())),
+            ("SpawnX", &Value::Int(8)),
+            ("SpawnY", &Value::Int(70)),
+            ("SpawnZ", &Value::Int(-8)),
+        ]));
+        std::fs::write(dir.join("level.dat"), level_dat).unwrap();
+        let world = BedrockWorld::try_from_storage(Arc::new(FileSystem), dir.to_owned()).unwrap();
+        let level = world.level.as_ref().unwrap();
+        assert_eq!(level.name, "Bedrock Test");
+        assert_eq!(level.spawn, BCoords((8, -8, 70).into()));
+        assert_eq!(world.dimension_ids(), vec![&DimensionID::Overworld]);
+
+        let dimension = world.get_dimension(&DimensionID::Overworld).unwrap();
+        assert_eq!(dimension.height_range, HeightRange::OVERWORLD);
+        assert_eq!(dimension.region_coords(), vec![RCoords((0, -1).into())]);
+        let timestamps = dimension
+            .get_region_timestamps(RCoords((0, -1).into()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamps.iter().filter(|&&t| t != 0).count(), 1);
+
+        let settings = test_settings();
+        let chunk = dimension
+            .load_chunk(CCoords((0, -1).into()), &settings)
+            .unwrap()
+            .unwrap();
+        assert!(chunk.fully_generated);
+        assert_eq!(chunk.sections.len(), 1);
+        let section = &chunk.sections[0];
+        assert_eq!(section.base, BCoords((0, -16, -32).into()));
+        let stone = section.get_block(BIndex((0, 0, 0).into()));
+        assert_eq!(stone.state.name, "minecraft:stone");
+        assert_eq!(stone.biome, "minecraft:cherry_grove");
+        assert_eq!(stone.lighting.effective(), 15);
+        let stairs = section.get_block(BIndex((1, 2, 3).into()));
+        assert_eq!(stairs.state.name, "minecraft:oak_stairs");
+        assert_eq!(stairs.state.get_property("waterlogged"), Some("true"));
+        assert!(
+            dimension
+                .load_chunk(CCoords((5, 5).into()), &settings)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use byteorder::{LittleEndian, ReadBytesExt};
+use fastnbt::Value;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// Lists and compounds nested deeper than this are assumed to be corrupt.
+const MAX_DEPTH: usize = 512;
+
+/// Read a little-endian NBT compound from the start of `data`, advancing `data` past it. Bedrock
+/// stores several compounds back to back, e.g. in block palettes.
+pub(super) fn read_compound(data: &mut &[u8]) -> anyhow::Result<HashMap<String, Value>> {
+    let tag = data.read_u8()?;
+    if tag != TAG_COMPOUND {
+        return Err(anyhow!("expected NBT compound, found tag {}", tag));
+    }
+    let _name = read_string(data)?;
+    match read_payload(data, TAG_COMPOUND, 0)? {
+        Value::Compound(compound) => Ok(compound),
+        _ => unreachable!(),
+    }
+}
+
+fn read_string(data: &mut &[u8]) -> anyhow::Result<String> {
+    let len = data.read_u16::<LittleEndian>()? as usize;
+    if len > data.len() {
+        return Err(anyhow!("NBT string extends past end of data"));
+    }
+    let (raw, rest) = data.split_at(len);
+    *data = rest;
+    Ok(String::from_utf8_lossy(raw).into_owned())
+}
+
+/// Read an array length, checking that there's enough data left for `len` elements of
+/// `element_size` bytes.
+fn read_len(data: &mut &[u8], element_size: usize) -> anyhow::Result<usize> {
+    let len = data.read_i32::<LittleEndian>()?.max(0) as usize;
+    if len.saturating_mul(element_size) > data.len() {
+        return Err(anyhow!("NBT array extends past end of data"));
+    }
+    Ok(len)
+}
+
+fn read_payload(data: &mut &[u8], tag: u8, depth: usize) -> anyhow::Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(anyhow!("NBT nested too deeply"));
+    }
+    let value = match tag {
+        TAG_BYTE => Value::Byte(data.read_i8()?),
+        TAG_SHORT => Value::Short(data.read_i16::<LittleEndian>()?),
+        TAG_INT => Value::Int(data.read_i32::<LittleEndian>()?),
+        TAG_LONG => Value::Long(data.read_i64::<LittleEndian>()?),
+        TAG_FLOAT => Value::Float(data.read_f32::<LittleEndian>()?),
+        TAG_DOUBLE => Value::Double(data.read_f64::<LittleEndian>()?),
+        TAG_BYTE_ARRAY => {
+            let len = read_len(data, 1)?;
+            let values = (0..len).map(|_| data.read_i8()).collect::<Result<_, _>>()?;
+            Value::ByteArray(fastnbt::ByteArray::new(values))
+        }
+        TAG_STRING => Value::String(read_string(data)?),
+        TAG_LIST => {
+            let element_tag = data.read_u8()?;
+            let len = read_len(data, 0)?;
+            let mut values = Vec::new();
+            for _ in 0..len {
+                values.push(read_payload(data, element_tag, depth + 1)?);
+            }
+            Value::List(values)
+        }
+        TAG_COMPOUND => {
+            let mut compound = HashMap::new();
+            loop {
+                let tag = data.read_u8()?;
+                if tag == TAG_END {
+                    break;
+                }
+                let name = read_string(data)?;
+                compound.insert(name, read_payload(data, tag, depth + 1)?);
+            }
+            Value::Compound(compound)
+        }
+        TAG_INT_ARRAY => {
+            let len = read_len(data, 4)?;
+            let values = (0..len)
+                .map(|_| data.read_i32::<LittleEndian>())
+                .collect::<Result<_, _>>()?;
+            Value::IntArray(fastnbt::IntArray::new(values))
+        }
+        TAG_LONG_ARRAY => {
+            let len = read_len(data, 8)?;
+            let values = (0..len)
+                .map(|_| data.read_i64::<LittleEndian>())
+                .collect::<Result<_, _>>()?;
+            Value::LongArray(fastnbt::LongArray::new(values))
+        }
+        _ => return Err(anyhow!("unknown NBT tag: {}", tag)),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Encode a compound of `(name, value)` pairs as little-endian NBT.
+    pub(in crate::world::bedrock) fn encode_compound(entries: &[(&str, &Value)]) -> Vec<u8> {
+        fn tag(value: &Value) -> u8 {
+            match value {
+                Value::Byte(_) => TAG_BYTE,
+                Value::Short(_) => TAG_SHORT,
+                Value::Int(_) => TAG_INT,
+                Value::Long(_) => TAG_LONG,
+                Value::Float(_) => TAG_FLOAT,
+                Value::Double(_) => TAG_DOUBLE,
+                Value::ByteArray(_) => TAG_BYTE_ARRAY,
+                Value::String(_) => TAG_STRING,
+                Value::List(_) => TAG_LIST,
+                Value::Compound(_) => TAG_COMPOUND,
+                Value::IntArray(_) => TAG_INT_ARRAY,
+                Value::LongArray(_) => TAG_LONG_ARRAY,
+            }
+        }
+        fn write_string(output: &mut Vec<u8>, s: &str) {
+            output.extend_from_slice(&(s.len() as u16).to_le_bytes());
+            output.extend_from_slice(s.as_bytes());
+        }
+        fn write_entries(output: &mut Vec<u8>, entries: &[(&str, &Value)]) {
+            for (name, value) in entries {
+                output.push(tag(value));
+                write_string(output, name);
+                write_payload(output, value);
+            }
+            output.push(TAG_END);
+        }
+        fn write_payload(output: &mut Vec<u8>, value: &Value) {
+            match value {
+                Value::Byte(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::Short(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::Int(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::Long(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::Float(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::Double(v) => output.extend_from_slice(&v.to_le_bytes()),
+                Value::ByteArray(values) => {
+                    output.extend_from_slice(&(values.len() as i32).to_le_bytes());
+                    for v in values.iter() {
+                        output.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+                Value::String(s) => write_string(output, s),
+                Value::List(values) => {
+                    output.push(values.first().map_or(TAG_END, tag));
+                    output.extend_from_slice(&(values.len() as i32).to_le_bytes());
+                    for value in values {
+                        write_payload(output, value);
+                    }
+                }
+                Value::Compound(compound) => {
+                    let mut sorted: Vec<_> =
+                        compound.iter().map(|(k, v)| (k.as_str(), v)).collect();
+                    sorted.sort_by_key(|(name, _)| *name);
+                    write_entries(output, &sorted);
+                }
+                Value::IntArray(values) => {
+                    output.extend_from_slice(&(values.len() as i32).to_le_bytes());
+                    for v in values.iter() {
+                        output.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+                Value::LongArray(values) => {
+                    output.extend_from_slice(&(values.len() as i32).to_le_bytes());
+                    for v in values.iter() {
+                        output.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+            }
+        }
+        let mut output = vec![TAG_COMPOUND];
+        write_string(&mut output, "");
+        write_entries(&mut output, entries);
+        output
+    }
+
+    #[test]
+    fn test_read_compound() {
+        let states = Value::Compound(HashMap::from([
+            ("pillar_axis".to_owned(), Value::String("y".to_owned())),
+            ("age_bit".to_owned(), Value::Byte(1)),
+        ]));
+        let mut data = encode_compound(&[
+            ("name", &Value::String("minecraft:oak_log".to_owned())),
+            ("states", &states),
+            ("version", &Value::Int(18100737)),
+        ]);
+        data.extend_from_slice(&encode_compound(&[]));
+
+        let mut rest = &data[..];
+        let compound = read_compound(&mut rest).unwrap();
+        assert_eq!(
+            compound["name"],
+            Value::String("minecraft:oak_log".to_owned())
+        );
+        assert_eq!(compound["states"], states);
+        assert_eq!(compound["version"], Value::Int(18100737));
+        assert!(read_compound(&mut rest).unwrap().is_empty());
+        assert!(rest.is_empty());
+        assert!(read_compound(&mut &data[..10]).is_err());
+    }
+
+    #[test]
+    fn test_read_all_tags() {
+        let values = [
+            ("byte", Value::Byte(-1)),
+            ("short", Value::Short(300)),
+            ("int", Value::Int(-70000)),
+            ("long", Value::Long(1 << 40)),
+            ("float", Value::Float(0.5)),
+            ("double", Value::Double(-2.25)),
+            (
+                "bytes",
+                Value::ByteArray(fastnbt::ByteArray::new(vec![1, -2])),
+            ),
+            ("string", Value::String("stone".to_owned())),
+            ("list", Value::List(vec![Value::Short(1), Value::Short(2)])),
+            ("empty_list", Value::List(Vec::new())),
+            ("ints", Value::IntArray(fastnbt::IntArray::new(vec![3, -4]))),
+            (
+                "longs",
+                Value::LongArray(fastnbt::LongArray::new(vec![5, -6])),
+            ),
+        ];
+        let entries: Vec<_> = values.iter().map(|(name, value)| (*name, value)).collect();
+        let data = encode_compound(&entries);
+        let compound = read_compound(&mut &data[..]).unwrap();
+        for (name, value) in values.iter() {
+            assert_eq!(&compound[*name], value, "{name}");
+        }
+    }
+}
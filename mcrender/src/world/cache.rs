@@ -2,7 +2,7 @@ use lru::LruCache;
 use std::sync::Arc;
 
 use crate::settings::Settings;
-use crate::world::{CCoords, Chunk, DimensionInfo, RCoords, REGION_SIZE};
+use crate::world::{CCoords, Chunk, RCoords, REGION_SIZE, WorldSource};
 
 #[derive(Clone, Debug, Default)]
 pub enum ChunkBounds {
@@ -24,6 +24,21 @@ impl ChunkBounds {
         )
     }
 
+    /// Check whether any chunk of the region at `coords` is within the bounds.
+    pub fn intersects_region(&self, coords: RCoords) -> bool {
+        match self {
+            ChunkBounds::Unbounded => true,
+            ChunkBounds::MinMax(min, max) => {
+                let base = coords.to_chunk_coords();
+                let size = REGION_SIZE as i32;
+                base.x() < max.x()
+                    && base.x() + size > min.x()
+                    && base.z() < max.z()
+                    && base.z() + size > min.z()
+            }
+        }
+    }
+
     pub fn contains(&self, coords: &CCoords) -> bool {
         match self {
             ChunkBounds::Unbounded => true,
@@ -34,43 +49,231 @@ impl ChunkBounds {
     }
 }
 
+/// A chunk as it's kept in a [`ChunkCache`].
+#[derive(Clone)]
+enum CachedChunk {
+    /// There's no chunk, or it couldn't be read.
+    Missing,
+    /// A chunk that was only read as the neighbour of a chunk being lit, and hasn't been checked
+    /// or lit itself yet.
+    Loaded(Arc<Chunk>),
+    /// A chunk that isn't rendered because of its generation status.
+    Filtered(Arc<Chunk>),
+    /// A chunk that's ready to render.
+    Ready(Arc<Chunk>),
+}
+
 pub struct ChunkCache<'i, 's> {
-    dim_info: &'i DimensionInfo,
+    source: &'i dyn WorldSource,
     settings: &'s Settings,
     bounds: ChunkBounds,
-    cache: LruCache<CCoords, Option<Arc<Chunk>>>,
+    show_errors: bool,
+    cache: LruCache<CCoords, CachedChunk>,
 }
 
 impl<'i, 's> ChunkCache<'i, 's> {
     pub fn new(
-        dim_info: &'i DimensionInfo,
+        source: &'i dyn WorldSource,
         settings: &'s Settings,
         bounds: ChunkBounds,
         capacity: usize,
     ) -> Self {
         Self {
-            dim_info,
+            source,
             settings,
             bounds,
+            show_errors: false,
             cache: LruCache::new(capacity.try_into().unwrap()),
         }
     }
 
+    /// Set whether chunks that couldn't be read are replaced with [`Chunk::error_pattern()`]
+    /// instead of being treated as missing.
+    pub fn set_show_errors(&mut self, show_errors: bool) {
+        self.show_errors = show_errors;
+    }
+
     pub fn get(&mut self, coords: CCoords) -> Option<Arc<Chunk>> {
         if !self.bounds.contains(&coords) {
             return None;
         }
 
-        self.cache
-            .get_or_insert(coords, || {
-                self.dim_info
-                    .get_raw_chunk(coords)
-                    .ok()
-                    .flatten()
-                    .and_then(|raw_chunk| raw_chunk.parse(self.settings).ok())
-                    .filter(|chunk| chunk.fully_generated)
-                    .map(Arc::new)
-            })
-            .clone()
+        match self.cache.get(&coords) {
+            Some(CachedChunk::Ready(chunk)) => return Some(chunk.clone()),
+            Some(CachedChunk::Missing | CachedChunk::Filtered(_)) => return None,
+            Some(CachedChunk::Loaded(_)) | None => {}
+        }
+        let cached = self.load(coords);
+        self.cache.put(coords, cached.clone());
+        match cached {
+            CachedChunk::Ready(chunk) => Some(chunk),
+            _ => None,
+        }
+    }
+
+    fn load(&mut self, coords: CCoords) -> CachedChunk {
+        // A chunk that was read as the neighbour of another chunk doesn't need reading again
+        let loaded = match self.cache.pop(&coords) {
+            Some(CachedChunk::Loaded(chunk)) => Arc::try_unwrap(chunk).ok(),
+            _ => None,
+        };
+        let result = match loaded {
+            Some(chunk) => Ok(Some(chunk)),
+            None => self.source.load_chunk(coords, self.settings),
+        };
+        let mut chunk = match result {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => return CachedChunk::Missing,
+            Err((stage, err)) => {
+                self.source.report_chunk_error(coords, stage, &err);
+                if !self.show_errors {
+                    return CachedChunk::Missing;
+                }
+                return CachedChunk::Ready(Arc::new(Chunk::error_pattern(
+                    coords,
+                    self.source.height_range(),
+                    self.settings,
+                )));
+            }
+        };
+        if !self.settings.chunk_status.includes(&chunk) {
+            return CachedChunk::Filtered(Arc::new(chunk));
+        }
+        if chunk.missing_light && self.settings.compute_missing_light {
+            chunk.compute_light(|coords| self.load_neighbour(coords));
+        }
+        CachedChunk::Ready(Arc::new(chunk))
+    }
+
+    /// Get a chunk next to one that's being lit, whether or not it's in bounds or would be
+    /// rendered, because light can spread from it. A chunk that has to be read is cached as it
+    /// is, so it isn't read again if it's rendered too. Errors are left to be reported if the
+    /// chunk itself is rendered.
+    fn load_neighbour(&mut self, coords: CCoords) -> Option<Arc<Chunk>> {
+        match self.cache.get(&coords) {
+            Some(
+                CachedChunk::Loaded(chunk)
+                | CachedChunk::Filtered(chunk)
+                | CachedChunk::Ready(chunk),
+            ) => return Some(chunk.clone()),
+            Some(CachedChunk::Missing) => return None,
+            None => {}
+        }
+        match self.source.load_chunk(coords, self.settings) {
+            Ok(Some(chunk)) => {
+                let chunk = Arc::new(chunk);
+                self.cache.put(coords, CachedChunk::Loaded(chunk.clone()));
+                Some(chunk)
+            }
+            Ok(None) => {
+                self.cache.put(coords, CachedChunk::Missing);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::world::tests::test_settings;
+    use crate::world::{
+        ChunkBuilder, ErrorReport, HeightRange, MemorySource, REGION_CHUNK_COUNT, RawChunk,
+    };
+
+    /// A source that counts how many times each chunk is read.
+    struct CountingSource {
+        source: MemorySource,
+        reads: Mutex<BTreeMap<CCoords, usize>>,
+    }
+
+    impl WorldSource for CountingSource {
+        fn region_coords(&self) -> Vec<RCoords> {
+            self.source.region_coords()
+        }
+
+        fn height_range(&self) -> HeightRange {
+            self.source.height_range()
+        }
+
+        fn errors(&self) -> &ErrorReport {
+            self.source.errors()
+        }
+
+        fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>> {
+            *self.reads.lock().entry(chunk_coords).or_default() += 1;
+            self.source.get_raw_chunk(chunk_coords)
+        }
+
+        fn get_region_timestamps(
+            &self,
+            region_coords: RCoords,
+        ) -> anyhow::Result<Option<[u32; REGION_CHUNK_COUNT]>> {
+            self.source.get_region_timestamps(region_coords)
+        }
+    }
+
+    #[test]
+    fn test_chunk_status_filter() {
+        let height_range = HeightRange::OVERWORLD;
+        let mut source = MemorySource::new(height_range);
+        let full = CCoords((0, 0).into());
+        let partial = CCoords((1, 0).into());
+        let chunk = |coords, status| {
+            ChunkBuilder::new(coords, height_range)
+                .with_status(status)
+                .to_nbt()
+                .unwrap()
+        };
+        source.insert_chunk(full, chunk(full, "minecraft:full"), 0);
+        source.insert_chunk(partial, chunk(partial, "minecraft:features"), 0);
+
+        let mut settings = test_settings();
+        let mut cache = ChunkCache::new(&source, &settings, ChunkBounds::Unbounded, 4);
+        assert!(cache.get(full).is_some());
+        assert!(cache.get(partial).is_none());
+
+        settings.chunk_status.render = ["minecraft:features".to_owned()].into();
+        let mut cache = ChunkCache::new(&source, &settings, ChunkBounds::Unbounded, 4);
+        let chunk = cache.get(partial).unwrap();
+        assert_eq!(chunk.status.as_deref(), Some("minecraft:features"));
+        assert!(settings.chunk_status.saturation(&chunk).is_some());
+        assert!(
+            settings
+                .chunk_status
+                .saturation(&cache.get(full).unwrap())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_neighbours_read_once() {
+        let height_range = HeightRange::OVERWORLD;
+        let mut source = MemorySource::new(height_range);
+        for x in 0..3 {
+            let coords = CCoords((x, 0).into());
+            let chunk = ChunkBuilder::new(coords, height_range);
+            source.insert_chunk(coords, chunk.to_nbt().unwrap(), 0);
+        }
+        let source = CountingSource {
+            source,
+            reads: Default::default(),
+        };
+        let mut settings = test_settings();
+        settings.compute_missing_light = true;
+
+        let mut cache = ChunkCache::new(&source, &settings, ChunkBounds::Unbounded, 32);
+        for x in 0..3 {
+            let chunk = cache.get(CCoords((x, 0).into())).unwrap();
+            assert!(!chunk.missing_light);
+        }
+        let reads = source.reads.lock();
+        assert!(reads.values().all(|&count| count == 1), "{reads:?}");
+        assert_eq!(reads.len(), 3 * 5);
     }
 }
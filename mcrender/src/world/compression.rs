@@ -0,0 +1,248 @@
+/*
+Chunk compression notes:
+
+- Each chunk's data in a region file is prefixed with a big-endian u32 length and a single byte
+  compression method. The length includes the compression method byte.
+- If the compression method has bit 128 set, then the chunk data is stored in a separate
+  `c.X.Z.mcc` file alongside the region file (X and Z being global chunk coordinates), and the
+  entire contents of that file is the compressed chunk data.
+- LZ4 (method 4) is not the standard LZ4 frame format, but the block stream written by lz4-java's
+  `LZ4BlockOutputStream`: a sequence of blocks, each with a 21 byte header, terminated by an
+  empty block.
+ */
+
+use std::io;
+use std::io::{Read, Write};
+
+use anyhow::anyhow;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+
+/// Flag set on the compression method byte when the chunk data is stored externally.
+pub(super) const COMPRESSION_FLAG_EXTERNAL: u8 = 128;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, derive_more::Display)]
+pub enum CompressionMethod {
+    #[display("gzip")]
+    Gzip,
+    #[display("zlib")]
+    Zlib,
+    #[display("none")]
+    Uncompressed,
+    #[display("lz4")]
+    Lz4,
+    #[display("custom")]
+    Custom,
+}
+
+impl TryFrom<u8> for CompressionMethod {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(CompressionMethod::Gzip),
+            2 => Ok(CompressionMethod::Zlib),
+            3 => Ok(CompressionMethod::Uncompressed),
+            4 => Ok(CompressionMethod::Lz4),
+            127 => Ok(CompressionMethod::Custom),
+            _ => Err(anyhow!("unknown compression method: {:?}", value)),
+        }
+    }
+}
+
+impl From<CompressionMethod> for u8 {
+    fn from(value: CompressionMethod) -> Self {
+        match value {
+            CompressionMethod::Gzip => 1,
+            CompressionMethod::Zlib => 2,
+            CompressionMethod::Uncompressed => 3,
+            CompressionMethod::Lz4 => 4,
+            CompressionMethod::Custom => 127,
+        }
+    }
+}
+
+impl CompressionMethod {
+    /// Read all of `reader` and decompress it according to this compression method.
+    pub fn decompress<R: Read>(self, mut reader: R) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        match self {
+            CompressionMethod::Gzip => {
+                flate2::read::GzDecoder::new(reader).read_to_end(&mut data)?;
+            }
+            CompressionMethod::Zlib => {
+                flate2::read::ZlibDecoder::new(reader).read_to_end(&mut data)?;
+            }
+            CompressionMethod::Uncompressed => {
+                reader.read_to_end(&mut data)?;
+            }
+            CompressionMethod::Lz4 => {
+                read_lz4_block_stream(&mut reader, &mut data)?;
+            }
+            CompressionMethod::Custom => {
+                // Custom compression is identified by a length-prefixed name; the only thing we
+                // can usefully do with it is report what it was
+                let len = reader.read_u16::<BigEndian>()?;
+                let mut name = vec![0u8; len as usize];
+                reader.read_exact(&mut name)?;
+                return Err(anyhow!(
+                    "custom compression method not supported: {:?}",
+                    String::from_utf8_lossy(&name)
+                ));
+            }
+        }
+        Ok(data)
+    }
+
+    /// Compress `data` according to this compression method, so that [`Self::decompress()`] gives
+    /// it back. Only gzip, zlib and uncompressed are supported.
+    pub fn compress(self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            CompressionMethod::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            CompressionMethod::Zlib => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            CompressionMethod::Uncompressed => Ok(data.to_vec()),
+            CompressionMethod::Lz4 | CompressionMethod::Custom => Err(anyhow!(
+                "writing {} compressed chunks is not supported",
+                self
+            )),
+        }
+    }
+}
+
+const LZ4_BLOCK_MAGIC: &[u8; 8] = b"LZ4Block";
+const LZ4_BLOCK_METHOD_RAW: u8 = 0x10;
+const LZ4_BLOCK_METHOD_LZ4: u8 = 0x20;
+
+/// Decode an lz4-java block stream from `reader`, appending the decompressed data to `output`.
+fn read_lz4_block_stream<R: Read>(reader: &mut R, output: &mut Vec<u8>) -> anyhow::Result<()> {
+    let mut compressed = Vec::new();
+    loop {
+        let mut magic = [0u8; LZ4_BLOCK_MAGIC.len()];
+        match reader.read_exact(&mut magic) {
+            Ok(()) => {}
+            // Tolerate a missing end-of-stream marker if at least one block was read
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !output.is_empty() => break,
+            Err(err) => return Err(err.into()),
+        }
+        if &magic != LZ4_BLOCK_MAGIC {
+            return Err(anyhow!("invalid LZ4 block magic: {:?}", magic));
+        }
+        let token = reader.read_u8()?;
+        let compressed_len = reader.read_u32::<LittleEndian>()? as usize;
+        let original_len = reader.read_u32::<LittleEndian>()? as usize;
+        // Checksum is XXH32 of the decompressed data, not currently verified
+        let _checksum = reader.read_u32::<LittleEndian>()?;
+        if original_len == 0 {
+            // End-of-stream marker
+            break;
+        }
+
+        compressed.resize(compressed_len, 0);
+        reader.read_exact(&mut compressed)?;
+        let start = output.len();
+        match token & 0xF0 {
+            LZ4_BLOCK_METHOD_RAW => {
+                if compressed_len != original_len {
+                    return Err(anyhow!("raw LZ4 block length mismatch"));
+                }
+                output.extend_from_slice(&compressed);
+            }
+            LZ4_BLOCK_METHOD_LZ4 => {
+                output.resize(start + original_len, 0);
+                let written = lz4_flex::block::decompress_into(&compressed, &mut output[start..])?;
+                if written != original_len {
+                    return Err(anyhow!("LZ4 block decompressed to wrong length"));
+                }
+            }
+            method => return Err(anyhow!("unknown LZ4 block method: {:#x}", method)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_data() -> Vec<u8> {
+        (0..10000u32)
+            .flat_map(|i| (i % 251).to_be_bytes())
+            .collect()
+    }
+
+    /// Encode `data` the same way as lz4-java's `LZ4BlockOutputStream`.
+    fn encode_lz4_block_stream(data: &[u8], block_size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chunk in data.chunks(block_size) {
+            let compressed = lz4_flex::block::compress(chunk);
+            output.extend_from_slice(LZ4_BLOCK_MAGIC);
+            output.push(LZ4_BLOCK_METHOD_LZ4);
+            output.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            output.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            output.extend_from_slice(&0u32.to_le_bytes());
+            output.extend_from_slice(&compressed);
+        }
+        output.extend_from_slice(LZ4_BLOCK_MAGIC);
+        output.push(LZ4_BLOCK_METHOD_RAW);
+        output.extend_from_slice(&[0u8; 12]);
+        output
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data = test_data();
+
+        let mut gzip = flate2::write::GzEncoder::new(vec![], Default::default());
+        gzip.write_all(&data).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(CompressionMethod::Gzip.decompress(&gzip[..]).unwrap(), data);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(vec![], Default::default());
+        zlib.write_all(&data).unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert_eq!(CompressionMethod::Zlib.decompress(&zlib[..]).unwrap(), data);
+
+        assert_eq!(
+            CompressionMethod::Uncompressed
+                .decompress(&data[..])
+                .unwrap(),
+            data
+        );
+
+        let lz4 = encode_lz4_block_stream(&data, 4096);
+        assert_eq!(CompressionMethod::Lz4.decompress(&lz4[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_compress() {
+        let data = test_data();
+        for method in [
+            CompressionMethod::Gzip,
+            CompressionMethod::Zlib,
+            CompressionMethod::Uncompressed,
+        ] {
+            let compressed = method.compress(&data).unwrap();
+            assert_eq!(method.decompress(&compressed[..]).unwrap(), data);
+        }
+        assert!(CompressionMethod::Lz4.compress(&data).is_err());
+    }
+
+    #[test]
+    fn test_compression_method() {
+        for value in [1u8, 2, 3, 4, 127] {
+            let method = CompressionMethod::try_from(value).unwrap();
+            assert_eq!(u8::from(method), value);
+        }
+        assert!(CompressionMethod::try_from(0).is_err());
+        assert!(CompressionMethod::try_from(5).is_err());
+    }
+}
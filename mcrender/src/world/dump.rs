@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use fastnbt::Value;
+
+use crate::settings::Settings;
+use crate::world::nbt::compound;
+use crate::world::{BCoords, BIndex, CCoords, CHUNK_SIZE, Chunk, ErrorStage, Section, WorldSource};
+
+fn string(s: impl Into<String>) -> Value {
+    Value::String(s.into())
+}
+
+/// Load the chunk at `coords` the same way it would be loaded for rendering, including computing
+/// its light if that's enabled, but whatever its generation status.
+fn load_chunk(
+    source: &dyn WorldSource,
+    coords: CCoords,
+    settings: &Settings,
+) -> anyhow::Result<Chunk> {
+    let chunk = source
+        .load_chunk(coords, settings)
+        .map_err(|(stage, err)| anyhow!("{stage}: {err}"))?
+        .ok_or_else(|| anyhow!("no chunk at {coords}"))?;
+    Ok(with_light(source, chunk, settings))
+}
+
+/// Compute the light of `chunk` if it's missing and that's enabled, as it would be for
+/// rendering.
+fn with_light(source: &dyn WorldSource, mut chunk: Chunk, settings: &Settings) -> Chunk {
+    if chunk.missing_light && settings.compute_missing_light {
+        chunk.compute_light(|coords| source.load_chunk(coords, settings).ok().flatten());
+    }
+    chunk
+}
+
+/// Describe the decoded palettes of `section`, with the asset rule each block state matched.
+fn dump_section(section: &Section) -> Value {
+    let block_palette = section
+        .block_palette
+        .iter()
+        .zip(section.block_state_counts())
+        .map(|((state, rule), count)| {
+            compound([
+                ("state", string(state.to_string())),
+                ("rule", string(&rule.name)),
+                ("render", string(rule.render.type_name())),
+                ("count", Value::Int(count as i32)),
+            ])
+        })
+        .collect();
+    let biome_palette = section
+        .biome_palette
+        .iter()
+        .map(|biome| string(biome.as_str()))
+        .collect();
+    compound([
+        ("y", Value::Int(section.base.y())),
+        ("block_palette", Value::List(block_palette)),
+        ("biome_palette", Value::List(biome_palette)),
+        (
+            "block_entities",
+            Value::Int(section.block_entities.len() as i32),
+        ),
+    ])
+}
+
+/// Describe the chunk at `coords`: its NBT as stored in the world, and its decoded sections.
+pub fn dump_chunk(
+    source: &dyn WorldSource,
+    coords: CCoords,
+    settings: &Settings,
+) -> anyhow::Result<Value> {
+    // Read the chunk's data once, to show it both as it's stored and decoded. Not every source
+    // has NBT to show, e.g. Bedrock worlds, so those chunks are loaded without it.
+    let (chunk, nbt) = match source.get_raw_chunk(coords) {
+        Ok(Some(raw_chunk)) => {
+            let chunk = raw_chunk
+                .parse(source.height_range(), settings)
+                .map_err(|err| anyhow!("{}: {err}", ErrorStage::ChunkParse))?;
+            let nbt = fastnbt::from_bytes::<Value>(&raw_chunk.data).map_err(anyhow::Error::from);
+            (with_light(source, chunk, settings), nbt)
+        }
+        Ok(None) => return Err(anyhow!("no chunk at {coords}")),
+        Err(err) => (load_chunk(source, coords, settings)?, Err(err)),
+    };
+    let mut dump = HashMap::new();
+    dump.insert("x".to_owned(), Value::Int(coords.x()));
+    dump.insert("z".to_owned(), Value::Int(coords.z()));
+    if let Some(status) = &chunk.status {
+        dump.insert("status".to_owned(), string(status.as_str()));
+    }
+    dump.insert(
+        "fully_generated".to_owned(),
+        Value::Byte(chunk.fully_generated as i8),
+    );
+    dump.insert(
+        "missing_light".to_owned(),
+        Value::Byte(chunk.missing_light as i8),
+    );
+    dump.insert(
+        "sections".to_owned(),
+        Value::List(chunk.sections.iter().map(dump_section).collect()),
+    );
+    match nbt {
+        Ok(nbt) => dump.insert("nbt".to_owned(), nbt),
+        Err(err) => dump.insert("nbt_error".to_owned(), string(err.to_string())),
+    };
+    Ok(Value::Compound(dump))
+}
+
+/// Describe the block at `coords`: everything the renderer knows about it, and the palettes of its
+/// section.
+pub fn dump_block(
+    source: &dyn WorldSource,
+    coords: BCoords,
+    settings: &Settings,
+) -> anyhow::Result<Value> {
+    let chunk_coords = CCoords(
+        (
+            coords.x().div_euclid(CHUNK_SIZE as i32),
+            coords.z().div_euclid(CHUNK_SIZE as i32),
+        )
+            .into(),
+    );
+    let chunk = load_chunk(source, chunk_coords, settings)?;
+    let base_y = coords.y().div_euclid(CHUNK_SIZE as i32) * CHUNK_SIZE as i32;
+    let section = chunk
+        .get_section(base_y)
+        .ok_or_else(|| anyhow!("no section at Y {base_y} in chunk {chunk_coords}"))?;
+    let index = BIndex(
+        (
+            coords.x().rem_euclid(CHUNK_SIZE as i32) as u32,
+            coords.z().rem_euclid(CHUNK_SIZE as i32) as u32,
+            coords.y().rem_euclid(CHUNK_SIZE as i32) as u32,
+        )
+            .into(),
+    );
+    let block = section.get_block(index);
+    let (_, rule) = &section.block_palette[section.palette_index(index)];
+
+    let properties = block
+        .state
+        .properties
+        .iter()
+        .map(|(key, value)| (key.to_owned(), string(value)))
+        .collect();
+    let mut dump = HashMap::from([
+        ("x".to_owned(), Value::Int(coords.x())),
+        ("y".to_owned(), Value::Int(coords.y())),
+        ("z".to_owned(), Value::Int(coords.z())),
+        ("state".to_owned(), string(block.state.to_string())),
+        ("name".to_owned(), string(block.state.name.as_str())),
+        ("properties".to_owned(), Value::Compound(properties)),
+        ("biome".to_owned(), string(block.biome.as_str())),
+        (
+            "light".to_owned(),
+            compound([
+                ("sky", Value::Byte(block.lighting.sky() as i8)),
+                ("block", Value::Byte(block.lighting.block() as i8)),
+            ]),
+        ),
+        (
+            "rule".to_owned(),
+            compound([
+                ("name", string(&rule.name)),
+                ("render", string(rule.render.type_name())),
+            ]),
+        ),
+        ("section".to_owned(), dump_section(section)),
+    ]);
+    if let Some(block_entity) = block.block_entity {
+        let mut data = block_entity.data.clone();
+        data.insert("id".to_owned(), string(block_entity.id.as_str()));
+        dump.insert("block_entity".to_owned(), Value::Compound(data));
+    }
+    Ok(Value::Compound(dump))
+}
+
+/// Format `value` as SNBT, the text format used by commands, with compounds and lists spread
+/// over multiple lines. Compound keys are sorted, so the output is stable.
+pub fn to_snbt(value: &Value) -> String {
+    let mut output = String::new();
+    write_snbt(&mut output, value, 0);
+    output
+}
+
+fn write_snbt(output: &mut String, value: &Value, depth: usize) {
+    const INDENT: &str = "    ";
+    match value {
+        Value::Byte(v) => write!(output, "{v}b").unwrap(),
+        Value::Short(v) => write!(output, "{v}s").unwrap(),
+        Value::Int(v) => write!(output, "{v}").unwrap(),
+        Value::Long(v) => write!(output, "{v}L").unwrap(),
+        Value::Float(v) => write!(output, "{v:?}f").unwrap(),
+        Value::Double(v) => write!(output, "{v:?}d").unwrap(),
+        Value::String(s) => write_snbt_string(output, s),
+        Value::ByteArray(values) => write_snbt_array(output, 'B', values.iter(), "b"),
+        Value::IntArray(values) => write_snbt_array(output, 'I', values.iter(), ""),
+        Value::LongArray(values) => write_snbt_array(output, 'L', values.iter(), "L"),
+        Value::List(values) if values.is_empty() => output.push_str("[]"),
+        Value::List(values) => {
+            output.push_str("[\n");
+            for (i, value) in values.iter().enumerate() {
+                output.push_str(&INDENT.repeat(depth + 1));
+                write_snbt(output, value, depth + 1);
+                output.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&INDENT.repeat(depth));
+            output.push(']');
+        }
+        Value::Compound(entries) if entries.is_empty() => output.push_str("{}"),
+        Value::Compound(entries) => {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by_key(|(key, _)| key.as_str());
+            output.push_str("{\n");
+            for (i, (key, value)) in entries.iter().enumerate() {
+                output.push_str(&INDENT.repeat(depth + 1));
+                let is_plain = !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_-.+".contains(c));
+                if is_plain {
+                    output.push_str(key);
+                } else {
+                    write_snbt_string(output, key);
+                }
+                output.push_str(": ");
+                write_snbt(output, value, depth + 1);
+                output.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&INDENT.repeat(depth));
+            output.push('}');
+        }
+    }
+}
+
+fn write_snbt_string(output: &mut String, s: &str) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '\n' => output.push_str("\\n"),
+            _ => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+fn write_snbt_array<T: std::fmt::Display>(
+    output: &mut String,
+    prefix: char,
+    values: impl Iterator<Item = T>,
+    suffix: &str,
+) {
+    write!(output, "[{prefix};").unwrap();
+    for (i, value) in values.enumerate() {
+        let separator = if i == 0 { " " } else { ", " };
+        write!(output, "{separator}{value}{suffix}").unwrap();
+    }
+    output.push(']');
+}
+
+/// Convert `value` to JSON, with arrays of all kinds as JSON arrays, and every number type as a
+/// JSON number.
+pub fn to_json(value: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+    match value {
+        Value::Byte(v) => Json::from(*v),
+        Value::Short(v) => Json::from(*v),
+        Value::Int(v) => Json::from(*v),
+        Value::Long(v) => Json::from(*v),
+        Value::Float(v) => Json::from(*v),
+        Value::Double(v) => Json::from(*v),
+        Value::String(s) => Json::from(s.as_str()),
+        Value::ByteArray(values) => values.iter().copied().collect(),
+        Value::IntArray(values) => values.iter().copied().collect(),
+        Value::LongArray(values) => values.iter().copied().collect(),
+        Value::List(values) => values.iter().map(to_json).collect(),
+        Value::Compound(entries) => Json::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::intern_str;
+    use crate::world::tests::test_settings;
+    use crate::world::{BlockState, ChunkBuilder, HeightRange, MemorySource};
+
+    #[test]
+    fn test_snbt() {
+        let value = compound([
+            ("name", string("say \"hi\"")),
+            (
+                "list",
+                Value::List(vec![Value::Byte(1), Value::Double(0.5)]),
+            ),
+            (
+                "data",
+                Value::LongArray(fastnbt::LongArray::new(vec![1, -2])),
+            ),
+            ("minecraft:key", Value::Compound(HashMap::new())),
+        ]);
+        assert_eq!(
+            to_snbt(&value),
+            "{\n    data: [L; 1L, -2L],\n    list: [\n        1b,\n        0.5d\n    ],\n    \
+             \"minecraft:key\": {},\n    name: \"say \\\"hi\\\"\"\n}"
+        );
+        assert_eq!(to_json(&value)["data"], serde_json::json!([1, -2]),);
+    }
+
+    #[test]
+    fn test_dump_block() {
+        let settings = test_settings();
+        let height_range = HeightRange::OVERWORLD;
+        let mut source = MemorySource::new(height_range);
+        let mut chunk = ChunkBuilder::new(CCoords((-1, 0).into()), height_range);
+        chunk.set_block(
+            15,
+            -3,
+            2,
+            BlockState::new(intern_str("minecraft:hopper")).with_property("facing", "down"),
+        );
+        source.insert_chunk(chunk.coords(), chunk.to_nbt().unwrap(), 0);
+
+        let dump = dump_block(&source, BCoords((-1, 2, -3).into()), &settings).unwrap();
+        let json = to_json(&dump);
+        assert_eq!(json["state"], "minecraft:hopper{facing=down}");
+        assert_eq!(json["properties"]["facing"], "down");
+        assert_eq!(json["rule"]["name"], "_default");
+        assert_eq!(json["rule"]["render"], "solid_uniform");
+        assert_eq!(json["section"]["y"], -16);
+        assert_eq!(json["section"]["block_palette"][1]["count"], 1);
+
+        let dump = dump_chunk(&source, CCoords((-1, 0).into()), &settings).unwrap();
+        let json = to_json(&dump);
+        assert_eq!(json["status"], "minecraft:full");
+        assert_eq!(json["nbt"]["xPos"], -1);
+        assert!(dump_chunk(&source, CCoords((5, 5).into()), &settings).is_err());
+    }
+}
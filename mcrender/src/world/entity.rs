@@ -0,0 +1,295 @@
+/*
+Entity storage notes:
+
+- Since 1.17 (20w45a), entities are stored separately from blocks, in `entities/r.X.Z.mca` region
+  files alongside `region/`. Each chunk is a compound with `DataVersion`, `Position` (the chunk X
+  and Z as an int array) and an `Entities` list. Before that they were in the block chunk's
+  `Level.Entities`, which isn't read here.
+- Entities have an `id`, a `Pos` list of 3 doubles, and type-specific data, e.g. `Item` for item
+  frames and dropped items, or `VillagerData` for villagers.
+- Mobs being ridden hold their riders in a `Passengers` list, which is flattened into the chunk's
+  entity list.
+- `CustomName` was a JSON text component string until 1.21.5, and is now stored as the text
+  component itself: a string, a compound or a list.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use arcstr::ArcStr;
+use fastnbt::Value;
+use serde::Deserialize;
+
+use crate::util::intern_str;
+use crate::world::{BCoords, CCoords, RawChunk};
+
+/// Entity chunks nested deeper than this are assumed to be corrupt.
+const MAX_PASSENGER_DEPTH: usize = 16;
+
+#[derive(Debug, Deserialize)]
+struct EntityChunkNbt {
+    #[serde(rename = "Position")]
+    position: Option<fastnbt::IntArray>,
+    #[serde(rename = "Entities")]
+    #[serde(default)]
+    entities: Vec<HashMap<String, Value>>,
+}
+
+/// The entities in one chunk of an `entities/` region file.
+#[derive(Clone, Debug)]
+pub struct EntityChunk {
+    pub coords: CCoords,
+    pub entities: Vec<Entity>,
+}
+
+impl RawChunk {
+    /// Parse the chunk as entity data, from an `entities/` region file.
+    pub fn parse_entities(&self) -> anyhow::Result<EntityChunk> {
+        let chunk_nbt: EntityChunkNbt = fastnbt::from_bytes(self.data.as_slice())?;
+        let coords = match chunk_nbt.position.as_deref() {
+            Some(&[x, z]) => CCoords((x, z).into()),
+            Some(_) => return Err(anyhow!("invalid entity chunk position")),
+            None => self.coords,
+        };
+        let mut entities = Vec::with_capacity(chunk_nbt.entities.len());
+        for entity_nbt in chunk_nbt.entities {
+            Entity::collect(entity_nbt, &mut entities, 0);
+        }
+        Ok(EntityChunk { coords, entities })
+    }
+}
+
+/// A mob, item, vehicle or other non-block thing in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    /// The entity type, e.g. `minecraft:villager`.
+    pub id: ArcStr,
+    /// Position in blocks, as X, Y and Z.
+    pub pos: [f64; 3],
+    /// The name given to the entity with a name tag or command, as plain text.
+    pub custom_name: Option<String>,
+    /// The rest of the entity's NBT, without its ID, position and passengers.
+    pub data: HashMap<String, Value>,
+}
+
+impl Entity {
+    /// Add the entity from its NBT compound `nbt`, and any entities riding it, to `entities`.
+    /// Entities without an ID or position are skipped.
+    fn collect(mut nbt: HashMap<String, Value>, entities: &mut Vec<Entity>, depth: usize) {
+        let passengers = nbt.remove("Passengers");
+        if let Some(entity) = Self::from_nbt(nbt) {
+            entities.push(entity);
+        }
+        if let Some(Value::List(passengers)) = passengers
+            && depth < MAX_PASSENGER_DEPTH
+        {
+            for passenger in passengers {
+                if let Value::Compound(passenger) = passenger {
+                    Self::collect(passenger, entities, depth + 1);
+                }
+            }
+        }
+    }
+
+    fn from_nbt(mut nbt: HashMap<String, Value>) -> Option<Self> {
+        let id = match nbt.remove("id")? {
+            Value::String(id) => intern_str(id),
+            _ => return None,
+        };
+        let pos = match nbt.remove("Pos")? {
+            Value::List(pos) => match pos.as_slice() {
+                [Value::Double(x), Value::Double(y), Value::Double(z)] => [*x, *y, *z],
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let custom_name = nbt
+            .remove("CustomName")
+            .and_then(|name| text_component(&name));
+        Some(Self {
+            id,
+            pos,
+            custom_name,
+            data: nbt,
+        })
+    }
+
+    /// Get the coordinates of the block the entity is in.
+    pub fn block_coords(&self) -> BCoords {
+        let [x, y, z] = self.pos.map(|v| v.floor() as i32);
+        BCoords((x, z, y).into())
+    }
+
+    /// Get the ID of the item held by an item frame, or of a dropped item.
+    pub fn item_id(&self) -> Option<&str> {
+        let Value::Compound(item) = self.data.get("Item")? else {
+            return None;
+        };
+        match item.get("id")? {
+            Value::String(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Get the profession of a villager or zombie villager, e.g. `minecraft:librarian`.
+    pub fn villager_profession(&self) -> Option<&str> {
+        let Value::Compound(villager_data) = self.data.get("VillagerData")? else {
+            return None;
+        };
+        match villager_data.get("profession")? {
+            Value::String(profession) => Some(profession),
+            _ => None,
+        }
+    }
+}
+
+/// Get the plain text of a text component, from either its JSON form or its NBT form.
+fn text_component(value: &Value) -> Option<String> {
+    match value {
+        // Since 1.21.5 a string that isn't a JSON component is just the text
+        Value::String(s) => serde_json::from_str::<serde_json::Value>(s)
+            .ok()
+            .and_then(|json| json_text_component(&json))
+            .or_else(|| Some(s.clone())),
+        Value::Compound(compound) => {
+            let mut text = match compound.get("text") {
+                Some(Value::String(s)) => s.clone(),
+                _ => String::new(),
+            };
+            if let Some(Value::List(extra)) = compound.get("extra") {
+                text.extend(extra.iter().filter_map(text_component));
+            }
+            Some(text)
+        }
+        Value::List(parts) => Some(parts.iter().filter_map(text_component).collect()),
+        _ => None,
+    }
+}
+
+fn json_text_component(json: &serde_json::Value) -> Option<String> {
+    match json {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Object(object) => {
+            let mut text = object
+                .get("text")
+                .and_then(|text| text.as_str())
+                .unwrap_or_default()
+                .to_owned();
+            if let Some(serde_json::Value::Array(extra)) = object.get("extra") {
+                text.extend(extra.iter().filter_map(json_text_component));
+            }
+            Some(text)
+        }
+        serde_json::Value::Array(parts) => {
+            Some(parts.iter().filter_map(json_text_component).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::CompressionMethod;
+    use crate::world::nbt::compound;
+
+    fn pos(x: f64, y: f64, z: f64) -> Value {
+        Value::List(vec![Value::Double(x), Value::Double(y), Value::Double(z)])
+    }
+
+    #[test]
+    fn test_parse_entities() {
+        let villager = compound([
+            ("id", Value::String("minecraft:villager".to_owned())),
+            ("Pos", pos(-20.5, 64.0, 3.7)),
+            ("CustomName", Value::String(r#"{"text":"Bob"}"#.to_owned())),
+            (
+                "VillagerData",
+                compound([(
+                    "profession",
+                    Value::String("minecraft:librarian".to_owned()),
+                )]),
+            ),
+        ]);
+        let item_frame = compound([
+            ("id", Value::String("minecraft:item_frame".to_owned())),
+            ("Pos", pos(-30.0, 70.5, 1.0)),
+            (
+                "Item",
+                compound([
+                    ("id", Value::String("minecraft:map".to_owned())),
+                    ("count", Value::Int(1)),
+                ]),
+            ),
+        ]);
+        let chicken_jockey = compound([
+            ("id", Value::String("minecraft:chicken".to_owned())),
+            ("Pos", pos(-17.0, 64.0, 0.0)),
+            (
+                "CustomName",
+                compound([("text", Value::String("Jockey".to_owned()))]),
+            ),
+            (
+                "Passengers",
+                Value::List(vec![compound([
+                    ("id", Value::String("minecraft:zombie".to_owned())),
+                    ("Pos", pos(-17.0, 64.5, 0.0)),
+                ])]),
+            ),
+        ]);
+        let chunk_nbt = compound([
+            ("DataVersion", Value::Int(3953)),
+            (
+                "Position",
+                Value::IntArray(fastnbt::IntArray::new(vec![-2, 0])),
+            ),
+            (
+                "Entities",
+                Value::List(vec![
+                    villager,
+                    item_frame,
+                    chicken_jockey,
+                    compound([("id", Value::String("minecraft:pig".to_owned()))]),
+                ]),
+            ),
+        ]);
+        let raw_chunk = RawChunk {
+            index: Default::default(),
+            coords: Default::default(),
+            data: fastnbt::to_bytes(&chunk_nbt).unwrap(),
+            compression: CompressionMethod::Uncompressed,
+        };
+
+        let chunk = raw_chunk.parse_entities().unwrap();
+        assert_eq!(chunk.coords, CCoords((-2, 0).into()));
+        let ids: Vec<_> = chunk.entities.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "minecraft:villager",
+                "minecraft:item_frame",
+                "minecraft:chicken",
+                "minecraft:zombie"
+            ]
+        );
+        let villager = &chunk.entities[0];
+        assert_eq!(villager.custom_name.as_deref(), Some("Bob"));
+        assert_eq!(villager.villager_profession(), Some("minecraft:librarian"));
+        assert_eq!(villager.block_coords(), BCoords((-21, 3, 64).into()));
+        assert_eq!(chunk.entities[1].item_id(), Some("minecraft:map"));
+        assert_eq!(chunk.entities[2].custom_name.as_deref(), Some("Jockey"));
+        assert!(!chunk.entities[2].data.contains_key("Passengers"));
+    }
+
+    #[test]
+    fn test_text_component() {
+        let text = |s: &str| text_component(&Value::String(s.to_owned()));
+        assert_eq!(text(r#""Plain""#).as_deref(), Some("Plain"));
+        assert_eq!(
+            text(r#"{"text":"A","extra":[{"text":"B"},"C"]}"#).as_deref(),
+            Some("ABC")
+        );
+        assert_eq!(text("Not JSON").as_deref(), Some("Not JSON"));
+        assert_eq!(text("42").as_deref(), Some("42"));
+    }
+}
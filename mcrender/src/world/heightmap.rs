@@ -0,0 +1,136 @@
+/*
+Heightmap notes:
+
+- Since 1.13 chunks have a `Heightmaps` compound of long arrays, with one value per block column
+  in Z-major order. Each value is the height of the block above the highest block that counts for
+  that heightmap, relative to the bottom of the world, so 0 means there's no such block.
+- Values use just enough bits for the world's height (9 bits for both 256 and 384 blocks), packed
+  like block states: end-to-end before 20w17a (1.16), and without spanning longs after that.
+- `WORLD_SURFACE` counts every block except air, and `OCEAN_FLOOR` only counts blocks that block
+  motion, i.e. not water, plants or other blocks that can be walked through.
+- Chunks that aren't fully generated may be missing some heightmaps, or have the worldgen-only
+  variants (`WORLD_SURFACE_WG` and `OCEAN_FLOOR_WG`) instead, which aren't used here.
+ */
+
+use anyhow::anyhow;
+
+use crate::world::{CHUNK_SIZE, PackedBits, Section, nbt};
+
+const COLUMN_COUNT: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Blocks that don't count towards the `WORLD_SURFACE` heightmap.
+pub(super) const AIR_BLOCKS: [&str; 3] =
+    ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// The heightmaps stored with a chunk, if it has them.
+#[derive(Clone, Debug, Default)]
+pub struct Heightmaps {
+    /// The top of the highest block in each column that isn't air.
+    pub world_surface: Option<Heightmap>,
+    /// The top of the highest block in each column that can't be walked or swum through.
+    pub ocean_floor: Option<Heightmap>,
+}
+
+impl Heightmaps {
+    /// Decode the heightmaps of a chunk in a world whose lowest block is at `min_y`, and that's
+    /// `height` blocks high. Heightmaps that can't be decoded are left out.
+    pub(super) fn from_nbt(
+        heightmaps_nbt: nbt::Heightmaps,
+        min_y: i32,
+        height: u32,
+        spanning: bool,
+    ) -> Self {
+        let decode = |data: Option<fastnbt::LongArray>| {
+            Heightmap::from_packed(&data?, min_y, height, spanning)
+                .inspect_err(|err| log::debug!("invalid heightmap: {err}"))
+                .ok()
+        };
+        Self {
+            world_surface: decode(heightmaps_nbt.world_surface),
+            ocean_floor: decode(heightmaps_nbt.ocean_floor),
+        }
+    }
+
+    /// Drop the heightmaps if `WORLD_SURFACE` is below the highest of `sections` with blocks
+    /// other than air, which means they weren't decoded correctly and can't be trusted to skip
+    /// sections.
+    pub(super) fn check_sections(&mut self, sections: &[Section]) {
+        let Some(world_surface) = &self.world_surface else {
+            return;
+        };
+        let top_y = sections
+            .iter()
+            .filter(|section| {
+                section
+                    .block_palette
+                    .iter()
+                    .any(|(state, _)| !AIR_BLOCKS.contains(&state.name.as_str()))
+            })
+            .map(|section| section.base.y())
+            .max();
+        if let Some(top_y) = top_y
+            && world_surface.max() <= top_y
+        {
+            log::debug!("heightmap below the highest blocks: {world_surface:?}");
+            *self = Heightmaps::default();
+        }
+    }
+}
+
+/// For each block column of a chunk, the Y coordinate just above the highest block of some kind.
+#[derive(Clone, derive_more::Debug, PartialEq)]
+#[debug("Heightmap({}..={})", self.min(), self.max())]
+pub struct Heightmap([i32; COLUMN_COUNT]);
+
+impl Heightmap {
+    fn from_packed(data: &[i64], min_y: i32, height: u32, spanning: bool) -> anyhow::Result<Self> {
+        let bits = u32::BITS - height.leading_zeros();
+        let mut heights = [min_y; COLUMN_COUNT];
+        let mut count = 0;
+        for (value, height) in
+            PackedBits::new(data.iter().copied(), bits, spanning).zip(heights.iter_mut())
+        {
+            *height += value as i32;
+            count += 1;
+        }
+        if count < COLUMN_COUNT {
+            return Err(anyhow!("heightmap too short: {} values", count));
+        }
+        Ok(Self(heights))
+    }
+
+    /// Get the height of the column at chunk-relative `x` and `z`.
+    pub fn get(&self, x: u32, z: u32) -> i32 {
+        self.0[(z * CHUNK_SIZE + x) as usize]
+    }
+
+    /// Get the height of the lowest column.
+    pub fn min(&self) -> i32 {
+        self.0.iter().copied().min().unwrap()
+    }
+
+    /// Get the height of the highest column.
+    pub fn max(&self) -> i32 {
+        self.0.iter().copied().max().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::writer::pack_bits;
+
+    #[test]
+    fn test_heightmap() {
+        let values = (0..COLUMN_COUNT).map(|i| if i == 17 { 300 } else { 64 + i as u64 % 4 });
+        let data = pack_bits(values, 9);
+        assert_eq!(data.len(), 37);
+        let heightmap = Heightmap::from_packed(&data, -64, 384, false).unwrap();
+        assert_eq!(heightmap.get(0, 0), 0);
+        assert_eq!(heightmap.get(3, 0), 3);
+        assert_eq!(heightmap.get(1, 1), 236);
+        assert_eq!(heightmap.min(), 0);
+        assert_eq!(heightmap.max(), 236);
+        assert!(Heightmap::from_packed(&data[..36], -64, 384, false).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+/*
+Pre-1.18 chunk format notes:
+
+- Everything is inside a `Level` compound, and the section fields are `Palette` and `BlockStates`
+  rather than `block_states.palette` and `block_states.data`.
+- Before 20w17a (1.16), packed block state indexes are stored end-to-end and can span two longs.
+- Biomes are numeric IDs for the whole chunk in `Level.Biomes`: 256 values (one per XZ column)
+  before 19w36a (1.15), and 1024 values (one per 4x4x4 cell, bottom to top) after that.
+- Biome IDs are mapped to their modern names, using the same replacements as the 1.18 upgrade for
+  biomes that no longer exist, so that biome tints keep working.
+ */
+
+use std::collections::HashMap;
+
+use arcstr::ArcStr;
+
+use crate::asset::DEFAULT_BIOME;
+use crate::settings::Settings;
+use crate::util::intern_str;
+use crate::world::{
+    BCoords, BIndex, BlockData, CCoords, CHUNK_SIZE, Chunk, DATA_VERSION_ALIGNED_PACKING,
+    HeightRange, Heightmaps, PackedBits, SECTION_BLOCK_COUNT, Section, SkyLightData,
+    apply_block_light, block_index_bits, convert_block_palette, nbt, status_name,
+};
+
+/// Parse a chunk from before 1.18, from the contents of its `Level` compound.
+pub(super) fn parse_level(
+    mut level: nbt::LegacyLevel,
+    data_version: i32,
+    settings: &Settings,
+) -> anyhow::Result<(Chunk, SkyLightData)> {
+    let coords = CCoords((level.x_pos, level.z_pos).into());
+    let mut chunk = Chunk {
+        coords,
+        sections: Vec::with_capacity(level.sections.len()),
+        fully_generated: is_fully_generated(level.status.as_deref()),
+        status: level.status.as_deref().map(status_name),
+        missing_light: false,
+        heightmaps: Heightmaps::default(),
+    };
+    let biome_ids = biome_ids(level.biomes);
+    let spanning = data_version < DATA_VERSION_ALIGNED_PACKING;
+    if let Some(heightmaps) = level.heightmaps {
+        let height_range = HeightRange::LEGACY;
+        chunk.heightmaps = Heightmaps::from_nbt(
+            heightmaps,
+            height_range.min_y,
+            height_range.height,
+            spanning,
+        );
+    }
+
+    let mut sky_light_data: SkyLightData = Vec::with_capacity(level.sections.len());
+
+    // Sky light processing relies on sections being in order
+    level.sections.sort_by_key(|section_nbt| section_nbt.y);
+    for section_nbt in level.sections.into_iter() {
+        // Skip sections that only exist for lighting
+        let Some(palette) = section_nbt.palette else {
+            continue;
+        };
+
+        let block_palette = convert_block_palette(palette, settings);
+
+        let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+        if let Some(data) = section_nbt.block_states {
+            let bits = block_index_bits(block_palette.len());
+            PackedBits::new(data.iter(), bits, spanning)
+                .take(SECTION_BLOCK_COUNT)
+                .zip(block_data.iter_mut())
+                .for_each(|(v, data)| {
+                    data.set_state_index(v as u16);
+                });
+        }
+
+        let biome_palette = apply_biomes(&mut block_data, &biome_ids, section_nbt.y);
+
+        if let Some(data) = section_nbt.block_light {
+            apply_block_light(&mut block_data, data.iter());
+        }
+
+        sky_light_data.push(section_nbt.sky_light);
+
+        chunk.sections.push(Section {
+            base: BCoords(
+                (
+                    coords.x() * CHUNK_SIZE as i32,
+                    coords.z() * CHUNK_SIZE as i32,
+                    section_nbt.y as i32 * CHUNK_SIZE as i32,
+                )
+                    .into(),
+            ),
+            block_data,
+            block_palette,
+            biome_palette,
+            block_entities: HashMap::new(),
+        });
+    }
+
+    Ok((chunk, sky_light_data))
+}
+
+/// Is a chunk with generation status `status` fully generated? Before 1.14 the final statuses were
+/// `fullchunk` and `postprocessed`.
+fn is_fully_generated(status: Option<&str>) -> bool {
+    matches!(
+        status,
+        Some("full" | "minecraft:full" | "fullchunk" | "postprocessed")
+    )
+}
+
+/// Get the numeric biome IDs from a chunk's `Biomes` array, which changed from bytes to ints in
+/// 1.13.
+pub(super) fn biome_ids(biomes: Option<fastnbt::Value>) -> Vec<i32> {
+    match biomes {
+        Some(fastnbt::Value::IntArray(ids)) => ids.iter().copied().collect(),
+        Some(fastnbt::Value::ByteArray(ids)) => ids.iter().map(|&id| id as u8 as i32).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Set the biome index of each block in the section at `section_y` from the chunk's numeric
+/// `biome_ids`, returning the section's biome palette.
+pub(super) fn apply_biomes(
+    block_data: &mut [BlockData; SECTION_BLOCK_COUNT],
+    biome_ids: &[i32],
+    section_y: i8,
+) -> Vec<ArcStr> {
+    const COLUMN_COUNT: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+    let mut palette_ids: Vec<Option<i32>> = Vec::new();
+    for (i, data) in block_data.iter_mut().enumerate() {
+        let index = BIndex::from_flat_index(i);
+        let id = if biome_ids.len() == COLUMN_COUNT {
+            biome_ids
+                .get((index.z() * CHUNK_SIZE + index.x()) as usize)
+                .copied()
+        } else {
+            // 4x4x4 cells, 16 per layer, from the bottom of the world
+            let cell_y = section_y as i32 * 4 + (index.y() / 4) as i32;
+            usize::try_from(cell_y).ok().and_then(|cell_y| {
+                let cell =
+                    (cell_y << 4) | ((index.z() / 4) << 2) as usize | (index.x() / 4) as usize;
+                biome_ids.get(cell).copied()
+            })
+        };
+        let palette_index = match palette_ids.iter().position(|&p| p == id) {
+            Some(palette_index) => palette_index,
+            None => {
+                palette_ids.push(id);
+                palette_ids.len() - 1
+            }
+        };
+        data.set_biome_index(palette_index as u8);
+    }
+
+    palette_ids
+        .into_iter()
+        .map(|id| intern_str(id.and_then(biome_name).unwrap_or(DEFAULT_BIOME)))
+        .collect()
+}
+
+/// Get the modern name for numeric biome `id`.
+pub(super) fn biome_name(id: i32) -> Option<&'static str> {
+    let name = match id {
+        0 => "minecraft:ocean",
+        1 => "minecraft:plains",
+        2 => "minecraft:desert",
+        3 => "minecraft:windswept_hills",
+        4 => "minecraft:forest",
+        5 => "minecraft:taiga",
+        6 => "minecraft:swamp",
+        7 => "minecraft:river",
+        8 => "minecraft:nether_wastes",
+        9 => "minecraft:the_end",
+        10 => "minecraft:frozen_ocean",
+        11 => "minecraft:frozen_river",
+        12 => "minecraft:snowy_plains",
+        13 => "minecraft:snowy_plains",
+        14 => "minecraft:mushroom_fields",
+        15 => "minecraft:mushroom_fields",
+        16 => "minecraft:beach",
+        17 => "minecraft:desert",
+        18 => "minecraft:forest",
+        19 => "minecraft:taiga",
+        20 => "minecraft:windswept_hills",
+        21 => "minecraft:jungle",
+        22 => "minecraft:jungle",
+        23 => "minecraft:sparse_jungle",
+        24 => "minecraft:deep_ocean",
+        25 => "minecraft:stony_shore",
+        26 => "minecraft:snowy_beach",
+        27 => "minecraft:birch_forest",
+        28 => "minecraft:birch_forest",
+        29 => "minecraft:dark_forest",
+        30 => "minecraft:snowy_taiga",
+        31 => "minecraft:snowy_taiga",
+        32 => "minecraft:old_growth_pine_taiga",
+        33 => "minecraft:old_growth_pine_taiga",
+        34 => "minecraft:windswept_forest",
+        35 => "minecraft:savanna",
+        36 => "minecraft:savanna_plateau",
+        37 => "minecraft:badlands",
+        38 => "minecraft:wooded_badlands",
+        39 => "minecraft:badlands",
+        40 => "minecraft:small_end_islands",
+        41 => "minecraft:end_midlands",
+        42 => "minecraft:end_highlands",
+        43 => "minecraft:end_barrens",
+        44 => "minecraft:warm_ocean",
+        45 => "minecraft:lukewarm_ocean",
+        46 => "minecraft:cold_ocean",
+        47 => "minecraft:warm_ocean",
+        48 => "minecraft:deep_lukewarm_ocean",
+        49 => "minecraft:deep_cold_ocean",
+        50 => "minecraft:deep_frozen_ocean",
+        127 => "minecraft:the_void",
+        129 => "minecraft:sunflower_plains",
+        130 => "minecraft:desert",
+        131 => "minecraft:windswept_gravelly_hills",
+        132 => "minecraft:flower_forest",
+        133 => "minecraft:taiga",
+        134 => "minecraft:swamp",
+        140 => "minecraft:ice_spikes",
+        149 => "minecraft:jungle",
+        151 => "minecraft:sparse_jungle",
+        155 => "minecraft:old_growth_birch_forest",
+        156 => "minecraft:old_growth_birch_forest",
+        157 => "minecraft:dark_forest",
+        158 => "minecraft:snowy_taiga",
+        160 => "minecraft:old_growth_spruce_taiga",
+        161 => "minecraft:old_growth_spruce_taiga",
+        162 => "minecraft:windswept_gravelly_hills",
+        163 => "minecraft:windswept_savanna",
+        164 => "minecraft:windswept_savanna",
+        165 => "minecraft:eroded_badlands",
+        166 => "minecraft:wooded_badlands",
+        167 => "minecraft:badlands",
+        168 => "minecraft:bamboo_jungle",
+        169 => "minecraft:bamboo_jungle",
+        170 => "minecraft:soul_sand_valley",
+        171 => "minecraft:crimson_forest",
+        172 => "minecraft:warped_forest",
+        173 => "minecraft:basalt_deltas",
+        174 => "minecraft:dripstone_caves",
+        175 => "minecraft:lush_caves",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::test_settings;
+    use crate::world::{CompressionMethod, RawChunk};
+
+    #[test]
+    fn test_parse_level() {
+        // 1.15 chunk, with 3D biomes and a light-only section
+        let mut block_states = vec![0i64; 256];
+        // Block at index 0 is stone, everything else is air
+        block_states[0] = 1;
+        let chunk_nbt = fastnbt::nbt!({
+            "DataVersion": 2230_i32,
+            "Level": {
+                "xPos": 3_i32,
+                "zPos": -2_i32,
+                "Status": "full",
+                "Biomes": fastnbt::IntArray::new(vec![4; 1024]),
+                "Sections": [
+                    {
+                        "Y": 1_i8,
+                        "Palette": [{"Name": "minecraft:air"}, {"Name": "minecraft:stone"}],
+                        "BlockStates": fastnbt::LongArray::new(block_states),
+                    },
+                    {
+                        "Y": -1_i8,
+                        "SkyLight": fastnbt::ByteArray::new(vec![-1; 2048]),
+                    },
+                    {
+                        "Y": 0_i8,
+                        "Palette": [{"Name": "minecraft:dirt"}],
+                    },
+                ],
+            },
+        });
+        let raw_chunk = RawChunk {
+            index: Default::default(),
+            coords: Default::default(),
+            data: fastnbt::to_bytes(&chunk_nbt).unwrap(),
+            compression: CompressionMethod::Uncompressed,
+        };
+        let chunk = raw_chunk
+            .parse(HeightRange::LEGACY, &test_settings())
+            .unwrap();
+        assert_eq!(chunk.coords, CCoords((3, -2).into()));
+        assert!(chunk.fully_generated);
+        assert_eq!(chunk.status.as_deref(), Some("minecraft:full"));
+        assert_eq!(chunk.sections.len(), 2);
+        assert_eq!(chunk.sections[0].base, BCoords((48, -32, 0).into()));
+        assert_eq!(chunk.sections[1].base, BCoords((48, -32, 16).into()));
+
+        let dirt = chunk.sections[0].get_block(BIndex((5, 5, 5).into()));
+        assert_eq!(dirt.state.name, "minecraft:dirt");
+        assert_eq!(dirt.biome, "minecraft:forest");
+        let section = &chunk.sections[1];
+        assert_eq!(
+            section.get_block(BIndex((0, 0, 0).into())).state.name,
+            "minecraft:stone"
+        );
+        assert_eq!(
+            section.get_block(BIndex((1, 0, 0).into())).state.name,
+            "minecraft:air"
+        );
+    }
+
+    #[test]
+    fn test_packed_bits() {
+        // 5-bit values 0..=24, packed end-to-end across two longs
+        let mut longs = [0u64; 2];
+        for i in 0..25u64 {
+            let bit = i * 5;
+            longs[(bit / 64) as usize] |= i << (bit % 64);
+            if bit % 64 > 59 {
+                longs[(bit / 64) as usize + 1] |= i >> (64 - bit % 64);
+            }
+        }
+        let values: Vec<u64> = PackedBits::new(longs.iter().map(|&v| v as i64), 5, true)
+            .take(25)
+            .collect();
+        assert_eq!(values, (0..25).collect::<Vec<_>>());
+
+        // The same values without spanning, 12 per long with 4 bits of padding
+        let mut longs = [0u64; 3];
+        for i in 0..25u64 {
+            longs[(i / 12) as usize] |= i << ((i % 12) * 5);
+        }
+        let values: Vec<u64> = PackedBits::new(longs.iter().map(|&v| v as i64), 5, false)
+            .take(25)
+            .collect();
+        assert_eq!(values, (0..25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_apply_biomes() {
+        let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+
+        // 2D biomes: plains everywhere except one column of desert
+        let mut biome_ids = vec![1; 256];
+        biome_ids[0x21] = 2;
+        let palette = apply_biomes(&mut block_data, &biome_ids, 3);
+        assert_eq!(palette, vec!["minecraft:plains", "minecraft:desert"]);
+        let index = BIndex((1, 2, 7).into()).to_flat_index();
+        assert_eq!(block_data[index].biome_index(), 1);
+        assert_eq!(block_data[index + 1].biome_index(), 0);
+
+        // 3D biomes: the second section has a renamed biome in one 4x4x4 cell
+        let mut biome_ids = vec![0; 1024];
+        biome_ids[(5 << 4) | (1 << 2) | 2] = 3;
+        let palette = apply_biomes(&mut block_data, &biome_ids, 1);
+        assert_eq!(
+            palette,
+            vec!["minecraft:ocean", "minecraft:windswept_hills"]
+        );
+        let index = BIndex((8, 4, 4).into()).to_flat_index();
+        assert_eq!(block_data[index].biome_index(), 1);
+        let index = BIndex((8, 4, 3).into()).to_flat_index();
+        assert_eq!(block_data[index].biome_index(), 0);
+
+        // No biome data
+        let palette = apply_biomes(&mut block_data, &[], 0);
+        assert_eq!(palette, vec![DEFAULT_BIOME]);
+    }
+}
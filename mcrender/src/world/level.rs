@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::world::{BCoords, DimensionID, nbt};
+
+/// Default world border diameter, in blocks.
+const DEFAULT_BORDER_SIZE: f64 = 59_999_968.0;
+
+/// World metadata from `level.dat`.
+#[derive(Clone, Debug)]
+pub struct LevelInfo {
+    pub name: String,
+    pub data_version: Option<i32>,
+    pub seed: Option<i64>,
+    pub spawn: BCoords,
+    pub spawn_dimension: DimensionID,
+    /// Total game ticks elapsed in the world.
+    pub game_time: i64,
+    /// Time of day in ticks, which (unlike `game_time`) can be changed by commands.
+    pub day_time: i64,
+    pub world_border: WorldBorder,
+    pub game_rules: BTreeMap<String, String>,
+}
+
+/// The world border, a square centred on `center` (block X and Z).
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldBorder {
+    pub center: (f64, f64),
+    pub size: f64,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self {
+            center: (0.0, 0.0),
+            size: DEFAULT_BORDER_SIZE,
+        }
+    }
+}
+
+impl LevelInfo {
+    /// Read `level.dat` from `path` (gzip-compressed NBT).
+    pub fn try_from_path(path: &Path) -> anyhow::Result<Self> {
+        Self::from_gzip_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read gzip-compressed `level.dat` NBT, i.e. the file as it's stored, from `reader`.
+    pub fn from_gzip_reader<R: Read>(reader: R) -> anyhow::Result<Self> {
+        Self::from_reader(flate2::read::GzDecoder::new(reader))
+    }
+
+    /// Read uncompressed `level.dat` NBT from `reader`.
+    pub fn from_reader<R: Read>(mut reader: R) -> anyhow::Result<Self> {
+        let mut raw = Vec::new();
+        reader.read_to_end(&mut raw)?;
+        let level_dat: nbt::LevelDat = fastnbt::from_bytes(&raw)?;
+        Ok(Self::from_nbt(level_dat.data))
+    }
+
+    pub(super) fn from_nbt(data: nbt::LevelData) -> Self {
+        let (spawn, spawn_dimension) = match data.spawn {
+            Some(spawn) if spawn.pos.len() == 3 => (
+                BCoords((spawn.pos[0], spawn.pos[2], spawn.pos[1]).into()),
+                spawn
+                    .dimension
+                    .as_deref()
+                    .map(DimensionID::from_namespaced_id)
+                    .unwrap_or(DimensionID::Overworld),
+            ),
+            _ => (
+                BCoords(
+                    (
+                        data.spawn_x.unwrap_or(0),
+                        data.spawn_z.unwrap_or(0),
+                        data.spawn_y.unwrap_or(0),
+                    )
+                        .into(),
+                ),
+                DimensionID::Overworld,
+            ),
+        };
+        let seed = data
+            .world_gen_settings
+            .map(|settings| settings.seed)
+            .or(data.random_seed);
+        let default_border = WorldBorder::default();
+        let world_border = WorldBorder {
+            center: (
+                data.border_center_x.unwrap_or(default_border.center.0),
+                data.border_center_z.unwrap_or(default_border.center.1),
+            ),
+            size: data.border_size.unwrap_or(default_border.size),
+        };
+        let game_rules = data
+            .game_rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| {
+                // Game rules have historically been stored as strings, but handle other types too
+                let value = match value {
+                    fastnbt::Value::String(s) => s,
+                    fastnbt::Value::Byte(b) => (b != 0).to_string(),
+                    fastnbt::Value::Short(v) => v.to_string(),
+                    fastnbt::Value::Int(v) => v.to_string(),
+                    fastnbt::Value::Long(v) => v.to_string(),
+                    other => format!("{other:?}"),
+                };
+                (name, value)
+            })
+            .collect();
+
+        Self {
+            name: data.level_name.unwrap_or_default(),
+            data_version: data.data_version,
+            seed,
+            spawn,
+            spawn_dimension,
+            game_time: data.time.unwrap_or(0),
+            day_time: data.day_time.unwrap_or(0),
+            world_border,
+            game_rules,
+        }
+    }
+
+    /// Get a game rule value, e.g. `doDaylightCycle`.
+    pub fn get_game_rule(&self, name: &str) -> Option<&str> {
+        self.game_rules.get(name).map(|value| value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_info() {
+        let level_dat = fastnbt::nbt!({
+            "Data": {
+                "LevelName": "Test World",
+                "DataVersion": 3953_i32,
+                "Time": 123456_i64,
+                "DayTime": 6000_i64,
+                "SpawnX": 10_i32,
+                "SpawnY": 64_i32,
+                "SpawnZ": -20_i32,
+                "WorldGenSettings": {
+                    "seed": -42_i64,
+                },
+                "BorderSize": 1000.0_f64,
+                "GameRules": {
+                    "doDaylightCycle": "false",
+                },
+            },
+        });
+        let raw = fastnbt::to_bytes(&level_dat).unwrap();
+        let info = LevelInfo::from_reader(&raw[..]).unwrap();
+        assert_eq!(info.name, "Test World");
+        assert_eq!(info.data_version, Some(3953));
+        assert_eq!(info.seed, Some(-42));
+        assert_eq!(info.spawn, BCoords((10, -20, 64).into()));
+        assert_eq!(info.spawn_dimension, DimensionID::Overworld);
+        assert_eq!(info.game_time, 123456);
+        assert_eq!(info.day_time, 6000);
+        assert_eq!(
+            info.world_border,
+            WorldBorder {
+                center: (0.0, 0.0),
+                size: 1000.0,
+            }
+        );
+        assert_eq!(info.get_game_rule("doDaylightCycle"), Some("false"));
+        assert_eq!(info.get_game_rule("keepInventory"), None);
+    }
+}
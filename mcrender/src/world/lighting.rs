@@ -0,0 +1,349 @@
+/*
+Lighting notes:
+
+- Light levels go from 0 to 15, and each block has a sky light and a block light level.
+- Sky light starts at 15 above the world, and goes straight down without dimming until it reaches
+  a block that absorbs light. Block light starts at the emission level of blocks like torches
+  (14), glowstone and lava (15).
+- Both then spread in all six directions, dropping by one for each block, or by the opacity of
+  the block when that's more. Opaque blocks (opacity 15) stop light entirely, and water and leaves
+  have an opacity of 1, so sky light going down through them dims too.
+- The game saves both with each section (`SkyLight` and `BlockLight`), and relights chunks that
+  don't have them when they're loaded, which is common for chunks written by editors, world
+  generators and some server forks. Bedrock worlds don't save light at all.
+- Light spreads at most 15 blocks, so lighting a chunk only needs the chunks next to it.
+- Real opacity depends on block shapes that aren't known here, so blocks rendered as solid cubes
+  are treated as opaque, and everything else as transparent. Emission only depends on the block
+  name, so blocks that light up depending on their state (e.g. furnaces) are always assumed
+  unlit.
+ */
+
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+
+use crate::settings::AssetRenderSpec;
+use crate::world::{CCoords, CHUNK_SIZE, Chunk, LightLevel, Section};
+
+const MAX_LIGHT: u8 = 15;
+/// Width of the volume light is computed in: the chunk and one chunk either side of it.
+const VOLUME_SIZE: usize = 3 * CHUNK_SIZE as usize;
+
+/// Get the amount of light that blocks of the kind rendered as `render` absorb.
+fn light_opacity(render: &AssetRenderSpec) -> u8 {
+    match render {
+        AssetRenderSpec::SolidUniform { .. } | AssetRenderSpec::SolidTopSide { .. } => MAX_LIGHT,
+        AssetRenderSpec::Leaves { .. } | AssetRenderSpec::Water { .. } => 1,
+        _ => 0,
+    }
+}
+
+/// Get the block light level given off by the block called `name`.
+fn light_emission(name: &str) -> u8 {
+    match name.strip_prefix("minecraft:").unwrap_or(name) {
+        "beacon"
+        | "campfire"
+        | "conduit"
+        | "end_gateway"
+        | "end_portal"
+        | "fire"
+        | "glowstone"
+        | "jack_o_lantern"
+        | "lantern"
+        | "lava"
+        | "ochre_froglight"
+        | "pearlescent_froglight"
+        | "respawn_anchor"
+        | "sea_lantern"
+        | "shroomlight"
+        | "verdant_froglight" => 15,
+        "copper_torch" | "copper_wall_torch" | "end_rod" | "torch" | "wall_torch" => 14,
+        "nether_portal" => 11,
+        "crying_obsidian" | "soul_campfire" | "soul_fire" | "soul_lantern" | "soul_torch"
+        | "soul_wall_torch" => 10,
+        "enchanting_table"
+        | "ender_chest"
+        | "glow_lichen"
+        | "redstone_torch"
+        | "redstone_wall_torch" => 7,
+        "sculk_catalyst" | "sea_pickle" => 6,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "brewing_stand" | "brown_mushroom" | "dragon_egg" | "end_portal_frame" | "sculk_sensor"
+        | "small_amethyst_bud" => 1,
+        _ => 0,
+    }
+}
+
+/// The light-related properties of the blocks around a chunk, and the light computed for them.
+struct LightVolume {
+    /// Y coordinate of the bottom of the volume.
+    min_y: i32,
+    height: usize,
+    opacity: Vec<u8>,
+    sky: Vec<u8>,
+    block: Vec<u8>,
+    /// Blocks that give off enough light for it to spread.
+    emitters: Vec<usize>,
+}
+
+impl LightVolume {
+    fn new(min_y: i32, height: usize) -> Self {
+        let len = VOLUME_SIZE * VOLUME_SIZE * height;
+        Self {
+            min_y,
+            height,
+            opacity: vec![0; len],
+            sky: vec![0; len],
+            block: vec![0; len],
+            emitters: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn index(&self, x: usize, z: usize, y: usize) -> usize {
+        (y * VOLUME_SIZE + z) * VOLUME_SIZE + x
+    }
+
+    /// Fill in the blocks of `section`, from the chunk at position `(cx, cz)` in the 3x3 grid of
+    /// chunks.
+    fn add_section(&mut self, cx: usize, cz: usize, section: &Section) {
+        let palette: Vec<_> = section
+            .block_palette
+            .iter()
+            .map(|(state, rule)| (light_opacity(&rule.render), light_emission(&state.name)))
+            .collect();
+        let base_y = (section.base.y() - self.min_y) as usize;
+        for (i, data) in section.block_data.iter().enumerate() {
+            let (x, z, y) = (i & 0xF, (i >> 4) & 0xF, i >> 8);
+            let index = self.index(
+                cx * CHUNK_SIZE as usize + x,
+                cz * CHUNK_SIZE as usize + z,
+                base_y + y,
+            );
+            let (opacity, emission) = palette[data.state_index() as usize];
+            self.opacity[index] = opacity;
+            self.block[index] = emission;
+            if emission > 1 {
+                self.emitters.push(index);
+            }
+        }
+    }
+
+    /// Make the column of chunk position `(cx, cz)` opaque, because there's no chunk there.
+    fn add_missing(&mut self, cx: usize, cz: usize) {
+        for y in 0..self.height {
+            for z in 0..CHUNK_SIZE as usize {
+                let start = self.index(cx * CHUNK_SIZE as usize, cz * CHUNK_SIZE as usize + z, y);
+                self.opacity[start..start + CHUNK_SIZE as usize].fill(MAX_LIGHT);
+            }
+        }
+    }
+
+    /// Start sky light at the top of the volume, and take it straight down each column until
+    /// it's absorbed. Returns the blocks that the light might spread sideways from.
+    fn seed_sky_light(&mut self) -> Vec<usize> {
+        // The lowest Y that light reaches in each column, and the lowest Y above which it's at
+        // full level
+        let mut lit_from = vec![self.height; VOLUME_SIZE * VOLUME_SIZE];
+        let mut full_from = vec![self.height; VOLUME_SIZE * VOLUME_SIZE];
+        for z in 0..VOLUME_SIZE {
+            for x in 0..VOLUME_SIZE {
+                let column = z * VOLUME_SIZE + x;
+                let mut level = MAX_LIGHT;
+                for y in (0..self.height).rev() {
+                    let index = self.index(x, z, y);
+                    level = level.saturating_sub(self.opacity[index]);
+                    if level == 0 {
+                        break;
+                    }
+                    self.sky[index] = level;
+                    lit_from[column] = y;
+                    if level == MAX_LIGHT {
+                        full_from[column] = y;
+                    }
+                }
+            }
+        }
+
+        // Light going down a column never spreads further down, and can only spread sideways
+        // where the next column isn't at full level
+        let mut seeds = Vec::new();
+        for z in 0..VOLUME_SIZE {
+            for x in 0..VOLUME_SIZE {
+                let column = z * VOLUME_SIZE + x;
+                let neighbours_full_from = [
+                    (x > 0).then(|| column - 1),
+                    (x + 1 < VOLUME_SIZE).then(|| column + 1),
+                    (z > 0).then(|| column - VOLUME_SIZE),
+                    (z + 1 < VOLUME_SIZE).then(|| column + VOLUME_SIZE),
+                ]
+                .into_iter()
+                .flatten()
+                .map(|n| full_from[n])
+                .max()
+                .unwrap_or(0);
+                for y in lit_from[column]..neighbours_full_from {
+                    let index = self.index(x, z, y);
+                    if self.sky[index] > 1 {
+                        seeds.push(index);
+                    }
+                }
+            }
+        }
+        seeds
+    }
+
+    /// Get the indices of the blocks next to the block at `index`.
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let x = index % VOLUME_SIZE;
+        let z = (index / VOLUME_SIZE) % VOLUME_SIZE;
+        let y = index / (VOLUME_SIZE * VOLUME_SIZE);
+        let layer = VOLUME_SIZE * VOLUME_SIZE;
+        [
+            (x > 0).then(|| index - 1),
+            (x + 1 < VOLUME_SIZE).then(|| index + 1),
+            (z > 0).then(|| index - VOLUME_SIZE),
+            (z + 1 < VOLUME_SIZE).then(|| index + VOLUME_SIZE),
+            (y > 0).then(|| index - layer),
+            (y + 1 < self.height).then(|| index + layer),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Spread `light` out from the blocks at `seeds`, which must include every block that's
+    /// brighter than its neighbours allow for.
+    fn propagate(&self, light: &mut [u8], seeds: Vec<usize>) {
+        let spread = |level: u8, opacity: u8| level.saturating_sub(opacity.max(1));
+        let mut queue = VecDeque::from(seeds);
+        while let Some(index) = queue.pop_front() {
+            let level = light[index];
+            for n in self.neighbours(index) {
+                let next = spread(level, self.opacity[n]);
+                if next > light[n] {
+                    light[n] = next;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+}
+
+impl Chunk {
+    /// Replace the chunk's lighting with sky and block light computed from its blocks, and the
+    /// blocks of the chunks around it, which `get_neighbour` gets by their coordinates. Missing
+    /// neighbours are treated as opaque.
+    pub fn compute_light<C>(&mut self, mut get_neighbour: impl FnMut(CCoords) -> Option<C>)
+    where
+        C: Borrow<Chunk>,
+    {
+        let (Some(first), Some(last)) = (self.sections.first(), self.sections.last()) else {
+            return;
+        };
+        let min_y = first.base.y();
+        let height = (last.base.y() + CHUNK_SIZE as i32 - min_y) as usize;
+        let mut volume = LightVolume::new(min_y, height);
+        for cz in 0..3 {
+            for cx in 0..3 {
+                let coords = CCoords(self.coords.0 + (cx as i32 - 1, cz as i32 - 1).into());
+                let neighbour;
+                let chunk = if coords == self.coords {
+                    &*self
+                } else if let Some(chunk) = get_neighbour(coords) {
+                    neighbour = chunk;
+                    neighbour.borrow()
+                } else {
+                    volume.add_missing(cx, cz);
+                    continue;
+                };
+                for section in chunk.sections.iter() {
+                    let y = section.base.y();
+                    if y >= min_y && y < min_y + height as i32 {
+                        volume.add_section(cx, cz, section);
+                    }
+                }
+            }
+        }
+
+        let sky_seeds = volume.seed_sky_light();
+        let mut sky = std::mem::take(&mut volume.sky);
+        volume.propagate(&mut sky, sky_seeds);
+        let mut block = std::mem::take(&mut volume.block);
+        let emitters = std::mem::take(&mut volume.emitters);
+        volume.propagate(&mut block, emitters);
+
+        for section in self.sections.iter_mut() {
+            let base_y = (section.base.y() - min_y) as usize;
+            for (i, data) in section.block_data.iter_mut().enumerate() {
+                let (x, z, y) = (i & 0xF, (i >> 4) & 0xF, i >> 8);
+                let index =
+                    volume.index(CHUNK_SIZE as usize + x, CHUNK_SIZE as usize + z, base_y + y);
+                data.set_lighting(
+                    LightLevel::new()
+                        .with_sky(sky[index])
+                        .with_block(block[index]),
+                );
+            }
+        }
+        self.missing_light = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::intern_str;
+    use crate::world::tests::test_settings;
+    use crate::world::{
+        BIndex, BlockState, ChunkBuilder, CompressionMethod, HeightRange, RawChunk,
+    };
+
+    fn parse(builder: &ChunkBuilder) -> Chunk {
+        RawChunk {
+            index: Default::default(),
+            coords: builder.coords(),
+            data: builder.to_nbt().unwrap(),
+            compression: CompressionMethod::Uncompressed,
+        }
+        .parse(HeightRange::LEGACY, &test_settings())
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compute_light() {
+        let height_range = HeightRange::LEGACY;
+        let stone = BlockState::new(intern_str("minecraft:stone"));
+        let mut builder = ChunkBuilder::new(CCoords((0, 0).into()), height_range);
+        builder.fill_layers(0..64, stone.clone());
+        // A cave under the surface, lit by a torch, and a hole in the roof of the cave
+        for x in 2..14 {
+            for z in 2..14 {
+                builder.set_block(x, 40, z, BlockState::new(intern_str("minecraft:air")));
+            }
+        }
+        builder.set_block(2, 40, 2, BlockState::new(intern_str("minecraft:torch")));
+        for y in 41..64 {
+            builder.set_block(13, y, 13, BlockState::new(intern_str("minecraft:air")));
+        }
+        let mut chunk = parse(&builder);
+        assert!(chunk.missing_light);
+        let neighbour = parse(&ChunkBuilder::new(CCoords((1, 0).into()), height_range));
+        chunk.compute_light(|coords| (coords == neighbour.coords).then_some(&neighbour));
+        assert!(!chunk.missing_light);
+
+        let light = |x, y: u32, z| {
+            let section = chunk.get_section((y & !0xF) as i32).unwrap();
+            section.get_block(BIndex((x, z, y & 0xF).into())).lighting
+        };
+        assert_eq!(light(5, 64, 5).sky(), 15);
+        assert_eq!(light(5, 63, 5).sky(), 0);
+        assert_eq!(light(2, 40, 2).block(), 14);
+        assert_eq!(light(5, 40, 2).block(), 11);
+        assert_eq!(light(5, 40, 2).sky(), 0);
+        assert_eq!(light(13, 40, 13).sky(), 15);
+        assert_eq!(light(12, 40, 13).sky(), 14);
+        assert_eq!(light(13, 39, 13).sky(), 0);
+    }
+}
@@ -0,0 +1,493 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::proplist::DefaultPropList as PropList;
+use crate::settings::Settings;
+use crate::util::csv_field;
+use crate::world::numeric::split_block_state;
+use crate::world::{
+    BlockState, CCoords, CHUNK_SIZE, Chunk, ChunkBounds, DimensionInfo, Section, WorldSource,
+};
+
+/// A pattern for block, biome or entity names, where `*` matches any run of characters. Names are
+/// matched with their namespace if the pattern has one, and without it otherwise, so `*_ore`
+/// matches `minecraft:diamond_ore`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NamePattern(String);
+
+impl NamePattern {
+    pub fn new(pattern: &str) -> Self {
+        Self(pattern.to_owned())
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let name = if self.0.contains(':') {
+            name
+        } else {
+            name.split_once(':').map_or(name, |(_, short)| short)
+        };
+        glob_match(&self.0, name)
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow!("empty name pattern"));
+        }
+        Ok(Self::new(s))
+    }
+}
+
+/// Check whether `text` matches `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There's always a first part, which has to be at the start
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcards, so it has to be an exact match
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// A pattern for block states: a [`NamePattern`] for the block name, optionally followed by
+/// properties that must match, e.g. `chest[type=single]`. Only the properties kept by the block's
+/// asset rule can be matched.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockPattern {
+    pub name: NamePattern,
+    pub properties: PropList,
+}
+
+impl BlockPattern {
+    pub fn matches(&self, state: &BlockState) -> bool {
+        self.name.matches(&state.name)
+            && self
+                .properties
+                .iter()
+                .all(|(key, value)| state.get_property(key) == Some(value))
+    }
+}
+
+impl FromStr for BlockPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The name is kept as it's written, because it's only given a namespace if it has one
+        let (name, properties) = split_block_state(s)?;
+        Ok(Self {
+            name: name.parse()?,
+            properties,
+        })
+    }
+}
+
+/// A box of blocks, including both corners.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlockBox {
+    /// The lowest X, Y and Z coordinates in the box.
+    pub min: [i32; 3],
+    /// The highest X, Y and Z coordinates in the box.
+    pub max: [i32; 3],
+}
+
+impl BlockBox {
+    /// Make the box with corners at `a` and `b`, given as X, Y and Z.
+    pub fn new(a: [i32; 3], b: [i32; 3]) -> Self {
+        Self {
+            min: [0, 1, 2].map(|i| a[i].min(b[i])),
+            max: [0, 1, 2].map(|i| a[i].max(b[i])),
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        (self.min[0]..=self.max[0]).contains(&x)
+            && (self.min[1]..=self.max[1]).contains(&y)
+            && (self.min[2]..=self.max[2]).contains(&z)
+    }
+
+    /// Get the bounds of the chunks that overlap the box.
+    pub fn chunk_bounds(&self) -> ChunkBounds {
+        let chunk = |x: i32, z: i32| {
+            CCoords(
+                (
+                    x.div_euclid(CHUNK_SIZE as i32),
+                    z.div_euclid(CHUNK_SIZE as i32),
+                )
+                    .into(),
+            )
+        };
+        ChunkBounds::MinMax(
+            chunk(self.min[0], self.min[2]),
+            chunk(self.max[0], self.max[2]) + CCoords((1, 1).into()),
+        )
+    }
+}
+
+impl FromStr for BlockBox {
+    type Err = anyhow::Error;
+
+    /// Parse a box from the coordinates of two corners, as `x1,y1,z1,x2,y2,z2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow!("invalid box {s:?}: {err}"))?;
+        match values[..] {
+            [x1, y1, z1, x2, y2, z2] => Ok(Self::new([x1, y1, z1], [x2, y2, z2])),
+            _ => Err(anyhow!("invalid box {s:?}: expected x1,y1,z1,x2,y2,z2")),
+        }
+    }
+}
+
+/// What to look for with [`Locations`].
+#[derive(Clone, Debug, Default)]
+pub struct LocateQuery {
+    /// Find every block whose state matches one of these.
+    pub blocks: Vec<BlockPattern>,
+    /// Find every 4x4x4 biome cell whose biome matches one of these.
+    pub biomes: Vec<NamePattern>,
+    /// Find every entity whose ID matches one of these.
+    pub entities: Vec<NamePattern>,
+    /// Only look inside this box.
+    pub bounds: Option<BlockBox>,
+}
+
+impl LocateQuery {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.biomes.is_empty() && self.entities.is_empty()
+    }
+
+    fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        self.bounds.is_none_or(|bounds| bounds.contains(x, y, z))
+    }
+
+    fn chunk_bounds(&self) -> ChunkBounds {
+        self.bounds
+            .map_or(ChunkBounds::Unbounded, |bounds| bounds.chunk_bounds())
+    }
+}
+
+/// What kind of thing was found at a [`Location`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, derive_more::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationKind {
+    #[display("block")]
+    Block,
+    /// A 4x4x4 biome cell, located by its lowest corner.
+    #[display("biome")]
+    Biome,
+    /// An entity, located by the block it's in.
+    #[display("entity")]
+    Entity,
+}
+
+/// Something found by a [`LocateQuery`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct Location {
+    pub kind: LocationKind,
+    /// The block state, biome or entity ID.
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// The places in a dimension that match a [`LocateQuery`].
+#[derive(Debug, Default)]
+pub struct Locations {
+    pub locations: Vec<Location>,
+}
+
+impl Locations {
+    /// Find the blocks and biomes matching `query` in every chunk of `source`. Chunks that can't
+    /// be read are recorded in the source's [`ErrorReport`](crate::world::ErrorReport) and
+    /// skipped.
+    pub fn collect(source: &dyn WorldSource, settings: &Settings, query: &LocateQuery) -> Self {
+        let mut locations = source.fold_chunks_in(
+            &query.chunk_bounds(),
+            settings,
+            Locations::default,
+            |locations, chunk| locations.add_chunk(&chunk, query),
+            |mut a, b| {
+                a.merge(b);
+                a
+            },
+        );
+        locations.locations.sort();
+        locations
+    }
+
+    pub fn add_chunk(&mut self, chunk: &Chunk, query: &LocateQuery) {
+        for section in chunk.sections.iter() {
+            self.add_blocks(section, query);
+            self.add_biomes(section, query);
+        }
+    }
+
+    fn add_blocks(&mut self, section: &Section, query: &LocateQuery) {
+        let matches: Vec<bool> = section
+            .block_palette
+            .iter()
+            .map(|(state, _)| query.blocks.iter().any(|pattern| pattern.matches(state)))
+            .collect();
+        if !matches.contains(&true) {
+            return;
+        }
+        for (i, data) in section.block_data.iter().enumerate() {
+            let index = data.state_index() as usize;
+            if !matches[index] {
+                continue;
+            }
+            let (x, z, y) = section_coords(section, i);
+            if query.contains(x, y, z) {
+                self.locations.push(Location {
+                    kind: LocationKind::Block,
+                    name: section.block_palette[index].0.to_string(),
+                    x,
+                    y,
+                    z,
+                });
+            }
+        }
+    }
+
+    fn add_biomes(&mut self, section: &Section, query: &LocateQuery) {
+        let matches: Vec<bool> = section
+            .biome_palette
+            .iter()
+            .map(|biome| query.biomes.iter().any(|pattern| pattern.matches(biome)))
+            .collect();
+        if !matches.contains(&true) {
+            return;
+        }
+        // Every block of a 4x4x4 cell has the same biome, so just check one corner of each
+        for (i, data) in section.block_data.iter().enumerate() {
+            if i & 0b0011_0011_0011 != 0 {
+                continue;
+            }
+            let index = data.biome_index() as usize;
+            let (x, z, y) = section_coords(section, i);
+            if matches[index] && query.contains(x, y, z) {
+                self.locations.push(Location {
+                    kind: LocationKind::Biome,
+                    name: section.biome_palette[index].to_string(),
+                    x,
+                    y,
+                    z,
+                });
+            }
+        }
+    }
+
+    /// Find the entities matching `query` in the entity region files of `dimension`.
+    pub fn add_entities(&mut self, dimension: &DimensionInfo, query: &LocateQuery) {
+        if query.entities.is_empty() {
+            return;
+        }
+        let bounds = query.chunk_bounds();
+        let found: Vec<Location> = dimension
+            .entity_regions
+            .keys()
+            .copied()
+            .filter(|&region_coords| bounds.intersects_region(region_coords))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .flat_map_iter(|region_coords| {
+                // Errors are recorded in the dimension's error report
+                let chunks = dimension
+                    .load_region_entities(region_coords)
+                    .unwrap_or_default();
+                chunks.into_iter().flat_map(|chunk| chunk.entities)
+            })
+            .filter(|entity| {
+                query
+                    .entities
+                    .iter()
+                    .any(|pattern| pattern.matches(&entity.id))
+            })
+            .filter_map(|entity| {
+                let coords = entity.block_coords();
+                let (x, z, y) = (coords.x(), coords.z(), coords.y());
+                query.contains(x, y, z).then(|| Location {
+                    kind: LocationKind::Entity,
+                    name: entity.id.to_string(),
+                    x,
+                    y,
+                    z,
+                })
+            })
+            .collect();
+        self.locations.extend(found);
+        self.locations.sort();
+    }
+
+    pub fn merge(&mut self, other: Locations) {
+        self.locations.extend(other.locations);
+    }
+
+    /// Write the locations as CSV, with one row per location.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        writeln!(writer, "kind,name,x,y,z")?;
+        for location in self.locations.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                location.kind,
+                csv_field(&location.name),
+                location.x,
+                location.y,
+                location.z
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the locations as a JSON array.
+    pub fn write_json<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.locations)?;
+        Ok(())
+    }
+
+    /// Write the locations as a GeoJSON feature collection of points, with X and Z as the point
+    /// coordinates (so north is towards negative Y), and the rest as properties.
+    pub fn write_geojson<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        let features: Vec<_> = self
+            .locations
+            .iter()
+            .map(|location| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": [location.x, location.z],
+                    },
+                    "properties": {
+                        "kind": location.kind,
+                        "name": location.name,
+                        "y": location.y,
+                    },
+                })
+            })
+            .collect();
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+        serde_json::to_writer_pretty(writer, &collection)?;
+        Ok(())
+    }
+}
+
+/// Get the world coordinates of the block at flat index `i` in `section`, as X, Z and Y.
+fn section_coords(section: &Section, i: usize) -> (i32, i32, i32) {
+    let x = section.base.x() + (i & 0xF) as i32;
+    let z = section.base.z() + ((i >> 4) & 0xF) as i32;
+    let y = section.base.y() + (i >> 8) as i32;
+    (x, z, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::intern_str;
+    use crate::world::tests::test_settings;
+    use crate::world::{ChunkBuilder, HeightRange, MemorySource};
+
+    #[test]
+    fn test_block_pattern() {
+        let chest = BlockState::new(intern_str("minecraft:chest")).with_property("type", "single");
+        let pattern: BlockPattern = "chest[type=single]".parse().unwrap();
+        assert!(pattern.matches(&chest));
+        assert!(
+            !"chest[type=left]"
+                .parse::<BlockPattern>()
+                .unwrap()
+                .matches(&chest)
+        );
+        assert!(
+            "minecraft:ch*"
+                .parse::<BlockPattern>()
+                .unwrap()
+                .matches(&chest)
+        );
+        assert!(
+            !"other:chest"
+                .parse::<BlockPattern>()
+                .unwrap()
+                .matches(&chest)
+        );
+        assert!("chest[type".parse::<BlockPattern>().is_err());
+
+        let ore = NamePattern::new("*_ore");
+        assert!(ore.matches("minecraft:diamond_ore"));
+        assert!(!ore.matches("minecraft:diamond_ore_block"));
+        assert!(NamePattern::new("*").matches("minecraft:stone"));
+        assert!(NamePattern::new("deep*_*_ore").matches("minecraft:deepslate_iron_ore"));
+    }
+
+    #[test]
+    fn test_locate() {
+        let height_range = HeightRange::OVERWORLD;
+        let mut source = MemorySource::new(height_range);
+        let mut chunk = ChunkBuilder::new(CCoords((-1, 2).into()), height_range);
+        chunk.set_block(3, 12, 5, BlockState::new(intern_str("minecraft:spawner")));
+        chunk.set_block(
+            4,
+            -60,
+            5,
+            BlockState::new(intern_str("minecraft:diamond_ore")),
+        );
+        chunk.set_biome(8, 64, 8, intern_str("minecraft:desert"));
+        source.insert_chunk(chunk.coords(), chunk.to_nbt().unwrap(), 0);
+
+        let query = LocateQuery {
+            blocks: vec!["spawner".parse().unwrap(), "*_ore".parse().unwrap()],
+            biomes: vec![NamePattern::new("desert")],
+            ..Default::default()
+        };
+        let settings = test_settings();
+        let source: &dyn WorldSource = &source;
+        let locations = Locations::collect(source, &settings, &query);
+        let mut csv = Vec::new();
+        locations.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "kind,name,x,y,z\n\
+             block,minecraft:diamond_ore,-12,-60,37\n\
+             block,minecraft:spawner,-13,12,37\n\
+             biome,minecraft:desert,-8,64,40\n"
+        );
+
+        let query = LocateQuery {
+            bounds: Some("0,0,0,-13,100,100".parse().unwrap()),
+            ..query
+        };
+        let locations = Locations::collect(source, &settings, &query);
+        let names: Vec<_> = locations
+            .locations
+            .iter()
+            .map(|l| l.name.as_str())
+            .collect();
+        assert_eq!(names, ["minecraft:spawner", "minecraft:desert"]);
+    }
+}
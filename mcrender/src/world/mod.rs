@@ -5,20 +5,52 @@ Anvil file format notes:
 - A chunk's blocks are ordered by (Y, Z, X).
  */
 
+mod bedrock;
 mod cache;
+mod compression;
+mod dump;
+mod entity;
+mod heightmap;
+mod legacy;
+mod level;
+mod lighting;
+mod locate;
 mod nbt;
+mod numeric;
+mod report;
+mod scan;
+mod source;
+mod stats;
+mod storage;
+mod writer;
+pub use bedrock::{BedrockDimension, BedrockWorld, LevelDb};
 pub use cache::{ChunkBounds, ChunkCache};
-
-use std::cmp::{max, min};
+pub use compression::CompressionMethod;
+pub use dump::{dump_block, dump_chunk, to_json, to_snbt};
+pub use entity::{Entity, EntityChunk};
+pub use heightmap::{Heightmap, Heightmaps};
+pub use level::{LevelInfo, WorldBorder};
+pub use locate::{
+    BlockBox, BlockPattern, LocateQuery, Location, LocationKind, Locations, NamePattern,
+};
+pub use report::{DimensionError, ErrorReport, ErrorStage, ErrorSummary, WorldError};
+pub use scan::{ChunkStats, DimensionScan, RegionScan, SectorProblem, WorldScan};
+pub use source::{MemorySource, WorldSource};
+pub use stats::{BlockStats, StatsCounts, StatsGroup, StatsGrouping, StatsOptions};
+pub use storage::{FileSystem, ReadSeek, TarStorage, WorldStorage, ZipStorage, open_storage};
+pub use writer::{
+    ChunkBuilder, DEFAULT_DATA_VERSION, RegionWriter, SectionBuilder, write_region_files,
+};
+
+use std::cmp::max;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::num::NonZeroUsize;
 use std::ops::Range;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
-use std::{fs, io};
 
 use anyhow::anyhow;
 use arcstr::ArcStr;
@@ -26,29 +58,139 @@ use bitfields::bitfield;
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::Buf;
 use derivative::Derivative; // TODO: replace with derive_more::Debug
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::Serialize;
 
+use crate::asset::DEFAULT_BIOME;
 use crate::coords::{CoordsXZ, CoordsXZY, IndexXZ, IndexXZY};
 use crate::proplist::DefaultPropList as PropList;
 use crate::settings::{AssetRenderSpec, AssetRule, Settings};
 use crate::util::intern_str;
+use crate::world::compression::COMPRESSION_FLAG_EXTERNAL;
 
 const SECTOR_SIZE: usize = 4096;
 pub const REGION_SIZE: u32 = 32;
 const REGION_HEADER_SIZE: usize = 2 * SECTOR_SIZE;
-const REGION_CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE) as usize;
+pub const REGION_CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE) as usize;
 pub const CHUNK_SIZE: u32 = 16;
 const SECTION_BLOCK_COUNT: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
 const SECTION_BIOME_COUNT: usize = SECTION_BLOCK_COUNT / (4 * 4 * 4) as usize;
-pub const WORLD_HEIGHT: u32 = 384;
-
-const COMPRESSION_METHOD_ZLIB: u8 = 2;
+/// Maximum number of region files each dimension keeps open at once.
+const REGION_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(128).unwrap();
+/// As [`REGION_CACHE_CAPACITY`], for storage that reads whole region files into memory.
+const IN_MEMORY_REGION_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(8).unwrap();
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum DimensionID {
     Overworld,
     Nether,
     TheEnd,
-    // Other(String),
+    /// Any other dimension, e.g. from a datapack or mod, identified by its namespaced ID.
+    Other(String),
+}
+
+impl DimensionID {
+    /// Get the dimension for a namespaced ID, e.g. `minecraft:the_nether`.
+    pub fn from_namespaced_id(id: &str) -> Self {
+        match id {
+            "minecraft:overworld" => DimensionID::Overworld,
+            "minecraft:the_nether" => DimensionID::Nether,
+            "minecraft:the_end" => DimensionID::TheEnd,
+            _ => DimensionID::Other(id.to_owned()),
+        }
+    }
+
+    /// Get the namespaced ID of the dimension, e.g. `minecraft:the_nether`.
+    pub fn namespaced_id(&self) -> &str {
+        match self {
+            DimensionID::Overworld => "minecraft:overworld",
+            DimensionID::Nether => "minecraft:the_nether",
+            DimensionID::TheEnd => "minecraft:the_end",
+            DimensionID::Other(id) => id.as_str(),
+        }
+    }
+}
+
+impl std::fmt::Display for DimensionID {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.namespaced_id())
+    }
+}
+
+impl FromStr for DimensionID {
+    type Err = anyhow::Error;
+
+    /// Parse a dimension ID, accepting namespaced IDs (`minecraft:the_end`), IDs without the
+    /// `minecraft:` namespace (`the_end`), and a few common aliases (`nether`, `end`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = match s {
+            "" => return Err(anyhow!("empty dimension ID")),
+            "nether" => DimensionID::Nether,
+            "end" => DimensionID::TheEnd,
+            s if s.contains(':') => DimensionID::from_namespaced_id(s),
+            s => DimensionID::from_namespaced_id(&format!("minecraft:{s}")),
+        };
+        Ok(id)
+    }
+}
+
+/// The vertical extent of a dimension, in blocks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize)]
+pub struct HeightRange {
+    /// The lowest block Y coordinate.
+    pub min_y: i32,
+    /// The number of blocks from `min_y` to the top of the world, always a multiple of 16.
+    pub height: u32,
+}
+
+impl HeightRange {
+    /// The overworld since 1.18.
+    pub const OVERWORLD: HeightRange = HeightRange::new(-64, 384);
+    /// The Nether and the End, and the overworld before 1.18.
+    pub const LEGACY: HeightRange = HeightRange::new(0, 256);
+    /// Worlds saved in the McRegion format, before 1.2.
+    pub const MCREGION: HeightRange = HeightRange::new(0, 128);
+
+    /// The first data version with the 1.18 overworld height (21w37a).
+    const OVERWORLD_DATA_VERSION: i32 = 2834;
+
+    pub const fn new(min_y: i32, height: u32) -> Self {
+        Self { min_y, height }
+    }
+
+    /// Get the vanilla height range for dimension `id` in a world saved with `data_version`. This
+    /// is only a fallback for when the height range can't be detected from the world itself.
+    pub fn for_dimension(id: &DimensionID, data_version: Option<i32>) -> Self {
+        match id {
+            DimensionID::Nether | DimensionID::TheEnd => Self::LEGACY,
+            _ => match data_version {
+                Some(v) if v < Self::OVERWORLD_DATA_VERSION => Self::LEGACY,
+                _ => Self::OVERWORLD,
+            },
+        }
+    }
+
+    /// The Y coordinate just above the highest block.
+    pub const fn max_y(self) -> i32 {
+        self.min_y + self.height as i32
+    }
+
+    /// The number of chunk sections needed to cover the height range.
+    pub const fn section_count(self) -> usize {
+        (self.height / CHUNK_SIZE) as usize
+    }
+
+    /// Does the height range include the section whose lowest block is at `base_y`?
+    pub fn contains_section(self, base_y: i32) -> bool {
+        (self.min_y..self.max_y()).contains(&base_y)
+    }
+}
+
+impl Default for HeightRange {
+    fn default() -> Self {
+        Self::OVERWORLD
+    }
 }
 
 /// Global region coordinates.
@@ -173,11 +315,11 @@ impl CIndex {
         )
     }
 
-    fn to_flat_index(self) -> usize {
+    pub fn to_flat_index(self) -> usize {
         (self.z() * REGION_SIZE + self.x()) as usize
     }
 
-    fn from_flat_index(index: usize) -> Self {
+    pub fn from_flat_index(index: usize) -> Self {
         assert!(
             index < (REGION_SIZE * REGION_SIZE) as usize,
             "not a valid region chunk index"
@@ -279,97 +421,512 @@ impl BIndex {
 #[derive(Debug)]
 pub struct WorldInfo {
     pub path: PathBuf,
+    /// Where the world's files are stored.
+    pub storage: Arc<dyn WorldStorage>,
+    /// World metadata, if the world has a readable `level.dat`.
+    pub level: Option<LevelInfo>,
     pub dimensions: HashMap<DimensionID, DimensionInfo>,
 }
 
 impl WorldInfo {
+    /// Find the world at `path`, which can be a world directory or an archive containing one. See
+    /// [`open_storage()`].
     pub fn try_from_path(path: PathBuf) -> anyhow::Result<Self> {
+        let (storage, path) = open_storage(&path)?;
+        Self::try_from_storage(storage, path)
+    }
+
+    /// Find the world whose root directory is at `path` in `storage`.
+    pub fn try_from_storage(storage: Arc<dyn WorldStorage>, path: PathBuf) -> anyhow::Result<Self> {
+        let level_path = path.join("level.dat");
+        let level = if storage.is_file(&level_path) {
+            storage
+                .open(&level_path)
+                .and_then(LevelInfo::from_gzip_reader)
+                .inspect_err(|err| log::warn!("failed to read {:?}: {err}", &level_path))
+                .ok()
+        } else {
+            None
+        };
+        let data_version = level.as_ref().and_then(|level| level.data_version);
+
         let mut dimensions = HashMap::new();
-        if let Ok(dimension_info) = DimensionInfo::try_from_path(path.clone()) {
-            dimensions.insert(DimensionID::Overworld, dimension_info);
-        }
-        if let Ok(dimension_info) = DimensionInfo::try_from_path(path.join("DIM-1")) {
-            dimensions.insert(DimensionID::Nether, dimension_info);
+        for (id, dimension_path) in [
+            (DimensionID::Overworld, path.clone()),
+            (DimensionID::Nether, path.join("DIM-1")),
+            (DimensionID::TheEnd, path.join("DIM1")),
+        ] {
+            let default_height = HeightRange::for_dimension(&id, data_version);
+            if let Ok(dimension_info) =
+                DimensionInfo::try_from_storage(storage.clone(), dimension_path, default_height)
+            {
+                dimensions.insert(id, dimension_info);
+            }
         }
-        if let Ok(dimension_info) = DimensionInfo::try_from_path(path.join("DIM1")) {
-            dimensions.insert(DimensionID::TheEnd, dimension_info);
+        // Custom dimensions are stored at `dimensions/<namespace>/<path>`, where the path may
+        // itself contain multiple components
+        let custom_root = path.join("dimensions");
+        if storage.is_dir(&custom_root) {
+            for namespace_path in storage.read_dir(&custom_root)? {
+                let Some(namespace) = namespace_path.file_name().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let namespace = namespace.to_owned();
+                Self::find_custom_dimensions(
+                    &storage,
+                    &namespace,
+                    &namespace_path,
+                    data_version,
+                    &mut dimensions,
+                )?;
+            }
         }
         if dimensions.is_empty() {
-            Err(anyhow!("No dimensions found"))
-        } else {
-            Ok(Self { path, dimensions })
+            return Err(anyhow!("No dimensions found"));
         }
+        Ok(Self {
+            path,
+            storage,
+            level,
+            dimensions,
+        })
     }
 
     pub fn get_dimension(&self, id: &DimensionID) -> Option<&DimensionInfo> {
         self.dimensions.get(id)
     }
+
+    /// Collect the errors recorded for all dimensions so far.
+    pub fn error_summary(&self) -> ErrorSummary {
+        let mut summary = ErrorSummary::default();
+        for id in self.dimension_ids() {
+            for error in self.dimensions[id].errors.errors() {
+                summary.errors.push(DimensionError {
+                    dimension: id.namespaced_id().to_owned(),
+                    error,
+                });
+            }
+        }
+        summary.error_count = summary.errors.len();
+        summary
+    }
+
+    /// Get the IDs of all dimensions found in the world, in a consistent order.
+    pub fn dimension_ids(&self) -> Vec<&DimensionID> {
+        let mut ids: Vec<_> = self.dimensions.keys().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Recursively search `dir` for dimension directories, adding them to `dimensions` with IDs
+    /// based on `prefix` (`<namespace>:<path>`).
+    fn find_custom_dimensions(
+        storage: &Arc<dyn WorldStorage>,
+        prefix: &str,
+        dir: &Path,
+        data_version: Option<i32>,
+        dimensions: &mut HashMap<DimensionID, DimensionInfo>,
+    ) -> anyhow::Result<()> {
+        for sub_path in storage.read_dir(dir)? {
+            if !storage.is_dir(&sub_path) {
+                continue;
+            }
+            let Some(name) = sub_path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if name == "region" {
+                continue;
+            }
+            let id = if prefix.contains(':') {
+                format!("{prefix}/{name}")
+            } else {
+                format!("{prefix}:{name}")
+            };
+            if storage.is_dir(&sub_path.join("region")) {
+                let id = DimensionID::from_namespaced_id(&id);
+                // Prefer the legacy location for vanilla dimensions if both exist
+                if dimensions.contains_key(&id) {
+                    continue;
+                }
+                let default_height = HeightRange::for_dimension(&id, data_version);
+                match DimensionInfo::try_from_storage(
+                    storage.clone(),
+                    sub_path.clone(),
+                    default_height,
+                ) {
+                    Ok(dimension_info) => {
+                        dimensions.insert(id, dimension_info);
+                    }
+                    Err(err) => {
+                        log::debug!("skipping dimension {id} at {:?}: {err}", sub_path);
+                    }
+                }
+            } else {
+                Self::find_custom_dimensions(storage, &id, &sub_path, data_version, dimensions)?;
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+/// A region file that is kept open, shared between all users of a [`DimensionInfo`].
+pub type SharedRegion = Arc<Mutex<Region<Box<dyn ReadSeek>>>>;
+
+#[derive(derive_more::Debug)]
 pub struct DimensionInfo {
     pub path: PathBuf,
     pub regions: BTreeMap<RCoords, RegionInfo>,
+    /// Region files in `entities/`, which hold the dimension's entities since 1.17.
+    pub entity_regions: BTreeMap<RCoords, RegionInfo>,
+    pub height_range: HeightRange,
+    /// Problems found while reading the dimension.
+    pub errors: ErrorReport,
+    /// Where the dimension's files are stored.
+    pub storage: Arc<dyn WorldStorage>,
+    /// Open region files, including entity regions, by path.
+    #[debug(ignore)]
+    open_regions: Mutex<LruCache<PathBuf, SharedRegion>>,
 }
 
 impl DimensionInfo {
     pub fn try_from_path(path: PathBuf) -> anyhow::Result<Self> {
-        log::debug!("DimensionInfo::try_from_path: {:?}", path);
+        Self::try_from_path_with_height(path, HeightRange::default())
+    }
+
+    /// As [`Self::try_from_path()`], but using `default_height` as the dimension's height range
+    /// if it can't be detected from the chunk data.
+    pub fn try_from_path_with_height(
+        path: PathBuf,
+        default_height: HeightRange,
+    ) -> anyhow::Result<Self> {
+        Self::try_from_storage(Arc::new(FileSystem), path, default_height)
+    }
+
+    /// As [`Self::try_from_path_with_height()`], but reading the dimension directory at `path` in
+    /// `storage`.
+    pub fn try_from_storage(
+        storage: Arc<dyn WorldStorage>,
+        path: PathBuf,
+        default_height: HeightRange,
+    ) -> anyhow::Result<Self> {
+        log::debug!("DimensionInfo::try_from_storage: {:?}", path);
         let regions_path = path.join("region");
-        if !regions_path.is_dir() {
+        if !storage.is_dir(&regions_path) {
             return Err(anyhow!("not a dimension directory"));
         }
         let mut regions = BTreeMap::new();
-        for entry in fs::read_dir(regions_path)? {
-            if let Ok(region) = RegionInfo::try_from_path(entry?.path()) {
-                regions.insert(region.coords, region);
+        let errors = ErrorReport::default();
+        for region_path in storage.read_dir(&regions_path)? {
+            if !storage.is_file(&region_path) {
+                continue;
+            }
+            if let Ok(region) = RegionInfo::try_from_path(region_path) {
+                if let Err(err) = region.check_size(&*storage) {
+                    errors.record(WorldError::region(region.coords, region.path.clone(), &err));
+                    continue;
+                }
+                // Upgraded worlds keep their old McRegion files, so prefer Anvil if both exist
+                match regions.get(&region.coords) {
+                    Some(RegionInfo {
+                        format: RegionFormat::Anvil,
+                        ..
+                    }) => {}
+                    _ => {
+                        regions.insert(region.coords, region);
+                    }
+                }
             }
         }
         if regions.len() == 0 {
             return Err(anyhow!("no regions found"));
         }
-        Ok(Self { path, regions })
+        let entity_regions = Self::find_entity_regions(&*storage, &path.join("entities"));
+        let region_cache_capacity = if storage.reads_into_memory() {
+            IN_MEMORY_REGION_CACHE_CAPACITY
+        } else {
+            REGION_CACHE_CAPACITY
+        };
+        let mut dimension_info = Self {
+            path,
+            regions,
+            entity_regions,
+            height_range: default_height,
+            errors,
+            storage,
+            open_regions: Mutex::new(LruCache::new(region_cache_capacity)),
+        };
+        match dimension_info.detect_height_range() {
+            Some(height_range) => dimension_info.height_range = height_range,
+            None => log::debug!("using default height range: {:?}", default_height),
+        }
+        Ok(dimension_info)
+    }
+
+    /// Find the entity region files in `entities_path`, if it exists. Empty files are skipped.
+    fn find_entity_regions(
+        storage: &dyn WorldStorage,
+        entities_path: &Path,
+    ) -> BTreeMap<RCoords, RegionInfo> {
+        let Ok(paths) = storage.read_dir(entities_path) else {
+            return BTreeMap::new();
+        };
+        paths
+            .into_iter()
+            .filter(|path| storage.is_file(path))
+            .filter_map(|path| RegionInfo::try_from_path(path).ok())
+            .filter(|region| {
+                region.format == RegionFormat::Anvil && region.check_size(storage).is_ok()
+            })
+            .map(|region| (region.coords, region))
+            .collect()
+    }
+
+    /// Read the entities in the chunk at `chunk_coords` from the dimension's `entities/` region
+    /// files. Returns `None` if there's no entity data for the chunk.
+    pub fn load_entities(&self, chunk_coords: CCoords) -> anyhow::Result<Option<EntityChunk>> {
+        let (region_coords, chunk_index) = chunk_coords.to_region_coords();
+        let Some(region_info) = self.entity_regions.get(&region_coords) else {
+            return Ok(None);
+        };
+        let region = self.open_entity_region(region_info)?;
+        self.read_entities(region_info, &region, chunk_index)
+    }
+
+    /// Read all the entities in the entity region at `region_coords`, skipping chunks that can't
+    /// be read. Errors are recorded in [`Self::errors`].
+    pub fn load_region_entities(&self, region_coords: RCoords) -> anyhow::Result<Vec<EntityChunk>> {
+        let Some(region_info) = self.entity_regions.get(&region_coords) else {
+            return Ok(Vec::new());
+        };
+        let region = self.open_entity_region(region_info)?;
+        Ok((0..REGION_CHUNK_COUNT)
+            .filter_map(|i| {
+                self.read_entities(region_info, &region, CIndex::from_flat_index(i))
+                    .ok()
+                    .flatten()
+            })
+            .collect())
+    }
+
+    /// Open the entity region described by `region_info`, recording an error if it can't be
+    /// opened.
+    fn open_entity_region(&self, region_info: &RegionInfo) -> anyhow::Result<SharedRegion> {
+        self.open_region(region_info).inspect_err(|err| {
+            self.errors.record(WorldError {
+                stage: ErrorStage::Entities,
+                ..WorldError::region(region_info.coords, region_info.path.clone(), err)
+            })
+        })
+    }
+
+    /// Read the entities in the chunk at `chunk_index` of an open entity region, recording an
+    /// error if they can't be read.
+    fn read_entities(
+        &self,
+        region_info: &RegionInfo,
+        region: &SharedRegion,
+        chunk_index: CIndex,
+    ) -> anyhow::Result<Option<EntityChunk>> {
+        let compressed_chunk = region.lock().get_compressed_chunk(chunk_index);
+        compressed_chunk
+            .and_then(|compressed_chunk| {
+                compressed_chunk
+                    .map(|compressed_chunk| compressed_chunk.decompress()?.parse_entities())
+                    .transpose()
+            })
+            .inspect_err(|err| {
+                self.errors.record(WorldError::chunk(
+                    ErrorStage::Entities,
+                    chunk_index.to_chunk_coords(region_info.coords),
+                    Some(region_info.path.clone()),
+                    region.lock().get_chunk_offset(chunk_index),
+                    err,
+                ))
+            })
+    }
+
+    /// Detect the height range of the dimension from the first readable chunk.
+    pub fn detect_height_range(&self) -> Option<HeightRange> {
+        for region_info in self.regions.values() {
+            let Ok(region) = self.open_region(region_info) else {
+                continue;
+            };
+            let mut region = region.lock();
+            for i in 0..REGION_CHUNK_COUNT {
+                let Ok(Some(raw_chunk)) = region.get_raw_chunk(CIndex::from_flat_index(i)) else {
+                    continue;
+                };
+                let Ok(chunk_nbt) = fastnbt::from_bytes::<nbt::ChunkHeight>(&raw_chunk.data) else {
+                    continue;
+                };
+                let Some(y_pos) = chunk_nbt.y_pos else {
+                    // Chunks without yPos predate variable world height
+                    return Some(match region_info.format {
+                        RegionFormat::Anvil => HeightRange::LEGACY,
+                        RegionFormat::McRegion => HeightRange::MCREGION,
+                    });
+                };
+                // Sections just outside the height range may exist to hold lighting data, but
+                // won't have any block states
+                let Some(max_section) = chunk_nbt
+                    .sections
+                    .iter()
+                    .filter(|section| section.block_states.is_some())
+                    .map(|section| section.y as i32)
+                    .max()
+                else {
+                    continue;
+                };
+                let height = (max_section + 1 - y_pos) * CHUNK_SIZE as i32;
+                if height <= 0 {
+                    continue;
+                }
+                return Some(HeightRange::new(y_pos * CHUNK_SIZE as i32, height as u32));
+            }
+        }
+        None
+    }
+
+    /// Update the dimension after the region file at `path` was created, changed or deleted, so
+    /// that later reads see its current contents. Returns the coordinates of the region, or `None`
+    /// if `path` isn't a region file.
+    ///
+    /// A region file that's too small for its header is probably still being written, so it's
+    /// left as it was until it has been written.
+    pub fn refresh_region(&mut self, path: &Path) -> Option<RCoords> {
+        let region = RegionInfo::try_from_path(path.to_owned()).ok()?;
+        let coords = region.coords;
+        self.open_regions.lock().pop(path);
+        let existing = self.regions.get(&coords);
+        if !self.storage.is_file(path) {
+            if existing.is_some_and(|existing| existing.path == path) {
+                self.regions.remove(&coords);
+            }
+        } else if region.check_size(&*self.storage).is_ok()
+            && !existing.is_some_and(|existing| {
+                existing.format == RegionFormat::Anvil && region.format == RegionFormat::McRegion
+            })
+        {
+            self.regions.insert(coords, region);
+        }
+        Some(coords)
     }
 
     pub fn get_region(&self, region_coords: RCoords) -> Option<&RegionInfo> {
         self.regions.get(&region_coords)
     }
 
-    /// Get region coordinates such that all existing regions have coordinates `X >= min.x()` and `Z >= min.z()`.
-    pub fn min_region_coords(&self) -> RCoords {
-        self.regions
-            .keys()
-            .cloned()
-            .reduce(|acc, k| RCoords((min(acc.x(), k.x()), min(acc.z(), k.z())).into()))
-            .unwrap()
+    /// Get an open handle to the region described by `region_info`, reusing a previously opened
+    /// handle if one is still cached.
+    pub fn open_region(&self, region_info: &RegionInfo) -> anyhow::Result<SharedRegion> {
+        // Try to get an already open region, but don't hold the lock while opening a new one
+        if let Some(region) = self.open_regions.lock().get(&region_info.path) {
+            return Ok(region.clone());
+        }
+        let region = Arc::new(Mutex::new(region_info.open(&self.storage)?));
+
+        let mut open_regions = self.open_regions.lock();
+        if let Some(existing) = open_regions.get(&region_info.path) {
+            // If something else opened the region in the meantime, reuse that handle
+            Ok(existing.clone())
+        } else {
+            open_regions.put(region_info.path.clone(), region.clone());
+            Ok(region)
+        }
     }
+}
 
-    /// Get region coordinates such that all existing regions have coordinates `X < max.x()` and `Z < max.z()`.
-    pub fn max_region_coords(&self) -> RCoords {
-        RCoords((1, 1).into())
-            + self
-                .regions
-                .keys()
-                .cloned()
-                .reduce(|acc, k| RCoords((max(acc.x(), k.x()), max(acc.z(), k.z())).into()))
-                .unwrap()
+impl WorldSource for DimensionInfo {
+    fn region_coords(&self) -> Vec<RCoords> {
+        self.regions.keys().cloned().collect()
     }
 
-    /// Get the raw chunk at `chunk_coords`, if such a chunk has data.
-    pub fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>> {
+    fn height_range(&self) -> HeightRange {
+        self.height_range
+    }
+
+    fn errors(&self) -> &ErrorReport {
+        &self.errors
+    }
+
+    fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>> {
         let (region_coords, chunk_index) = chunk_coords.to_region_coords();
         let Some(region_info) = self.regions.get(&region_coords) else {
             // No such region
             return Ok(None);
         };
-        // TODO: cache open regions
-        let mut region = region_info.open()?;
-        let Some(mut raw_chunk) = region.get_raw_chunk(chunk_index)? else {
-            // No chunk data for this chunk
+        let region = self.open_region(region_info)?;
+        // Only hold the lock while reading, so other chunks in the region can be read while this
+        // one is decompressed
+        let compressed_chunk = region.lock().get_compressed_chunk(chunk_index)?;
+        compressed_chunk
+            .map(CompressedChunk::decompress)
+            .transpose()
+    }
+
+    fn get_region_timestamps(
+        &self,
+        region_coords: RCoords,
+    ) -> anyhow::Result<Option<[u32; REGION_CHUNK_COUNT]>> {
+        let Some(region_info) = self.regions.get(&region_coords) else {
             return Ok(None);
         };
-        raw_chunk.coords = raw_chunk.index.to_chunk_coords(region_coords);
-        Ok(Some(raw_chunk))
+        let region = self.open_region(region_info)?;
+        let timestamps = *region.lock().timestamps();
+        Ok(Some(timestamps))
+    }
+
+    /// Also records where the chunk's data is. If the region file couldn't be opened at all, the
+    /// error is recorded for the region instead.
+    fn report_chunk_error(&self, coords: CCoords, stage: ErrorStage, err: &anyhow::Error) {
+        let (region_coords, chunk_index) = coords.to_region_coords();
+        let Some(region_info) = self.regions.get(&region_coords) else {
+            self.errors
+                .record(WorldError::chunk(stage, coords, None, None, err));
+            return;
+        };
+        let offset = match self.open_region(region_info) {
+            Ok(region) => region.lock().get_chunk_offset(chunk_index),
+            Err(region_err) => {
+                self.errors.record(WorldError::region(
+                    region_coords,
+                    region_info.path.clone(),
+                    &region_err,
+                ));
+                return;
+            }
+        };
+        self.errors.record(WorldError::chunk(
+            stage,
+            coords,
+            Some(region_info.path.clone()),
+            offset,
+            err,
+        ));
+    }
+}
+
+/// The format of a region file, identified by its file extension.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, derive_more::Display, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionFormat {
+    /// `r.X.Z.mca`, since 1.2.
+    #[display("anvil")]
+    Anvil,
+    /// `r.X.Z.mcr`, before 1.2. Replaced by Anvil, but left alongside the new files when a world
+    /// is upgraded.
+    #[display("mcregion")]
+    McRegion,
+}
+
+impl RegionFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RegionFormat::Anvil => "mca",
+            RegionFormat::McRegion => "mcr",
+        }
     }
 }
 
@@ -377,19 +934,20 @@ impl DimensionInfo {
 pub struct RegionInfo {
     pub coords: RCoords,
     pub path: PathBuf,
+    pub format: RegionFormat,
 }
 
 impl RegionInfo {
     pub fn try_from_path(path: PathBuf) -> anyhow::Result<Self> {
-        if !path.is_file() {
-            return Err(anyhow!("not a file"));
-        }
         let filename = path
             .file_name()
             .unwrap()
             .to_str()
             .ok_or(anyhow!("invalid filename"))?;
-        if let Some(next) = filename.strip_suffix(".mca")
+        if let Some((next, extension)) = filename.rsplit_once(".")
+            && let Some(format) = [RegionFormat::Anvil, RegionFormat::McRegion]
+                .into_iter()
+                .find(|format| format.extension() == extension)
             && let Some(next) = next.strip_prefix("r.")
             && let Some((raw_x, raw_z)) = next.split_once(".")
             && let Ok(x) = i32::from_str(raw_x)
@@ -398,22 +956,46 @@ impl RegionInfo {
             Ok(Self {
                 coords: RCoords((x, z).into()),
                 path,
+                format,
             })
         } else {
-            Err(anyhow!("not a region filename (r.X.Z.mca)"))
+            Err(anyhow!("not a region filename (r.X.Z.mca or r.X.Z.mcr)"))
+        }
+    }
+
+    /// Check that the region file is at least big enough for its header. Minecraft sometimes
+    /// leaves empty region files behind, which can't be read.
+    pub fn check_size(&self, storage: &dyn WorldStorage) -> anyhow::Result<()> {
+        let len = storage.file_len(&self.path)?;
+        if len < REGION_HEADER_SIZE as u64 {
+            return Err(anyhow!("region file too small for header: {} bytes", len));
         }
+        Ok(())
     }
 
-    pub fn open(&self) -> anyhow::Result<Region<File>> {
-        let file = File::open(&self.path)?;
-        Region::from_stream(self.clone(), file)
+    pub fn open(
+        &self,
+        storage: &Arc<dyn WorldStorage>,
+    ) -> anyhow::Result<Region<Box<dyn ReadSeek>>> {
+        let stream = storage.open(&self.path)?;
+        Ok(Region::from_stream(self.clone(), stream)?.with_storage(storage.clone()))
+    }
+
+    /// Get the path of the external chunk file (`c.X.Z.mcc`) that would hold data for the chunk at
+    /// `chunk_coords` if it was too large to fit in the region file.
+    pub fn external_chunk_path(&self, chunk_coords: CCoords) -> PathBuf {
+        self.path
+            .with_file_name(format!("c.{}.{}.mcc", chunk_coords.x(), chunk_coords.z()))
     }
 }
 
 pub struct Region<S: Read + Seek> {
     info: RegionInfo,
     chunks: [u32; REGION_CHUNK_COUNT],
+    timestamps: [u32; REGION_CHUNK_COUNT],
     stream: S,
+    /// Where to find external chunk files.
+    storage: Arc<dyn WorldStorage>,
 }
 
 impl<S: Read + Seek> Region<S> {
@@ -421,18 +1003,31 @@ impl<S: Read + Seek> Region<S> {
         stream.seek(SeekFrom::Start(0))?;
         let mut header = [0u8; REGION_HEADER_SIZE];
         let mut chunks = [0u32; REGION_CHUNK_COUNT];
+        let mut timestamps = [0u32; REGION_CHUNK_COUNT];
         stream.read_exact(&mut header)?;
-        let mut locations = &header[..(REGION_CHUNK_COUNT * 4)];
+        let mut locations = &header[..SECTOR_SIZE];
         for i in 0..REGION_CHUNK_COUNT {
             chunks[i] = locations.get_u32();
         }
+        let mut modified = &header[SECTOR_SIZE..];
+        for timestamp in timestamps.iter_mut() {
+            *timestamp = modified.get_u32();
+        }
         Ok(Self {
             info,
             chunks,
+            timestamps,
             stream,
+            storage: Arc::new(FileSystem),
         })
     }
 
+    /// Use `storage` to read external chunk files, instead of the file system.
+    pub fn with_storage(mut self, storage: Arc<dyn WorldStorage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
     pub fn into_inner(self) -> S {
         self.stream
     }
@@ -448,16 +1043,44 @@ impl<S: Read + Seek> Region<S> {
         &self.info
     }
 
+    /// Get the last modification time of the chunk at `chunk_index`, in seconds since the Unix
+    /// epoch, or 0 if the chunk has never been saved.
+    pub fn get_timestamp(&self, chunk_index: CIndex) -> u32 {
+        self.timestamps[chunk_index.to_flat_index()]
+    }
+
+    /// Get the last modification times of all chunks in the region, in the same order as the
+    /// region's chunk offset table.
+    pub fn timestamps(&self) -> &[u32; REGION_CHUNK_COUNT] {
+        &self.timestamps
+    }
+
+    /// Get the byte offset of the chunk's data within the region file, if the chunk exists.
+    pub fn get_chunk_offset(&self, chunk_index: CIndex) -> Option<u64> {
+        let offset_count = self.chunks[chunk_index.to_flat_index()];
+        (offset_count != 0).then(|| (offset_count >> 8) as u64 * SECTOR_SIZE as u64)
+    }
+
+    /// Get the first sector and sector count of the chunk's data within the region file, if the
+    /// chunk exists. These come straight from the region header, so aren't necessarily valid.
+    pub fn get_chunk_sectors(&self, chunk_index: CIndex) -> Option<(u32, u32)> {
+        let offset_count = self.chunks[chunk_index.to_flat_index()];
+        (offset_count != 0).then_some((offset_count >> 8, offset_count & 0xFF))
+    }
+
     pub fn get_raw_chunk(&mut self, chunk_index: CIndex) -> anyhow::Result<Option<RawChunk>> {
-        let Some(mut raw_chunk) = self.get_raw_chunk_by_index(chunk_index.to_flat_index())? else {
-            return Ok(None);
-        };
-        raw_chunk.index = chunk_index;
-        Ok(Some(raw_chunk))
+        self.get_compressed_chunk(chunk_index)?
+            .map(CompressedChunk::decompress)
+            .transpose()
     }
 
-    fn get_raw_chunk_by_index(&mut self, index: usize) -> anyhow::Result<Option<RawChunk>> {
-        assert!(index < self.chunks.len());
+    /// Read the chunk at `chunk_index` without decompressing it, so that the slower decompression
+    /// can happen without access to the region, e.g. after releasing a lock on it.
+    pub fn get_compressed_chunk(
+        &mut self,
+        chunk_index: CIndex,
+    ) -> anyhow::Result<Option<CompressedChunk>> {
+        let index = chunk_index.to_flat_index();
         let offset_count = self.chunks[index];
         // Offset of 0 means there is no chunk data for this chunk
         if offset_count == 0 {
@@ -472,28 +1095,72 @@ impl<S: Read + Seek> Region<S> {
         // Read the chunk header
         let compressed_size = self.stream.read_u32::<BigEndian>()?;
         let mut chunk_reader = (&mut self.stream).take(compressed_size as u64);
-        let compression_method = chunk_reader.read_u8()?;
-
-        // Decompress the chunk data
-        if compression_method != COMPRESSION_METHOD_ZLIB {
-            // Zlib
-            return Err(anyhow!(
-                "compression method not supported: {:?}",
-                compression_method
-            ));
-        }
-        let mut chunk_decoder = flate2::write::ZlibDecoder::new(vec![]);
-        io::copy(&mut chunk_reader, &mut chunk_decoder)?;
-        let chunk_data = chunk_decoder.finish()?;
-
-        Ok(Some(RawChunk {
-            data: chunk_data,
-            index: Default::default(),
-            coords: Default::default(),
+        let compression_byte = chunk_reader.read_u8()?;
+        let compression =
+            CompressionMethod::try_from(compression_byte & !COMPRESSION_FLAG_EXTERNAL)?;
+
+        // The chunk data is either in the region file or in an external chunk file
+        let data = if compression_byte & COMPRESSION_FLAG_EXTERNAL != 0 {
+            let chunk_coords = chunk_index.to_chunk_coords(self.info.coords);
+            CompressedData::External {
+                storage: self.storage.clone(),
+                path: self.info.external_chunk_path(chunk_coords),
+            }
+        } else {
+            let mut data = Vec::with_capacity(compressed_size.saturating_sub(1) as usize);
+            chunk_reader.read_to_end(&mut data)?;
+            CompressedData::Inline(data)
+        };
+
+        Ok(Some(CompressedChunk {
+            index: chunk_index,
+            coords: chunk_index.to_chunk_coords(self.info.coords),
+            compression,
+            data,
         }))
     }
 }
 
+/// A chunk's data as it's stored, before decompression. See [`Region::get_compressed_chunk()`].
+#[derive(Debug)]
+pub struct CompressedChunk {
+    pub index: CIndex,
+    pub coords: CCoords,
+    pub compression: CompressionMethod,
+    data: CompressedData,
+}
+
+#[derive(derive_more::Debug)]
+enum CompressedData {
+    Inline(#[debug("[.. {} bytes ..]", _0.len())] Vec<u8>),
+    /// Stored in an external `.mcc` file, because it didn't fit in the region file.
+    External {
+        #[debug(ignore)]
+        storage: Arc<dyn WorldStorage>,
+        path: PathBuf,
+    },
+}
+
+impl CompressedChunk {
+    pub fn decompress(self) -> anyhow::Result<RawChunk> {
+        let data = match self.data {
+            CompressedData::Inline(data) => self.compression.decompress(&data[..])?,
+            CompressedData::External { storage, path } => {
+                let external_file = storage
+                    .open(&path)
+                    .map_err(|err| anyhow!("failed to open external chunk {:?}: {}", path, err))?;
+                self.compression.decompress(BufReader::new(external_file))?
+            }
+        };
+        Ok(RawChunk {
+            index: self.index,
+            coords: self.coords,
+            data,
+            compression: self.compression,
+        })
+    }
+}
+
 pub struct RegionChunkIter<S: Read + Seek> {
     region: Region<S>,
     index_iter: Range<usize>,
@@ -510,12 +1177,8 @@ impl<S: Read + Seek> Iterator for RegionChunkIter<S> {
 
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(i) = self.index_iter.next() {
-            match self.region.get_raw_chunk_by_index(i) {
-                Ok(Some(mut raw_chunk)) => {
-                    raw_chunk.index = CIndex::from_flat_index(i);
-                    raw_chunk.coords = raw_chunk.index.to_chunk_coords(self.region.info.coords);
-                    return Some(Ok(raw_chunk));
-                }
+            match self.region.get_raw_chunk(CIndex::from_flat_index(i)) {
+                Ok(Some(raw_chunk)) => return Some(Ok(raw_chunk)),
                 Ok(None) => continue,
                 Err(e) => return Some(Err(e)),
             }
@@ -535,68 +1198,109 @@ pub struct RawChunk {
     pub coords: CCoords,
     #[derivative(Debug(format_with = "fmt_byte_count"))]
     pub data: Vec<u8>,
+    /// How the chunk data was compressed in the region file.
+    pub compression: CompressionMethod,
 }
+
+/// The first data version with the Flattening's palette-based chunk format (17w47a).
+const DATA_VERSION_FLATTENING: i32 = 1451;
+/// The first data version where packed values don't span across longs (20w17a).
+const DATA_VERSION_ALIGNED_PACKING: i32 = 2529;
+/// The first data version without the `Level` compound (21w43a).
+const DATA_VERSION_NO_LEVEL: i32 = 2844;
+
+/// Sky light data for each parsed section, in the same order as [`Chunk::sections`].
+type SkyLightData = Vec<Option<fastnbt::ByteArray>>;
+
 impl RawChunk {
-    pub fn parse(&self, settings: &Settings) -> anyhow::Result<Chunk> {
-        let chunk_nbt: nbt::Chunk = fastnbt::from_bytes(self.data.as_slice())?;
+    /// Parse the chunk, for a dimension with `height_range`.
+    pub fn parse(&self, height_range: HeightRange, settings: &Settings) -> anyhow::Result<Chunk> {
+        let mut chunk_nbt: nbt::Chunk = fastnbt::from_bytes(self.data.as_slice())?;
+
+        let data_version = chunk_nbt.data_version.unwrap_or(0);
+        let (mut chunk, sky_light_data, block_entities) = if data_version >= DATA_VERSION_NO_LEVEL {
+            let block_entities = std::mem::take(&mut chunk_nbt.block_entities);
+            let (chunk, sky_light_data) = Self::parse_sections(chunk_nbt, height_range, settings)?;
+            (chunk, sky_light_data, block_entities)
+        } else {
+            let mut level = chunk_nbt
+                .level
+                .ok_or_else(|| anyhow!("missing Level compound"))?;
+            let block_entities = std::mem::take(&mut level.tile_entities);
+            let (chunk, sky_light_data) = if data_version >= DATA_VERSION_FLATTENING {
+                legacy::parse_level(level, data_version, settings)?
+            } else {
+                numeric::parse_level(level, settings)?
+            };
+            (chunk, sky_light_data, block_entities)
+        };
+
+        chunk.heightmaps.check_sections(&chunk.sections);
+        apply_sky_light(&mut chunk, sky_light_data);
+        chunk.add_block_entities(block_entities);
+        Ok(chunk)
+    }
 
+    /// Parse the 1.18+ chunk format.
+    fn parse_sections(
+        chunk_nbt: nbt::Chunk,
+        height_range: HeightRange,
+        settings: &Settings,
+    ) -> anyhow::Result<(Chunk, SkyLightData)> {
+        let (Some(x_pos), Some(z_pos), Some(y_pos)) =
+            (chunk_nbt.x_pos, chunk_nbt.z_pos, chunk_nbt.y_pos)
+        else {
+            return Err(anyhow!("missing chunk position"));
+        };
         let mut chunk = Chunk {
-            coords: CCoords((chunk_nbt.x_pos, chunk_nbt.z_pos).into()),
+            coords: CCoords((x_pos, z_pos).into()),
             sections: Vec::with_capacity(chunk_nbt.sections.len()),
-            fully_generated: chunk_nbt.status == "minecraft:full",
+            fully_generated: chunk_nbt.status.as_deref() == Some("minecraft:full"),
+            status: chunk_nbt.status.as_deref().map(status_name),
+            missing_light: false,
+            heightmaps: Heightmaps::default(),
         };
+        // Heightmaps are relative to the bottom of the world, and sized for its height
+        if let Some(heightmaps) = chunk_nbt.heightmaps {
+            chunk.heightmaps =
+                Heightmaps::from_nbt(heightmaps, height_range.min_y, height_range.height, false);
+        }
         let chunk_base_coords = BCoords(
             (
                 chunk.coords.x() * CHUNK_SIZE as i32,
                 chunk.coords.z() * CHUNK_SIZE as i32,
-                chunk_nbt.y_pos * CHUNK_SIZE as i32,
+                y_pos * CHUNK_SIZE as i32,
             )
                 .into(),
         );
 
-        let mut sky_light_data: Vec<Option<fastnbt::ByteArray>> =
-            Vec::with_capacity(chunk_nbt.sections.len());
+        let mut sky_light_data: SkyLightData = Vec::with_capacity(chunk_nbt.sections.len());
 
         for section_nbt in chunk_nbt.sections.into_iter() {
+            // Skip sections outside the world's height range, which only exist for lighting
+            let (Some(block_states), Some(biomes)) = (section_nbt.block_states, section_nbt.biomes)
+            else {
+                continue;
+            };
+
             // Collect the block palette (the collection of unique block states that exist in this section)
-            let mut block_palette = Vec::with_capacity(section_nbt.block_states.palette.len());
-            for bs in section_nbt.block_states.palette.into_iter() {
-                let name = intern_str(bs.name);
-                let rule = settings.asset_rules.get_rule(&name);
-                let mut properties = bs.properties.unwrap_or_else(|| PropList::new());
-                // Filter properties to only those relevant to rendering
-                rule.filter_properties(&mut properties);
-                block_palette.push((BlockState { name, properties }, rule));
-            }
+            let block_palette = convert_block_palette(block_states.palette, settings);
 
             // Record the block state index for each block; if there is no data, then the indexes are all
             // 0 by default, i.e. the first block palette entry (correct according to chunk format)
             let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
-            if let Some(data) = section_nbt.block_states.data {
-                let palette_count = block_palette.len() as u64;
-                let bits = max(4, u64::BITS - (palette_count - 1).leading_zeros()) as usize;
-                let packing = u64::BITS as usize / bits;
-                let mask = (1u64 << bits) - 1;
-                data.iter()
-                    .flat_map(|v| {
-                        let mut v = v as u64;
-                        std::iter::repeat_with(move || {
-                            let next = v & mask;
-                            v = v >> bits;
-                            next as u16
-                        })
-                        .take(packing)
-                    })
+            if let Some(data) = block_states.data {
+                let bits = block_index_bits(block_palette.len());
+                PackedBits::new(data.iter(), bits, false)
                     .take(SECTION_BLOCK_COUNT)
                     .zip(block_data.iter_mut())
                     .for_each(|(v, data)| {
-                        data.set_state_index(v);
+                        data.set_state_index(v as u16);
                     });
             }
 
             // Collect the biome palette (the collection of biome names used in this section)
-            let biome_palette: Vec<_> = section_nbt
-                .biomes
+            let biome_palette: Vec<_> = biomes
                 .palette
                 .into_iter()
                 .map(|biome| intern_str(biome))
@@ -604,26 +1308,15 @@ impl RawChunk {
 
             // Record the biome index for each block; biomes indexes apply to 4x4x4 regions, not
             // individual blocks
-            if let Some(data) = section_nbt.biomes.data {
+            if let Some(data) = biomes.data {
                 let palette_count = biome_palette.len() as u64;
-                let bits = (u64::BITS - (palette_count - 1).leading_zeros()) as usize;
-                let packing = u64::BITS as usize / bits;
-                let mask = (1u64 << bits) - 1;
+                let bits = u64::BITS - (palette_count - 1).leading_zeros();
                 let mut indices = [0u8; SECTION_BIOME_COUNT];
-                data.iter()
-                    .flat_map(|v| {
-                        let mut v = v as u64;
-                        std::iter::repeat_with(move || {
-                            let next = v & mask;
-                            v = v >> bits;
-                            next as u8
-                        })
-                        .take(packing)
-                    })
+                PackedBits::new(data.iter(), bits, false)
                     .take(SECTION_BIOME_COUNT)
                     .zip(indices.iter_mut())
                     .for_each(|(v, index)| {
-                        *index = v;
+                        *index = v as u8;
                     });
                 block_data.iter_mut().enumerate().for_each(|(i, data)| {
                     let block_index = BIndex::from_flat_index(i);
@@ -634,15 +1327,7 @@ impl RawChunk {
 
             // If there's no block light data, then the block light is 0, which is the default value in the struct
             if let Some(data) = section_nbt.block_light {
-                data.iter()
-                    .flat_map(|v| {
-                        let v = v as u8;
-                        [v & 0xF, v >> 4]
-                    })
-                    .zip(block_data.iter_mut())
-                    .for_each(|(v, block_data)| {
-                        block_data.set_lighting(block_data.lighting().with_block(v));
-                    });
+                apply_block_light(&mut block_data, data.iter());
             }
 
             // Save sky light data to process top-to-bottom after all sections have been converted
@@ -660,42 +1345,163 @@ impl RawChunk {
                 block_data,
                 block_palette,
                 biome_palette,
+                block_entities: HashMap::new(),
             };
             chunk.sections.push(section);
         }
 
-        // Process the save sky light data top-to-bottom, because absent data needs to be propagated
-        // (default for top of the chunk is full sky light, i.e. 0xFF for each byte)
-        let mut sky_light = [-1i8; 2048];
-        for (data, section) in sky_light_data
-            .into_iter()
-            .rev()
-            .zip(chunk.sections.iter_mut().rev())
-        {
-            const LAYER_LEN: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize / 2;
-            if let Some(data) = data {
-                // Have data for this section, so use it
-                sky_light.copy_from_slice(&*data);
-            } else {
-                // No data for this section,  so duplicate the bottom layer of the section above
-                for i in (LAYER_LEN..sky_light.len()).step_by(LAYER_LEN) {
-                    sky_light.copy_within(0..LAYER_LEN, i);
-                }
-            }
-            sky_light
-                .iter()
-                .copied()
-                .flat_map(|v| {
-                    let v = v as u8;
-                    [v & 0xF, v >> 4]
-                })
-                .zip(section.block_data.iter_mut())
-                .for_each(|(v, block_data)| {
-                    block_data.set_lighting(block_data.lighting().with_sky(v));
-                });
+        Ok((chunk, sky_light_data))
+    }
+}
+
+/// Convert a section's block palette, looking up the asset rule for each block state.
+fn convert_block_palette(
+    palette: Vec<nbt::BlockState>,
+    settings: &Settings,
+) -> Vec<(BlockState, Arc<AssetRule>)> {
+    palette
+        .into_iter()
+        .map(|bs| {
+            let state = BlockState {
+                name: intern_str(bs.name),
+                properties: bs.properties.unwrap_or_else(PropList::new),
+            };
+            convert_block_state(state, settings)
+        })
+        .collect()
+}
+
+/// Look up the asset rule for `state`, and filter its properties to only those relevant to
+/// rendering, unless [`Settings::keep_block_properties`] is set.
+fn convert_block_state(mut state: BlockState, settings: &Settings) -> (BlockState, Arc<AssetRule>) {
+    let rule = settings.asset_rules.get_rule(&state.name);
+    if !settings.keep_block_properties {
+        rule.filter_properties(&mut state.properties);
+    }
+    (state, rule)
+}
+
+/// Get the full name of a chunk generation status, which older versions saved without the
+/// `minecraft:` namespace.
+fn status_name(status: &str) -> ArcStr {
+    if status.contains(':') {
+        intern_str(status)
+    } else {
+        intern_str(format!("minecraft:{status}"))
+    }
+}
+
+/// Get the number of bits used for each block state index with a palette of `palette_len` entries.
+fn block_index_bits(palette_len: usize) -> u32 {
+    max(4, u64::BITS - (palette_len as u64 - 1).leading_zeros())
+}
+
+/// Iterator over the values packed into a long array, lowest bits first.
+///
+/// Since 1.16 values never span across longs, and any bits left over at the top of each long are
+/// padding. Before that, values were packed end-to-end and could be split across two longs.
+struct PackedBits<I> {
+    data: I,
+    bits: u32,
+    spanning: bool,
+    current: u64,
+    available: u32,
+}
+
+impl<I: Iterator<Item = i64>> PackedBits<I> {
+    fn new(data: I, bits: u32, spanning: bool) -> Self {
+        Self {
+            data,
+            bits,
+            spanning,
+            current: 0,
+            available: 0,
         }
+    }
+}
 
-        Ok(chunk)
+impl<I: Iterator<Item = i64>> Iterator for PackedBits<I> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = (1u64 << self.bits) - 1;
+        if self.available >= self.bits {
+            let value = self.current & mask;
+            self.current = self.current.checked_shr(self.bits).unwrap_or(0);
+            self.available -= self.bits;
+            return Some(value);
+        }
+
+        let next = self.data.next()? as u64;
+        if self.spanning && self.available > 0 {
+            // Combine the leftover bits of the previous long with the start of this one
+            let value = (self.current | (next << self.available)) & mask;
+            let used = self.bits - self.available;
+            self.current = next >> used;
+            self.available = u64::BITS - used;
+            Some(value)
+        } else {
+            let value = next & mask;
+            self.current = next.checked_shr(self.bits).unwrap_or(0);
+            self.available = u64::BITS - self.bits;
+            Some(value)
+        }
+    }
+}
+
+/// Unpack 4-bit block light values into `block_data`.
+fn apply_block_light(
+    block_data: &mut [BlockData; SECTION_BLOCK_COUNT],
+    data: impl Iterator<Item = i8>,
+) {
+    data.flat_map(|v| {
+        let v = v as u8;
+        [v & 0xF, v >> 4]
+    })
+    .zip(block_data.iter_mut())
+    .for_each(|(v, block_data)| {
+        block_data.set_lighting(block_data.lighting().with_block(v));
+    });
+}
+
+/// Apply the saved sky light data to the chunk's sections.
+fn apply_sky_light(chunk: &mut Chunk, sky_light_data: SkyLightData) {
+    chunk.missing_light = sky_light_data.iter().all(Option::is_none)
+        && chunk.sections.iter().all(|section| {
+            section
+                .block_data
+                .iter()
+                .all(|data| data.lighting().block() == 0)
+        });
+    // Process the save sky light data top-to-bottom, because absent data needs to be propagated
+    // (default for top of the chunk is full sky light, i.e. 0xFF for each byte)
+    let mut sky_light = [-1i8; 2048];
+    for (data, section) in sky_light_data
+        .into_iter()
+        .rev()
+        .zip(chunk.sections.iter_mut().rev())
+    {
+        const LAYER_LEN: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize / 2;
+        if let Some(data) = data {
+            // Have data for this section, so use it
+            sky_light.copy_from_slice(&data);
+        } else {
+            // No data for this section,  so duplicate the bottom layer of the section above
+            for i in (LAYER_LEN..sky_light.len()).step_by(LAYER_LEN) {
+                sky_light.copy_within(0..LAYER_LEN, i);
+            }
+        }
+        sky_light
+            .iter()
+            .copied()
+            .flat_map(|v| {
+                let v = v as u8;
+                [v & 0xF, v >> 4]
+            })
+            .zip(section.block_data.iter_mut())
+            .for_each(|(v, block_data)| {
+                block_data.set_lighting(block_data.lighting().with_sky(v));
+            });
     }
 }
 
@@ -704,9 +1510,106 @@ pub struct Chunk {
     pub coords: CCoords,
     pub sections: Vec<Section>,
     pub fully_generated: bool,
+    /// The chunk's generation status, e.g. `minecraft:features`, if the world format has one.
+    pub status: Option<ArcStr>,
+    /// Whether the chunk was saved without any light data, so it has full sky light down to its
+    /// lowest section and no block light, unless [`Chunk::compute_light()`] is used.
+    pub missing_light: bool,
+    pub heightmaps: Heightmaps,
 }
 
 impl Chunk {
+    /// Make a stand-in for a chunk that couldn't be read, so that it stands out when rendered: a
+    /// single checkerboard layer at sea level (or as close as the height range allows).
+    pub fn error_pattern(coords: CCoords, height_range: HeightRange, settings: &Settings) -> Chunk {
+        const PATTERN_Y: i32 = 62;
+        const PATTERN_BLOCKS: [&str; 3] = [
+            "minecraft:air",
+            "minecraft:red_concrete",
+            "minecraft:black_concrete",
+        ];
+        let y = PATTERN_Y.clamp(height_range.min_y, height_range.max_y() - 1);
+        let base_y = y.div_euclid(CHUNK_SIZE as i32) * CHUNK_SIZE as i32;
+
+        let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+        for (i, data) in block_data.iter_mut().enumerate() {
+            data.set_lighting(LightLevel::new().with_sky(0xF));
+            let index = BIndex::from_flat_index(i);
+            if index.y() as i32 == y - base_y {
+                // Checkerboard of 4x4 squares
+                data.set_state_index(1 + ((index.x() / 4 + index.z() / 4) % 2) as u16);
+            }
+        }
+        let block_palette = PATTERN_BLOCKS
+            .into_iter()
+            .map(|name| convert_block_state(BlockState::new(intern_str(name)), settings))
+            .collect();
+
+        Chunk {
+            coords,
+            sections: vec![Section {
+                base: BCoords(
+                    (
+                        coords.x() * CHUNK_SIZE as i32,
+                        coords.z() * CHUNK_SIZE as i32,
+                        base_y,
+                    )
+                        .into(),
+                ),
+                block_data,
+                block_palette,
+                biome_palette: vec![intern_str(DEFAULT_BIOME)],
+                block_entities: HashMap::new(),
+            }],
+            fully_generated: true,
+            status: None,
+            missing_light: false,
+            heightmaps: Heightmaps::default(),
+        }
+    }
+
+    /// Get the section whose lowest block is at `base_y`, if the chunk has one.
+    pub fn get_section(&self, base_y: i32) -> Option<&Section> {
+        let first_y = self.sections.first()?.base.y();
+        // Sections are normally contiguous, so try the obvious position first
+        let i = (base_y - first_y).div_euclid(CHUNK_SIZE as i32);
+        if let Ok(i) = usize::try_from(i)
+            && let Some(section) = self.sections.get(i)
+            && section.base.y() == base_y
+        {
+            return Some(section);
+        }
+        self.sections
+            .iter()
+            .find(|section| section.base.y() == base_y)
+    }
+
+    /// Add block entities from their NBT compounds to the sections containing them. Block entities
+    /// without a valid position inside the chunk are ignored.
+    fn add_block_entities(&mut self, entities: Vec<HashMap<String, fastnbt::Value>>) {
+        for entity in entities {
+            let Some((coords, entity)) = BlockEntity::from_nbt(entity) else {
+                continue;
+            };
+            let base_y = coords.y().div_euclid(CHUNK_SIZE as i32) * CHUNK_SIZE as i32;
+            let Some(section) = self
+                .sections
+                .iter_mut()
+                .find(|section| section.base.y() == base_y)
+            else {
+                continue;
+            };
+            let offset = coords - section.base;
+            if !(0..CHUNK_SIZE as i32).contains(&offset.x())
+                || !(0..CHUNK_SIZE as i32).contains(&offset.z())
+            {
+                continue;
+            }
+            let index = BIndex((offset.x() as u32, offset.z() as u32, offset.y() as u32).into());
+            section.block_entities.insert(index, entity);
+        }
+    }
+
     pub fn iter_blocks(&self) -> impl Iterator<Item = BlockInfo<'_>> {
         self.sections.iter().enumerate().flat_map(|(i, section)| {
             let y_offset = i * CHUNK_SIZE as usize;
@@ -724,9 +1627,16 @@ pub struct Section {
     pub block_data: [BlockData; SECTION_BLOCK_COUNT],
     pub block_palette: Vec<(BlockState, Arc<AssetRule>)>,
     pub biome_palette: Vec<ArcStr>,
+    /// Block entities, e.g. chests and signs, by the index of their block.
+    pub block_entities: HashMap<BIndex, BlockEntity>,
 }
 
 impl Section {
+    /// Get the index into [`Self::block_palette`] of the state of the block at `index`.
+    pub fn palette_index(&self, index: BIndex) -> usize {
+        self.block_data[index.to_flat_index()].state_index() as usize
+    }
+
     pub fn get_block(&self, index: BIndex) -> BlockInfo<'_> {
         let data = self.block_data[index.to_flat_index()];
         let (state, rule) = &self.block_palette[data.state_index() as usize];
@@ -737,7 +1647,17 @@ impl Section {
             biome,
             lighting: data.lighting(),
             render: rule.render.clone(),
+            block_entity: self.block_entities.get(&index),
+        }
+    }
+
+    /// Count the blocks using each entry of [`Self::block_palette`].
+    pub fn block_state_counts(&self) -> Vec<u64> {
+        let mut counts = vec![0; self.block_palette.len()];
+        for data in self.block_data.iter() {
+            counts[data.state_index() as usize] += 1;
         }
+        counts
     }
 
     pub fn iter_blocks(&self) -> impl Iterator<Item = BlockInfo<'_>> {
@@ -754,6 +1674,11 @@ impl Section {
                 biome,
                 lighting: data.lighting(),
                 render: rule.render.clone(),
+                block_entity: if self.block_entities.is_empty() {
+                    None
+                } else {
+                    self.block_entities.get(&index)
+                },
             }
         })
     }
@@ -814,6 +1739,43 @@ pub struct BlockInfo<'a> {
     pub biome: ArcStr,
     pub lighting: LightLevel,
     pub render: Arc<AssetRenderSpec>,
+    pub block_entity: Option<&'a BlockEntity>,
+}
+
+/// Extra data for a block that its block state can't hold, e.g. the text of a sign, the patterns
+/// on a banner or the items in a chest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    /// The block entity type, e.g. `minecraft:sign`. Worlds from before 1.11 use names like
+    /// `Sign` instead.
+    pub id: ArcStr,
+    /// The rest of the block entity's NBT, without its ID and position.
+    pub data: HashMap<String, fastnbt::Value>,
+}
+
+impl BlockEntity {
+    /// Get a block entity and its position from its NBT compound, if it has an ID and position.
+    fn from_nbt(mut nbt: HashMap<String, fastnbt::Value>) -> Option<(BCoords, Self)> {
+        let mut take_int = |key| match nbt.remove(key)? {
+            fastnbt::Value::Int(v) => Some(v),
+            _ => None,
+        };
+        let (x, y, z) = (take_int("x")?, take_int("y")?, take_int("z")?);
+        let id = match nbt.remove("id")? {
+            fastnbt::Value::String(id) => intern_str(id),
+            _ => return None,
+        };
+        nbt.remove("keepPacked");
+        Some((BCoords((x, z, y).into()), Self { id, data: nbt }))
+    }
+
+    /// Get a string field from the block entity's data, e.g. `CustomName`.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.data.get(key)? {
+            fastnbt::Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
 }
 
 #[bitfield(u8)]
@@ -852,3 +1814,267 @@ pub struct BlockData {
     #[bits(8)]
     lighting: LightLevel,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Get the builtin settings, for parsing chunks.
+    pub(crate) fn test_settings() -> Settings {
+        let config = Settings::config_builder(false)
+            .set_override("assets_path", "")
+            .unwrap()
+            .build()
+            .unwrap();
+        Settings::from_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_refresh_region() {
+        let settings = test_settings();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let region_dir = dir.join("region");
+        let chunk = |x, z| ChunkBuilder::new(CCoords((x, z).into()), HeightRange::OVERWORLD);
+        write_region_files(&region_dir, &[chunk(0, 0)], 100).unwrap();
+        let mut dimension = DimensionInfo::try_from_storage(
+            Arc::new(FileSystem),
+            dir.to_owned(),
+            Default::default(),
+        )
+        .unwrap();
+        let region = RCoords((0, 0).into());
+        let timestamps = dimension.get_region_timestamps(region).unwrap().unwrap();
+        assert_eq!(timestamps[0], 100);
+
+        // Rewritten and new region files are picked up
+        let paths = write_region_files(&region_dir, &[chunk(0, 0), chunk(-1, 0)], 200).unwrap();
+        for path in paths.iter() {
+            assert!(dimension.refresh_region(path).is_some());
+        }
+        assert_eq!(dimension.region_coords().len(), 2);
+        let timestamps = dimension.get_region_timestamps(region).unwrap().unwrap();
+        assert_eq!(timestamps[0], 200);
+
+        // A partly written chunk can't be read
+        let path = region_dir.join("r.0.0.mca");
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..REGION_HEADER_SIZE + 100]).unwrap();
+        dimension.refresh_region(&path);
+        assert!(
+            dimension
+                .load_chunk(CCoords((0, 0).into()), &settings)
+                .is_err()
+        );
+        // A file that's too small for its header is ignored until it's been written
+        std::fs::write(&path, &data[..100]).unwrap();
+        dimension.refresh_region(&path);
+        assert!(dimension.get_region(region).is_some());
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dimension.refresh_region(&path), Some(region));
+        assert!(dimension.get_region(region).is_none());
+        assert_eq!(
+            dimension.refresh_region(&region_dir.join("level.dat")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_world_level_dat() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let chunk = ChunkBuilder::new(CCoords((0, 0).into()), HeightRange::OVERWORLD);
+        write_region_files(&temp_dir.path().join("region"), [&chunk], 100).unwrap();
+        let level_dat = fastnbt::nbt!({
+            "Data": {
+                "LevelName": "Test World",
+                "DataVersion": 3953_i32,
+            },
+        });
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(temp_dir.path().join("level.dat")).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder
+            .write_all(&fastnbt::to_bytes(&level_dat).unwrap())
+            .unwrap();
+        encoder.finish().unwrap();
+
+        let world = WorldInfo::try_from_path(temp_dir.path().to_owned()).unwrap();
+        let level = world.level.unwrap();
+        assert_eq!(level.name, "Test World");
+        assert_eq!(level.data_version, Some(3953));
+    }
+
+    #[test]
+    fn test_detect_height_range() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let height_range = HeightRange::new(-32, 128);
+        let mut region = RegionWriter::new(RCoords((0, 0).into()));
+        // A chunk without any sections comes first, and is skipped
+        let empty_chunk = fastnbt::nbt!({"yPos": -4_i32, "sections": []});
+        region.insert_chunk(
+            CIndex((0, 0).into()),
+            fastnbt::to_bytes(&empty_chunk).unwrap(),
+            100,
+        );
+        let mut chunk = ChunkBuilder::new(CCoords((1, 0).into()), height_range);
+        chunk.set_block(0, 90, 0, BlockState::new(intern_str("minecraft:stone")));
+        region.add_chunk(&chunk, 100).unwrap();
+        region.save(&temp_dir.path().join("region")).unwrap();
+
+        let dimension = DimensionInfo::try_from_storage(
+            Arc::new(FileSystem),
+            temp_dir.path().to_owned(),
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(dimension.height_range, height_range);
+    }
+
+    #[test]
+    fn test_parse_heightmaps() {
+        let settings = test_settings();
+        let mut chunk = ChunkBuilder::new(CCoords((0, 0).into()), HeightRange::OVERWORLD);
+        chunk.set_block(5, 10, 5, BlockState::new(intern_str("minecraft:stone")));
+        // Leave out the sections above the stone, as Minecraft does for some chunks
+        let mut chunk_nbt: fastnbt::Value = fastnbt::from_bytes(&chunk.to_nbt().unwrap()).unwrap();
+        let fastnbt::Value::Compound(compound) = &mut chunk_nbt else {
+            panic!("chunk isn't a compound");
+        };
+        let Some(fastnbt::Value::List(sections)) = compound.get_mut("sections") else {
+            panic!("chunk has no sections");
+        };
+        sections.retain(|section| match section {
+            fastnbt::Value::Compound(section) => {
+                !matches!(section["Y"], fastnbt::Value::Byte(y) if y > 0)
+            }
+            _ => true,
+        });
+        let raw_chunk = RawChunk {
+            index: Default::default(),
+            coords: Default::default(),
+            data: fastnbt::to_bytes(&chunk_nbt).unwrap(),
+            compression: CompressionMethod::Uncompressed,
+        };
+
+        // Heightmaps are sized for the world's height, not the sections the chunk has
+        let chunk = raw_chunk.parse(HeightRange::OVERWORLD, &settings).unwrap();
+        assert_eq!(chunk.sections.len(), 5);
+        let world_surface = chunk.heightmaps.world_surface.unwrap();
+        assert_eq!(world_surface.get(5, 5), 11);
+        assert_eq!(world_surface.max(), 11);
+
+        // Heightmaps below the highest blocks are dropped
+        let chunk = raw_chunk
+            .parse(HeightRange::new(-128, 384), &settings)
+            .unwrap();
+        assert!(chunk.heightmaps.world_surface.is_none());
+    }
+
+    #[test]
+    fn test_load_entities() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let chunk = ChunkBuilder::new(CCoords((0, 0).into()), HeightRange::OVERWORLD);
+        write_region_files(&temp_dir.path().join("region"), [&chunk], 100).unwrap();
+        let mut entities = RegionWriter::new(RCoords((0, 0).into()));
+        let entity_chunk = fastnbt::nbt!({
+            "Position": [I; 1, 0],
+            "Entities": [{"id": "minecraft:pig", "Pos": [1.5, 64.0, 2.5]}],
+        });
+        entities.insert_chunk(
+            CIndex((1, 0).into()),
+            fastnbt::to_bytes(&entity_chunk).unwrap(),
+            100,
+        );
+        entities.insert_chunk(CIndex((2, 0).into()), b"garbage".to_vec(), 100);
+        entities.save(&temp_dir.path().join("entities")).unwrap();
+
+        let dimension = DimensionInfo::try_from_storage(
+            Arc::new(FileSystem),
+            temp_dir.path().to_owned(),
+            Default::default(),
+        )
+        .unwrap();
+        let chunks = dimension
+            .load_region_entities(RCoords((0, 0).into()))
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].entities[0].id.as_str(), "minecraft:pig");
+        assert!(dimension.load_entities(CCoords((2, 0).into())).is_err());
+        assert!(
+            dimension
+                .load_entities(CCoords((3, 0).into()))
+                .unwrap()
+                .is_none()
+        );
+        // The broken chunk is reported once, and the block region is still read separately
+        let errors = dimension.errors.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].stage, ErrorStage::Entities);
+        assert_eq!(errors[0].chunk, Some((2, 0)));
+        assert!(
+            dimension
+                .load_chunk(CCoords((0, 0).into()), &test_settings())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_dimension_id() {
+        assert_eq!(
+            DimensionID::from_str("overworld").unwrap(),
+            DimensionID::Overworld
+        );
+        assert_eq!(
+            DimensionID::from_str("nether").unwrap(),
+            DimensionID::Nether
+        );
+        assert_eq!(
+            DimensionID::from_str("the_nether").unwrap(),
+            DimensionID::Nether
+        );
+        assert_eq!(
+            DimensionID::from_str("minecraft:the_end").unwrap(),
+            DimensionID::TheEnd
+        );
+        assert_eq!(
+            DimensionID::from_str("mymod:mining/deep").unwrap(),
+            DimensionID::Other("mymod:mining/deep".to_owned())
+        );
+        assert_eq!(
+            DimensionID::from_str("aether").unwrap(),
+            DimensionID::Other("minecraft:aether".to_owned())
+        );
+        assert!(DimensionID::from_str("").is_err());
+        assert_eq!(DimensionID::Nether.to_string(), "minecraft:the_nether");
+        assert_eq!(
+            DimensionID::Other("mymod:mining/deep".to_owned()).to_string(),
+            "mymod:mining/deep"
+        );
+    }
+
+    #[test]
+    fn test_height_range() {
+        assert_eq!(
+            HeightRange::for_dimension(&DimensionID::Overworld, None),
+            HeightRange::OVERWORLD
+        );
+        assert_eq!(
+            HeightRange::for_dimension(&DimensionID::Overworld, Some(1343)),
+            HeightRange::LEGACY
+        );
+        assert_eq!(
+            HeightRange::for_dimension(&DimensionID::Nether, Some(3953)),
+            HeightRange::LEGACY
+        );
+        assert_eq!(HeightRange::OVERWORLD.max_y(), 320);
+        assert_eq!(HeightRange::OVERWORLD.section_count(), 24);
+        assert!(HeightRange::OVERWORLD.contains_section(-64));
+        assert!(HeightRange::OVERWORLD.contains_section(304));
+        assert!(!HeightRange::OVERWORLD.contains_section(320));
+        assert!(!HeightRange::LEGACY.contains_section(-16));
+    }
+}
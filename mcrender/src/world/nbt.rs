@@ -1,33 +1,57 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
+use fastnbt::Value;
 use serde::Deserialize;
 
 use crate::proplist::DefaultPropList as PropList;
 
+/// Build an NBT compound from `(key, value)` pairs.
+pub(super) fn compound<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Compound(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct Chunk<'a> {
-    // #[serde(rename = "DataVersion")]
-    // pub data_version: u32,
+    #[serde(rename = "DataVersion")]
+    pub data_version: Option<i32>,
+    // Since 1.18 (21w43a) the chunk data is at the top level...
     #[serde(rename = "xPos")]
-    pub x_pos: i32,
+    pub x_pos: Option<i32>,
     #[serde(rename = "zPos")]
-    pub z_pos: i32,
+    pub z_pos: Option<i32>,
     #[serde(rename = "yPos")]
-    pub y_pos: i32,
+    pub y_pos: Option<i32>,
     #[serde(rename = "Status")]
-    pub status: Cow<'a, str>,
     #[serde(borrow)]
+    pub status: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    #[serde(default)]
     pub sections: Vec<Section<'a>>,
+    #[serde(rename = "Heightmaps")]
+    pub heightmaps: Option<Heightmaps>,
+    #[serde(default)]
+    pub block_entities: Vec<HashMap<String, fastnbt::Value>>,
+    // ... and before that it's all inside a `Level` compound
+    #[serde(rename = "Level")]
+    #[serde(borrow)]
+    pub level: Option<LegacyLevel<'a>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub(super) struct Section<'a> {
     #[serde(rename = "Y")]
     pub y: i8,
+    // Absent for sections just outside the world's height range that only store lighting
     #[serde(borrow)]
-    pub block_states: BlockStates<'a>,
+    pub block_states: Option<BlockStates<'a>>,
     #[serde(borrow)]
-    pub biomes: Biomes<'a>,
+    pub biomes: Option<Biomes<'a>>,
     #[serde(rename = "BlockLight")]
     #[serde(borrow)]
     pub block_light: Option<fastnbt::borrow::ByteArray<'a>>,
@@ -35,6 +59,54 @@ pub(super) struct Section<'a> {
     pub sky_light: Option<fastnbt::ByteArray>,
 }
 
+#[derive(Deserialize, derive_more::Debug)]
+pub(super) struct Heightmaps {
+    #[serde(rename = "WORLD_SURFACE")]
+    #[debug(ignore)]
+    pub world_surface: Option<fastnbt::LongArray>,
+    #[serde(rename = "OCEAN_FLOOR")]
+    #[debug(ignore)]
+    pub ocean_floor: Option<fastnbt::LongArray>,
+}
+
+/// Just enough of a chunk to figure out the height range of the world it belongs to.
+#[derive(Debug, Deserialize)]
+pub(super) struct ChunkHeight {
+    #[serde(rename = "yPos")]
+    pub y_pos: Option<i32>,
+    #[serde(default)]
+    pub sections: Vec<SectionHeight>,
+}
+
+/// Just enough of a chunk to summarize it when scanning a world.
+#[derive(Debug, Deserialize)]
+pub(super) struct ChunkSummary<'a> {
+    #[serde(rename = "DataVersion")]
+    pub data_version: Option<i32>,
+    #[serde(rename = "Status")]
+    #[serde(borrow)]
+    pub status: Option<Cow<'a, str>>,
+    #[serde(rename = "Level")]
+    #[serde(borrow)]
+    pub level: Option<LevelSummary<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct LevelSummary<'a> {
+    #[serde(rename = "Status")]
+    #[serde(borrow)]
+    pub status: Option<Cow<'a, str>>,
+    #[serde(rename = "TerrainPopulated")]
+    pub terrain_populated: Option<i8>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct SectionHeight {
+    #[serde(rename = "Y")]
+    pub y: i8,
+    pub block_states: Option<serde::de::IgnoredAny>,
+}
+
 #[derive(Deserialize, derive_more::Debug)]
 pub(super) struct BlockStates<'a> {
     pub palette: Vec<BlockState<'a>>,
@@ -60,3 +132,138 @@ pub(super) struct Biomes<'a> {
     #[debug(ignore)]
     pub data: Option<fastnbt::borrow::LongArray<'a>>,
 }
+
+/// Chunk data from before 1.18, inside the `Level` compound. This covers the formats from both
+/// before and after the Flattening (1.13), which differ in how blocks and biomes are stored.
+#[derive(derive_more::Debug, Deserialize)]
+pub(super) struct LegacyLevel<'a> {
+    #[serde(rename = "xPos")]
+    pub x_pos: i32,
+    #[serde(rename = "zPos")]
+    pub z_pos: i32,
+    #[serde(rename = "Status")]
+    #[serde(borrow)]
+    pub status: Option<Cow<'a, str>>,
+    // Before 1.13, instead of `Status`
+    #[serde(rename = "TerrainPopulated")]
+    pub terrain_populated: Option<i8>,
+    #[serde(rename = "Sections")]
+    #[serde(borrow)]
+    #[serde(default)]
+    pub sections: Vec<LegacySection<'a>>,
+    // Since 1.13
+    #[serde(rename = "Heightmaps")]
+    pub heightmaps: Option<Heightmaps>,
+    #[serde(rename = "TileEntities")]
+    #[serde(default)]
+    #[debug(ignore)]
+    pub tile_entities: Vec<HashMap<String, fastnbt::Value>>,
+    /// Numeric biome IDs: a byte per block column before 1.13, an int per block column until 1.15,
+    /// and an int per 4x4x4 cell after that.
+    #[serde(rename = "Biomes")]
+    #[debug(ignore)]
+    pub biomes: Option<fastnbt::Value>,
+    // McRegion chunks have no sections, just 128 block high arrays for the whole chunk
+    #[serde(rename = "Blocks")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub blocks: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "Data")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub data: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "BlockLight")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub block_light: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "SkyLight")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub sky_light: Option<fastnbt::borrow::ByteArray<'a>>,
+}
+
+#[derive(derive_more::Debug, Deserialize)]
+pub(super) struct LegacySection<'a> {
+    #[serde(rename = "Y")]
+    pub y: i8,
+    // Since 1.13; absent for sections that only store lighting
+    #[serde(rename = "Palette")]
+    #[serde(borrow)]
+    pub palette: Option<Vec<BlockState<'a>>>,
+    #[serde(rename = "BlockStates")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub block_states: Option<fastnbt::borrow::LongArray<'a>>,
+    // Before 1.13: low 8 bits of block IDs, high 4 bits of block IDs, and 4 bit data values
+    #[serde(rename = "Blocks")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub blocks: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "Add")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub add: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "Data")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub data: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "BlockLight")]
+    #[serde(borrow)]
+    #[debug(ignore)]
+    pub block_light: Option<fastnbt::borrow::ByteArray<'a>>,
+    #[serde(rename = "SkyLight")]
+    #[debug(ignore)]
+    pub sky_light: Option<fastnbt::ByteArray>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct LevelDat {
+    #[serde(rename = "Data")]
+    pub data: LevelData,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct LevelData {
+    #[serde(rename = "LevelName")]
+    pub level_name: Option<String>,
+    #[serde(rename = "DataVersion")]
+    pub data_version: Option<i32>,
+    #[serde(rename = "Time")]
+    pub time: Option<i64>,
+    #[serde(rename = "DayTime")]
+    pub day_time: Option<i64>,
+    // Spawn position before 1.21.9
+    #[serde(rename = "SpawnX")]
+    pub spawn_x: Option<i32>,
+    #[serde(rename = "SpawnY")]
+    pub spawn_y: Option<i32>,
+    #[serde(rename = "SpawnZ")]
+    pub spawn_z: Option<i32>,
+    // Spawn position since 1.21.9
+    pub spawn: Option<LevelSpawn>,
+    // Seed before 1.16
+    #[serde(rename = "RandomSeed")]
+    pub random_seed: Option<i64>,
+    // Seed since 1.16
+    #[serde(rename = "WorldGenSettings")]
+    pub world_gen_settings: Option<WorldGenSettings>,
+    #[serde(rename = "BorderCenterX")]
+    pub border_center_x: Option<f64>,
+    #[serde(rename = "BorderCenterZ")]
+    pub border_center_z: Option<f64>,
+    #[serde(rename = "BorderSize")]
+    pub border_size: Option<f64>,
+    #[serde(rename = "GameRules")]
+    pub game_rules: Option<HashMap<String, fastnbt::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct LevelSpawn {
+    pub dimension: Option<String>,
+    pub pos: fastnbt::IntArray,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct WorldGenSettings {
+    pub seed: i64,
+}
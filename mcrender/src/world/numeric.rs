@@ -0,0 +1,391 @@
+/*
+Pre-Flattening chunk format notes:
+
+- Before 1.13, blocks are stored as numeric IDs and 4 bit "data" values instead of block states. The
+  meaning of the data value depends on the block, e.g. wood type, facing direction or crop age.
+  `numeric_block_ids.txt` maps these to modern block states.
+- Anvil sections have `Blocks` (low 8 bits of the ID), `Add` (optional high 4 bits) and `Data`, all
+  in the same YZX order as modern sections. Nibble arrays store the even index in the low nibble.
+- McRegion chunks (before 1.2) have no sections: `Blocks`, `Data`, `BlockLight` and `SkyLight` cover
+  the whole 128 block high chunk, in XZY order (Y varies fastest).
+- The upper half of a double plant doesn't record which plant it is, so it's taken from the block
+  below.
+ */
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+
+use crate::proplist::DefaultPropList as PropList;
+use crate::settings::Settings;
+use crate::util::intern_str;
+use crate::world::legacy::{apply_biomes, biome_ids};
+use crate::world::{
+    BCoords, BIndex, BlockData, BlockState, CCoords, CHUNK_SIZE, Chunk, HeightRange, Heightmaps,
+    SECTION_BLOCK_COUNT, Section, SkyLightData, apply_block_light, convert_block_state, nbt,
+};
+
+static BLOCK_IDS: OnceLock<BlockIdTable> = OnceLock::new();
+
+const DOUBLE_PLANT_ID: u16 = 175;
+const DOUBLE_PLANT_UPPER: u16 = 8;
+
+/// Numeric block ID in the top 12 bits, data value in the bottom 4 bits.
+type BlockKey = u16;
+
+/// Mapping from pre-Flattening block IDs and data values to block states.
+#[derive(Debug)]
+pub struct BlockIdTable {
+    states: HashMap<(u16, Option<u8>), BlockState>,
+}
+
+impl BlockIdTable {
+    /// Get the bundled mapping table.
+    pub fn builtin() -> &'static Self {
+        BLOCK_IDS.get_or_init(|| {
+            Self::parse(include_str!("numeric_block_ids.txt")).expect("invalid block ID table")
+        })
+    }
+
+    /// Parse a mapping table, with one `ID[:DATA] NAME[PROPERTY=VALUE,...]` entry per line.
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut states = HashMap::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, state) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("invalid block ID mapping: {:?}", line))?;
+            let (id, data) = match key.split_once(':') {
+                Some((id, data)) => (id.parse()?, Some(data.parse()?)),
+                None => (key.parse()?, None),
+            };
+            states.insert((id, data), parse_block_state(state.trim())?);
+        }
+        Ok(Self { states })
+    }
+
+    /// Get the block state for block `id` with `data`, or air if the block ID isn't known.
+    pub fn get(&self, id: u16, data: u8) -> BlockState {
+        self.states
+            .get(&(id, Some(data)))
+            .or_else(|| self.states.get(&(id, None)))
+            .cloned()
+            .unwrap_or_else(|| BlockState::new(intern_str("minecraft:air")))
+    }
+}
+
+/// Parse `name[property=value,...]`, adding the `minecraft` namespace if `name` has none.
+pub(super) fn parse_block_state(s: &str) -> anyhow::Result<BlockState> {
+    let (name, properties) = split_block_state(s)?;
+    let name = if name.contains(':') {
+        intern_str(name)
+    } else {
+        intern_str(format!("minecraft:{name}"))
+    };
+    Ok(BlockState { name, properties })
+}
+
+/// Split `name[property=value,...]` into the name, as it's written, and the properties.
+pub(super) fn split_block_state(s: &str) -> anyhow::Result<(&str, PropList)> {
+    let (name, properties) = match s.split_once('[') {
+        Some((name, rest)) => {
+            let properties = rest
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("invalid block state: {:?}", s))?;
+            (name, Some(properties))
+        }
+        None => (s, None),
+    };
+    let mut property_list = PropList::new();
+    for property in properties.into_iter().flat_map(|p| p.split(',')) {
+        let (key, value) = property
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid block state: {:?}", s))?;
+        property_list.insert(key, value);
+    }
+    Ok((name, property_list))
+}
+
+/// Parse a chunk from before the Flattening, from the contents of its `Level` compound.
+pub(super) fn parse_level(
+    mut level: nbt::LegacyLevel,
+    settings: &Settings,
+) -> anyhow::Result<(Chunk, SkyLightData)> {
+    let coords = CCoords((level.x_pos, level.z_pos).into());
+    let mut chunk = Chunk {
+        coords,
+        sections: Vec::with_capacity(level.sections.len()),
+        fully_generated: level.terrain_populated.is_none_or(|v| v != 0),
+        status: None,
+        missing_light: false,
+        heightmaps: Heightmaps::default(),
+    };
+    let biome_ids = biome_ids(level.biomes);
+    let mut sky_light_data: SkyLightData = Vec::with_capacity(level.sections.len());
+
+    if let Some(blocks) = level.blocks {
+        // McRegion: split the whole-chunk arrays into sections
+        const CHUNK_HEIGHT: usize = HeightRange::MCREGION.height as usize;
+        let blocks: Vec<u8> = blocks.iter().map(|v| v as u8).collect();
+        let data: Vec<u8> = level
+            .data
+            .map(|d| nibbles(d.iter()).collect())
+            .unwrap_or_default();
+        let block_light: Vec<u8> = level
+            .block_light
+            .map(|d| nibbles(d.iter()).collect())
+            .unwrap_or_default();
+        let sky_light: Vec<u8> = level
+            .sky_light
+            .map(|d| nibbles(d.iter()).collect())
+            .unwrap_or_default();
+        if blocks.len() < CHUNK_HEIGHT * (CHUNK_SIZE * CHUNK_SIZE) as usize {
+            return Err(anyhow!("McRegion chunk has too few blocks"));
+        }
+
+        let mut below: Option<[BlockKey; SECTION_BLOCK_COUNT]> = None;
+        for section_y in 0..HeightRange::MCREGION.section_count() {
+            // Index into the McRegion arrays for each block in the section
+            let source_index = |i: usize| {
+                let index = BIndex::from_flat_index(i);
+                (section_y * CHUNK_SIZE as usize + index.y() as usize)
+                    + index.z() as usize * CHUNK_HEIGHT
+                    + index.x() as usize * CHUNK_HEIGHT * CHUNK_SIZE as usize
+            };
+            let mut keys = [0 as BlockKey; SECTION_BLOCK_COUNT];
+            for (i, key) in keys.iter_mut().enumerate() {
+                let j = source_index(i);
+                *key = ((blocks[j] as u16) << 4) | data.get(j).copied().unwrap_or(0) as u16;
+            }
+            let mut section = build_section(
+                coords,
+                section_y as i8,
+                &mut keys,
+                below.as_ref(),
+                &biome_ids,
+                settings,
+            );
+            below = Some(keys);
+
+            if !block_light.is_empty() {
+                let packed = pack_nibbles(|i| block_light.get(source_index(i)).copied());
+                apply_block_light(&mut section.block_data, packed.into_iter());
+            }
+            let section_sky_light = (!sky_light.is_empty()).then(|| {
+                fastnbt::ByteArray::new(pack_nibbles(|i| sky_light.get(source_index(i)).copied()))
+            });
+            sky_light_data.push(section_sky_light);
+            chunk.sections.push(section);
+        }
+    } else {
+        // Anvil: sections of 16x16x16 blocks, as in later formats
+        level.sections.sort_by_key(|section_nbt| section_nbt.y);
+        let mut below: Option<(i8, [BlockKey; SECTION_BLOCK_COUNT])> = None;
+        for section_nbt in level.sections.into_iter() {
+            // Skip sections that only exist for lighting
+            let Some(blocks) = section_nbt.blocks else {
+                continue;
+            };
+
+            let mut keys = [0 as BlockKey; SECTION_BLOCK_COUNT];
+            let mut add = section_nbt.add.map(|add| nibbles(add.iter()));
+            let mut data = section_nbt.data.map(|data| nibbles(data.iter()));
+            for (key, block) in keys.iter_mut().zip(blocks.iter()) {
+                let high = add.as_mut().and_then(Iterator::next).unwrap_or(0) as u16;
+                let value = data.as_mut().and_then(Iterator::next).unwrap_or(0) as u16;
+                *key = (high << 12) | ((block as u8 as u16) << 4) | value;
+            }
+            let below_keys = below
+                .as_ref()
+                .filter(|(y, _)| *y + 1 == section_nbt.y)
+                .map(|(_, keys)| keys);
+            let mut section = build_section(
+                coords,
+                section_nbt.y,
+                &mut keys,
+                below_keys,
+                &biome_ids,
+                settings,
+            );
+            below = Some((section_nbt.y, keys));
+
+            if let Some(data) = section_nbt.block_light {
+                apply_block_light(&mut section.block_data, data.iter());
+            }
+            sky_light_data.push(section_nbt.sky_light);
+            chunk.sections.push(section);
+        }
+    }
+
+    Ok((chunk, sky_light_data))
+}
+
+/// Build a section from the block keys of each block in the section. `below` is the keys of the
+/// section immediately below, if there is one.
+fn build_section(
+    coords: CCoords,
+    section_y: i8,
+    keys: &mut [BlockKey; SECTION_BLOCK_COUNT],
+    below: Option<&[BlockKey; SECTION_BLOCK_COUNT]>,
+    biome_ids: &[i32],
+    settings: &Settings,
+) -> Section {
+    const LAYER_LEN: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+    // Take the plant type of the upper half of double plants from the lower half
+    for i in 0..SECTION_BLOCK_COUNT {
+        let key = keys[i];
+        if key >> 4 != DOUBLE_PLANT_ID || key & DOUBLE_PLANT_UPPER == 0 {
+            continue;
+        }
+        let below_key = if i >= LAYER_LEN {
+            Some(keys[i - LAYER_LEN])
+        } else {
+            below.map(|below| below[i + SECTION_BLOCK_COUNT - LAYER_LEN])
+        };
+        if let Some(below_key) = below_key
+            && below_key >> 4 == DOUBLE_PLANT_ID
+            && below_key & DOUBLE_PLANT_UPPER == 0
+        {
+            keys[i] = (DOUBLE_PLANT_ID << 4) | DOUBLE_PLANT_UPPER | (below_key & 0x7);
+        }
+    }
+
+    // Build the block palette from the unique keys in the section
+    let mut palette_keys: Vec<BlockKey> = Vec::new();
+    let mut palette_indexes: HashMap<BlockKey, u16> = HashMap::new();
+    let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+    for (key, data) in keys.iter().zip(block_data.iter_mut()) {
+        let index = *palette_indexes.entry(*key).or_insert_with(|| {
+            palette_keys.push(*key);
+            (palette_keys.len() - 1) as u16
+        });
+        data.set_state_index(index);
+    }
+    let table = BlockIdTable::builtin();
+    let block_palette = palette_keys
+        .into_iter()
+        .map(|key| convert_block_state(table.get(key >> 4, (key & 0xF) as u8), settings))
+        .collect();
+
+    let biome_palette = apply_biomes(&mut block_data, biome_ids, section_y);
+
+    Section {
+        base: BCoords(
+            (
+                coords.x() * CHUNK_SIZE as i32,
+                coords.z() * CHUNK_SIZE as i32,
+                section_y as i32 * CHUNK_SIZE as i32,
+            )
+                .into(),
+        ),
+        block_data,
+        block_palette,
+        biome_palette,
+        block_entities: HashMap::new(),
+    }
+}
+
+/// Split a nibble array into its values, low nibble first.
+fn nibbles(data: impl Iterator<Item = i8>) -> impl Iterator<Item = u8> {
+    data.flat_map(|v| {
+        let v = v as u8;
+        [v & 0xF, v >> 4]
+    })
+}
+
+/// Build a section's nibble array, taking the value for each block index from `f`.
+fn pack_nibbles<F: Fn(usize) -> Option<u8>>(f: F) -> Vec<i8> {
+    (0..SECTION_BLOCK_COUNT / 2)
+        .map(|i| {
+            let low = f(2 * i).unwrap_or(0) & 0xF;
+            let high = f(2 * i + 1).unwrap_or(0) & 0xF;
+            (low | (high << 4)) as i8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::test_settings;
+    use crate::world::{CompressionMethod, RawChunk};
+
+    #[test]
+    fn test_block_id_table() {
+        let table = BlockIdTable::builtin();
+        assert_eq!(table.get(1, 0).to_string(), "minecraft:stone");
+        assert_eq!(table.get(1, 3).to_string(), "minecraft:diorite");
+        assert_eq!(table.get(17, 8).to_string(), "minecraft:oak_log{axis=z}");
+        assert_eq!(table.get(59, 5).get_property("age"), Some("5"));
+        // Unlisted data values use the block's default
+        assert_eq!(table.get(4, 7).to_string(), "minecraft:cobblestone");
+        // Unknown blocks are air
+        assert_eq!(table.get(4000, 0).to_string(), "minecraft:air");
+    }
+
+    #[test]
+    fn test_parse_level() {
+        // Anvil section with a double plant split across two sections
+        let mut lower_blocks = vec![0i8; 4096];
+        let mut lower_data = vec![0i8; 2048];
+        let mut upper_blocks = vec![0i8; 4096];
+        let mut upper_data = vec![0i8; 2048];
+        // Rose bush at (1, 15, 0), i.e. the top layer of the lower section
+        let index = BIndex((1, 0, 15).into()).to_flat_index();
+        lower_blocks[index] = 175u8 as i8;
+        lower_data[index / 2] = 4 << 4;
+        let index = BIndex((1, 0, 0).into()).to_flat_index();
+        upper_blocks[index] = 175u8 as i8;
+        upper_data[index / 2] = 8 << 4;
+        // Spruce planks at (0, 0, 0)
+        lower_blocks[0] = 5;
+        lower_data[0] = 1;
+        let chunk_nbt = fastnbt::nbt!({
+            "DataVersion": 1343_i32,
+            "Level": {
+                "xPos": 1_i32,
+                "zPos": 2_i32,
+                "TerrainPopulated": 1_i8,
+                "Biomes": fastnbt::ByteArray::new(vec![6; 256]),
+                "Sections": [
+                    {
+                        "Y": 4_i8,
+                        "Blocks": fastnbt::ByteArray::new(upper_blocks),
+                        "Data": fastnbt::ByteArray::new(upper_data),
+                    },
+                    {
+                        "Y": 3_i8,
+                        "Blocks": fastnbt::ByteArray::new(lower_blocks),
+                        "Data": fastnbt::ByteArray::new(lower_data),
+                    },
+                ],
+            },
+        });
+        let raw_chunk = RawChunk {
+            index: Default::default(),
+            coords: Default::default(),
+            data: fastnbt::to_bytes(&chunk_nbt).unwrap(),
+            compression: CompressionMethod::Uncompressed,
+        };
+        let chunk = raw_chunk
+            .parse(HeightRange::MCREGION, &test_settings())
+            .unwrap();
+        assert!(chunk.fully_generated);
+        assert_eq!(chunk.sections.len(), 2);
+        assert_eq!(chunk.sections[0].base, BCoords((16, 32, 48).into()));
+
+        let planks = chunk.sections[0].get_block(BIndex((0, 0, 0).into()));
+        assert_eq!(planks.state.name, "minecraft:spruce_planks");
+        assert_eq!(planks.biome, "minecraft:swamp");
+        let lower = chunk.sections[0].get_block(BIndex((1, 0, 15).into()));
+        assert_eq!(lower.state.name, "minecraft:rose_bush");
+        let upper = chunk.sections[1].get_block(BIndex((1, 0, 0).into()));
+        assert_eq!(upper.state.name, "minecraft:rose_bush");
+        assert_eq!(upper.state.get_property("half"), Some("upper"));
+    }
+}
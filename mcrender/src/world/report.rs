@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::world::{CCoords, RCoords};
+
+/// The stage of reading a world at which an error happened.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, derive_more::Display,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorStage {
+    /// Finding or opening a region file, or reading its header.
+    #[display("region")]
+    Region,
+    /// Reading and decompressing a chunk's data from a region file.
+    #[display("chunk read")]
+    ChunkRead,
+    /// Parsing a chunk's NBT data.
+    #[display("chunk parse")]
+    ChunkParse,
+    /// Reading an entity region file, or the entities in one of its chunks.
+    #[display("entities")]
+    Entities,
+}
+
+/// A problem with a region file or chunk that meant it couldn't be rendered.
+#[derive(Clone, Debug, Serialize)]
+pub struct WorldError {
+    pub stage: ErrorStage,
+    /// Region coordinates, if known.
+    pub region: Option<(i32, i32)>,
+    /// Chunk coordinates, if the error was for a single chunk.
+    pub chunk: Option<(i32, i32)>,
+    /// The region file.
+    pub path: Option<PathBuf>,
+    /// Byte offset of the chunk data within the region file, if known.
+    pub offset: Option<u64>,
+    pub cause: String,
+}
+
+impl WorldError {
+    pub fn region(region_coords: RCoords, path: PathBuf, cause: &anyhow::Error) -> Self {
+        Self {
+            stage: ErrorStage::Region,
+            region: Some((region_coords.x(), region_coords.z())),
+            chunk: None,
+            path: Some(path),
+            offset: None,
+            cause: format!("{cause:#}"),
+        }
+    }
+
+    pub fn chunk(
+        stage: ErrorStage,
+        chunk_coords: CCoords,
+        path: Option<PathBuf>,
+        offset: Option<u64>,
+        cause: &anyhow::Error,
+    ) -> Self {
+        let (region_coords, _) = chunk_coords.to_region_coords();
+        Self {
+            stage,
+            region: Some((region_coords.x(), region_coords.z())),
+            chunk: Some((chunk_coords.x(), chunk_coords.z())),
+            path,
+            offset,
+            cause: format!("{cause:#}"),
+        }
+    }
+
+    /// What the error is about, so that errors about the same thing can be recognised.
+    fn location(&self) -> ErrorLocation {
+        (self.stage, self.region, self.chunk)
+    }
+}
+
+impl std::fmt::Display for WorldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} error", self.stage)?;
+        if let Some((x, z)) = self.chunk {
+            write!(f, " in chunk {x},{z}")?;
+        } else if let Some((x, z)) = self.region {
+            write!(f, " in region {x},{z}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " ({:?}", path)?;
+            if let Some(offset) = self.offset {
+                write!(f, " at byte {offset:#x}")?;
+            }
+            write!(f, ")")?;
+        }
+        write!(f, ": {}", self.cause)
+    }
+}
+
+/// The stage, region and chunk of a [`WorldError`].
+type ErrorLocation = (ErrorStage, Option<(i32, i32)>, Option<(i32, i32)>);
+
+/// Errors collected while reading a dimension. Each error is logged as it's recorded.
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    errors: Mutex<RecordedErrors>,
+}
+
+#[derive(Debug, Default)]
+struct RecordedErrors {
+    /// Errors in the order they were recorded.
+    list: Vec<WorldError>,
+    locations: HashSet<ErrorLocation>,
+}
+
+impl ErrorReport {
+    /// Record `error`, unless an error was already recorded for the same region or chunk (which
+    /// happens when several renderers read the same chunk).
+    pub fn record(&self, error: WorldError) {
+        let mut errors = self.errors.lock();
+        if !errors.locations.insert(error.location()) {
+            return;
+        }
+        log::warn!("{error}");
+        errors.list.push(error);
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.lock().list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.lock().list.is_empty()
+    }
+
+    /// Get a copy of all the errors recorded so far.
+    pub fn errors(&self) -> Vec<WorldError> {
+        self.errors.lock().list.clone()
+    }
+}
+
+/// All the errors from reading a world, for reporting at the end of a run.
+#[derive(Debug, Default, Serialize)]
+pub struct ErrorSummary {
+    pub error_count: usize,
+    pub errors: Vec<DimensionError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DimensionError {
+    pub dimension: String,
+    #[serde(flatten)]
+    pub error: WorldError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_error_report() {
+        let report = ErrorReport::default();
+        let coords = CCoords((33, -1).into());
+        let path = PathBuf::from("region/r.1.-1.mca");
+        report.record(WorldError::chunk(
+            ErrorStage::ChunkParse,
+            coords,
+            Some(path.clone()),
+            Some(0x3000),
+            &anyhow!("invalid NBT"),
+        ));
+        // Duplicate errors for the same chunk are ignored
+        report.record(WorldError::chunk(
+            ErrorStage::ChunkParse,
+            coords,
+            Some(path.clone()),
+            Some(0x3000),
+            &anyhow!("invalid NBT"),
+        ));
+        assert_eq!(report.len(), 1);
+
+        let error = &report.errors()[0];
+        assert_eq!(error.region, Some((1, -1)));
+        assert_eq!(
+            error.to_string(),
+            "chunk parse error in chunk 33,-1 (\"region/r.1.-1.mca\" at byte 0x3000): invalid NBT"
+        );
+        let json = serde_json::to_value(error).unwrap();
+        assert_eq!(json["stage"], "chunk_parse");
+        assert_eq!(json["chunk"], serde_json::json!([33, -1]));
+        assert_eq!(json["offset"], 0x3000);
+    }
+}
@@ -0,0 +1,467 @@
+/*
+Region file validation notes:
+
+- Each chunk's entry in the region header is a 3 byte sector offset and a 1 byte sector count.
+  The first 2 sectors are the header itself, so no chunk can start before sector 2.
+- Minecraft pads region files to a whole number of sectors, but some other tools don't, so the
+  last chunk's final sector is allowed to be incomplete.
+- Chunks should never share sectors. When they do, usually after a crash or a bad copy, at least
+  one of them will fail to read or will silently contain another chunk's data.
+ */
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::settings::Settings;
+use crate::world::{
+    CIndex, DimensionID, DimensionInfo, ErrorStage, HeightRange, REGION_CHUNK_COUNT,
+    REGION_HEADER_SIZE, Region, RegionFormat, RegionInfo, SECTOR_SIZE, WorldError, WorldInfo, nbt,
+};
+
+/// Counts of chunk properties, for a region or a whole dimension.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ChunkStats {
+    pub chunk_count: usize,
+    /// Chunks by generation status. Before 1.13 chunks have no status, so `TerrainPopulated` is
+    /// used instead.
+    pub statuses: BTreeMap<String, usize>,
+    /// Chunks by `DataVersion`, with 0 for chunks that have none (before 1.9).
+    pub data_versions: BTreeMap<i32, usize>,
+    /// Chunks by compression method.
+    pub compression: BTreeMap<String, usize>,
+}
+
+impl ChunkStats {
+    fn merge(&mut self, other: &ChunkStats) {
+        self.chunk_count += other.chunk_count;
+        for (status, count) in other.statuses.iter() {
+            *self.statuses.entry(status.clone()).or_default() += count;
+        }
+        for (data_version, count) in other.data_versions.iter() {
+            *self.data_versions.entry(*data_version).or_default() += count;
+        }
+        for (method, count) in other.compression.iter() {
+            *self.compression.entry(method.clone()).or_default() += count;
+        }
+    }
+}
+
+/// A problem with a chunk's entry in a region file header.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SectorProblem {
+    /// The chunk's data starts inside the region header.
+    InHeader {
+        chunk: (i32, i32),
+        sector: u32,
+        count: u32,
+    },
+    /// The chunk's data has no sectors.
+    Empty { chunk: (i32, i32), sector: u32 },
+    /// The chunk's data extends past the end of the region file.
+    OutOfBounds {
+        chunk: (i32, i32),
+        sector: u32,
+        count: u32,
+        file_sectors: u32,
+    },
+    /// The chunk's data shares sectors with another chunk.
+    Overlap {
+        chunk: (i32, i32),
+        sector: u32,
+        count: u32,
+        other: (i32, i32),
+    },
+}
+
+impl std::fmt::Display for SectorProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SectorProblem::InHeader {
+                chunk,
+                sector,
+                count,
+            } => write!(
+                f,
+                "chunk {},{}: sectors {}..{} overlap the region header",
+                chunk.0,
+                chunk.1,
+                sector,
+                sector + count
+            ),
+            SectorProblem::Empty { chunk, sector } => write!(
+                f,
+                "chunk {},{}: zero sectors at sector {}",
+                chunk.0, chunk.1, sector
+            ),
+            SectorProblem::OutOfBounds {
+                chunk,
+                sector,
+                count,
+                file_sectors,
+            } => write!(
+                f,
+                "chunk {},{}: sectors {}..{} are past the end of the file ({} sectors)",
+                chunk.0,
+                chunk.1,
+                sector,
+                sector + count,
+                file_sectors
+            ),
+            SectorProblem::Overlap {
+                chunk,
+                sector,
+                count,
+                other,
+            } => write!(
+                f,
+                "chunk {},{}: sectors {}..{} overlap chunk {},{}",
+                chunk.0,
+                chunk.1,
+                sector,
+                sector + count,
+                other.0,
+                other.1
+            ),
+        }
+    }
+}
+
+/// Check the chunk entries in a region's header for sectors that can't be right, given the region
+/// file is `file_size` bytes long.
+pub fn check_sectors<S: std::io::Read + std::io::Seek>(
+    region: &Region<S>,
+    file_size: u64,
+) -> Vec<SectorProblem> {
+    let file_sectors = file_size.div_ceil(SECTOR_SIZE as u64) as u32;
+    let header_sectors = (REGION_HEADER_SIZE / SECTOR_SIZE) as u32;
+    let region_coords = region.info().coords;
+    let chunk_coords = |index: CIndex| {
+        let coords = index.to_chunk_coords(region_coords);
+        (coords.x(), coords.z())
+    };
+
+    let mut problems = Vec::new();
+    let mut valid = Vec::new();
+    for i in 0..REGION_CHUNK_COUNT {
+        let index = CIndex::from_flat_index(i);
+        let Some((sector, count)) = region.get_chunk_sectors(index) else {
+            continue;
+        };
+        let chunk = chunk_coords(index);
+        if sector < header_sectors {
+            problems.push(SectorProblem::InHeader {
+                chunk,
+                sector,
+                count,
+            });
+        } else if count == 0 {
+            problems.push(SectorProblem::Empty { chunk, sector });
+        } else if sector + count > file_sectors {
+            problems.push(SectorProblem::OutOfBounds {
+                chunk,
+                sector,
+                count,
+                file_sectors,
+            });
+        } else {
+            valid.push((sector, count, chunk));
+        }
+    }
+
+    // Sweep through the chunks in sector order, comparing each with whichever earlier chunk
+    // reaches furthest into the file
+    valid.sort();
+    let mut furthest: Option<(u32, (i32, i32))> = None;
+    for (sector, count, chunk) in valid {
+        let end = sector + count;
+        match furthest {
+            Some((furthest_end, other)) if sector < furthest_end => {
+                problems.push(SectorProblem::Overlap {
+                    chunk,
+                    sector,
+                    count,
+                    other,
+                });
+                if end > furthest_end {
+                    furthest = Some((end, chunk));
+                }
+            }
+            _ => furthest = Some((end, chunk)),
+        }
+    }
+    problems
+}
+
+/// The results of scanning a single region file.
+#[derive(Clone, Debug, Serialize)]
+pub struct RegionScan {
+    pub coords: (i32, i32),
+    pub path: PathBuf,
+    pub format: RegionFormat,
+    pub file_size: u64,
+    #[serde(flatten)]
+    pub stats: ChunkStats,
+    pub sector_problems: Vec<SectorProblem>,
+    /// Errors from opening the region, or reading and parsing its chunks.
+    pub errors: Vec<WorldError>,
+}
+
+impl RegionScan {
+    /// Read and parse every chunk in the region, recording any errors in `dim_info` as well as in
+    /// the result.
+    pub fn scan(dim_info: &DimensionInfo, region_info: &RegionInfo, settings: &Settings) -> Self {
+        let mut scan = RegionScan {
+            coords: (region_info.coords.x(), region_info.coords.z()),
+            path: region_info.path.clone(),
+            format: region_info.format,
+            file_size: 0,
+            stats: ChunkStats::default(),
+            sector_problems: Vec::new(),
+            errors: Vec::new(),
+        };
+        let mut record = |error: WorldError| {
+            dim_info.errors.record(error.clone());
+            scan.errors.push(error);
+        };
+
+        let storage = &dim_info.storage;
+        let (file_size, mut region) = match storage
+            .file_len(&region_info.path)
+            .and_then(|len| Ok((len, region_info.open(storage)?)))
+        {
+            Ok(opened) => opened,
+            Err(err) => {
+                record(WorldError::region(
+                    region_info.coords,
+                    region_info.path.clone(),
+                    &err,
+                ));
+                return scan;
+            }
+        };
+        let sector_problems = check_sectors(&region, file_size);
+
+        let mut stats = ChunkStats::default();
+        for i in 0..REGION_CHUNK_COUNT {
+            let index = CIndex::from_flat_index(i);
+            let coords = index.to_chunk_coords(region_info.coords);
+            let offset = region.get_chunk_offset(index);
+            let chunk_error = |stage, err: &anyhow::Error| {
+                WorldError::chunk(stage, coords, Some(region_info.path.clone()), offset, err)
+            };
+            let raw_chunk = match region.get_raw_chunk(index) {
+                Ok(Some(raw_chunk)) => raw_chunk,
+                Ok(None) => continue,
+                Err(err) => {
+                    record(chunk_error(ErrorStage::ChunkRead, &err));
+                    continue;
+                }
+            };
+            stats.chunk_count += 1;
+            *stats
+                .compression
+                .entry(raw_chunk.compression.to_string())
+                .or_default() += 1;
+            let summary = match fastnbt::from_bytes::<nbt::ChunkSummary>(&raw_chunk.data) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    record(chunk_error(ErrorStage::ChunkParse, &err.into()));
+                    continue;
+                }
+            };
+            *stats
+                .data_versions
+                .entry(summary.data_version.unwrap_or(0))
+                .or_default() += 1;
+            *stats.statuses.entry(chunk_status(&summary)).or_default() += 1;
+            if let Err(err) = raw_chunk.parse(dim_info.height_range, settings) {
+                record(chunk_error(ErrorStage::ChunkParse, &err));
+            }
+        }
+
+        scan.file_size = file_size;
+        scan.stats = stats;
+        scan.sector_problems = sector_problems;
+        scan
+    }
+
+    pub fn problem_count(&self) -> usize {
+        self.sector_problems.len() + self.errors.len()
+    }
+}
+
+/// Describe the generation status of a chunk.
+fn chunk_status(summary: &nbt::ChunkSummary) -> String {
+    let level = summary.level.as_ref();
+    if let Some(status) = summary
+        .status
+        .as_ref()
+        .or_else(|| level.and_then(|level| level.status.as_ref()))
+    {
+        status.to_string()
+    } else if let Some(populated) = level.and_then(|level| level.terrain_populated) {
+        format!("TerrainPopulated={populated}")
+    } else {
+        "none".to_owned()
+    }
+}
+
+/// The results of scanning every region in a dimension.
+#[derive(Clone, Debug, Serialize)]
+pub struct DimensionScan {
+    pub dimension: String,
+    pub path: PathBuf,
+    pub height_range: HeightRange,
+    #[serde(flatten)]
+    pub stats: ChunkStats,
+    pub regions: Vec<RegionScan>,
+    /// Region files that were skipped when the dimension was loaded.
+    pub errors: Vec<WorldError>,
+}
+
+impl DimensionScan {
+    pub fn scan(dimension: String, dim_info: &DimensionInfo, settings: &Settings) -> Self {
+        // Anything already recorded happened while finding the dimension's regions
+        let errors = dim_info.errors.errors();
+        let regions: Vec<_> = dim_info
+            .regions
+            .values()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|region_info| RegionScan::scan(dim_info, region_info, settings))
+            .collect();
+        let mut stats = ChunkStats::default();
+        for region in regions.iter() {
+            stats.merge(&region.stats);
+        }
+        DimensionScan {
+            dimension,
+            path: dim_info.path.clone(),
+            height_range: dim_info.height_range,
+            stats,
+            regions,
+            errors,
+        }
+    }
+
+    pub fn problem_count(&self) -> usize {
+        self.errors.len()
+            + self
+                .regions
+                .iter()
+                .map(RegionScan::problem_count)
+                .sum::<usize>()
+    }
+}
+
+/// The results of scanning a world: an inventory of its chunks, and anything that looks broken.
+#[derive(Clone, Debug, Serialize)]
+pub struct WorldScan {
+    pub path: PathBuf,
+    /// The world's name from `level.dat`, if it could be read.
+    pub name: Option<String>,
+    pub data_version: Option<i32>,
+    pub dimensions: Vec<DimensionScan>,
+}
+
+impl WorldScan {
+    /// Scan every dimension in `world_info`, or just those listed in `dimensions` if it isn't
+    /// empty.
+    pub fn scan(world_info: &WorldInfo, dimensions: &[&DimensionID], settings: &Settings) -> Self {
+        let ids = if dimensions.is_empty() {
+            world_info.dimension_ids()
+        } else {
+            dimensions.to_vec()
+        };
+        let dimensions = ids
+            .into_iter()
+            .filter_map(|id| {
+                let dim_info = world_info.get_dimension(id)?;
+                log::info!("scanning dimension {}", id);
+                Some(DimensionScan::scan(
+                    id.namespaced_id().to_owned(),
+                    dim_info,
+                    settings,
+                ))
+            })
+            .collect();
+        WorldScan {
+            path: world_info.path.clone(),
+            name: world_info.level.as_ref().map(|level| level.name.clone()),
+            data_version: world_info
+                .level
+                .as_ref()
+                .and_then(|level| level.data_version),
+            dimensions,
+        }
+    }
+
+    pub fn problem_count(&self) -> usize {
+        self.dimensions
+            .iter()
+            .map(DimensionScan::problem_count)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::world::RCoords;
+
+    #[test]
+    fn test_check_sectors() {
+        let mut header = vec![0u8; REGION_HEADER_SIZE];
+        let mut set_entry = |index: usize, sector: u32, count: u8| {
+            let entry = (sector << 8) | count as u32;
+            header[index * 4..index * 4 + 4].copy_from_slice(&entry.to_be_bytes());
+        };
+        set_entry(0, 2, 1); // fine
+        set_entry(1, 3, 2); // fine
+        set_entry(2, 4, 1); // overlaps chunk 1
+        set_entry(3, 1, 1); // inside header
+        set_entry(4, 5, 0); // empty
+        set_entry(5, 6, 2); // past end of file
+        let info = RegionInfo {
+            coords: RCoords((1, 0).into()),
+            path: PathBuf::from("r.1.0.mca"),
+            format: RegionFormat::Anvil,
+        };
+        let region = Region::from_stream(info, Cursor::new(header)).unwrap();
+        // A partial last sector is allowed
+        let file_size = 6 * SECTOR_SIZE as u64 + 100;
+        let problems = check_sectors(&region, file_size);
+        assert_eq!(
+            problems,
+            vec![
+                SectorProblem::InHeader {
+                    chunk: (35, 0),
+                    sector: 1,
+                    count: 1
+                },
+                SectorProblem::Empty {
+                    chunk: (36, 0),
+                    sector: 5
+                },
+                SectorProblem::OutOfBounds {
+                    chunk: (37, 0),
+                    sector: 6,
+                    count: 2,
+                    file_sectors: 7
+                },
+                SectorProblem::Overlap {
+                    chunk: (34, 0),
+                    sector: 4,
+                    count: 1,
+                    other: (33, 0)
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::settings::Settings;
+use crate::world::{
+    CCoords, CIndex, Chunk, ChunkBounds, CompressionMethod, ErrorReport, ErrorStage, HeightRange,
+    RCoords, REGION_CHUNK_COUNT, RawChunk, WorldError,
+};
+
+/// Somewhere to read a dimension's chunks from. [`DimensionInfo`](crate::world::DimensionInfo)
+/// reads region files from a [`WorldStorage`](crate::world::WorldStorage), and [`MemorySource`]
+/// holds chunks built in memory.
+pub trait WorldSource: Send + Sync {
+    /// Get the coordinates of every region that might have chunks.
+    fn region_coords(&self) -> Vec<RCoords>;
+
+    fn height_range(&self) -> HeightRange;
+
+    /// Errors encountered while reading the source, see [`Self::report_chunk_error()`].
+    fn errors(&self) -> &ErrorReport;
+
+    /// Get the raw chunk at `chunk_coords`, if such a chunk has data.
+    fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>>;
+
+    /// Read and parse the chunk at `chunk_coords`, if such a chunk has data. Errors come with the
+    /// stage they happened at. The default parses the result of [`Self::get_raw_chunk()`].
+    fn load_chunk(
+        &self,
+        chunk_coords: CCoords,
+        settings: &Settings,
+    ) -> Result<Option<Chunk>, (ErrorStage, anyhow::Error)> {
+        let raw_chunk = self
+            .get_raw_chunk(chunk_coords)
+            .map_err(|err| (ErrorStage::ChunkRead, err))?;
+        raw_chunk
+            .map(|raw_chunk| raw_chunk.parse(self.height_range(), settings))
+            .transpose()
+            .map_err(|err| (ErrorStage::ChunkParse, err))
+    }
+
+    /// Get the last modification times of all chunks in the region at `region_coords`, if such a
+    /// region exists. Chunks without data have a timestamp of 0.
+    fn get_region_timestamps(
+        &self,
+        region_coords: RCoords,
+    ) -> anyhow::Result<Option<[u32; REGION_CHUNK_COUNT]>>;
+
+    /// Record an error from reading or parsing the chunk at `coords` in [`Self::errors()`].
+    fn report_chunk_error(&self, coords: CCoords, stage: ErrorStage, err: &anyhow::Error) {
+        self.errors()
+            .record(WorldError::chunk(stage, coords, None, None, err));
+    }
+
+    /// Get region coordinates such that all existing regions have coordinates `X >= min.x()` and `Z >= min.z()`.
+    fn min_region_coords(&self) -> RCoords {
+        self.region_coords()
+            .into_iter()
+            .reduce(|acc, k| RCoords((min(acc.x(), k.x()), min(acc.z(), k.z())).into()))
+            .unwrap()
+    }
+
+    /// Get region coordinates such that all existing regions have coordinates `X < max.x()` and `Z < max.z()`.
+    fn max_region_coords(&self) -> RCoords {
+        RCoords((1, 1).into())
+            + self
+                .region_coords()
+                .into_iter()
+                .reduce(|acc, k| RCoords((max(acc.x(), k.x()), max(acc.z(), k.z())).into()))
+                .unwrap()
+    }
+}
+
+impl dyn WorldSource + '_ {
+    /// Read and parse every chunk in the source, in parallel across regions. The chunks of each
+    /// region are folded into a value starting from `init()`, and the values for all regions are
+    /// then combined with `merge`. Chunks that can't be read or parsed are recorded in
+    /// [`WorldSource::errors()`] and skipped.
+    pub fn fold_chunks<T: Send>(
+        &self,
+        settings: &Settings,
+        init: impl Fn() -> T + Sync + Send,
+        fold: impl Fn(&mut T, Chunk) + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+    ) -> T {
+        self.fold_chunks_in(&ChunkBounds::Unbounded, settings, init, fold, merge)
+    }
+
+    /// As [`Self::fold_chunks()`], but only reading the chunks within `bounds`.
+    pub fn fold_chunks_in<T: Send>(
+        &self,
+        bounds: &ChunkBounds,
+        settings: &Settings,
+        init: impl Fn() -> T + Sync + Send,
+        fold: impl Fn(&mut T, Chunk) + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+    ) -> T {
+        self.region_coords()
+            .into_par_iter()
+            .filter(|&region_coords| bounds.intersects_region(region_coords))
+            .map(|region_coords| {
+                let mut acc = init();
+                for i in 0..REGION_CHUNK_COUNT {
+                    let coords = CIndex::from_flat_index(i).to_chunk_coords(region_coords);
+                    if !bounds.contains(&coords) {
+                        continue;
+                    }
+                    match self.load_chunk(coords, settings) {
+                        Ok(Some(chunk)) => fold(&mut acc, chunk),
+                        Ok(None) => {}
+                        Err((stage, err)) => self.report_chunk_error(coords, stage, &err),
+                    }
+                }
+                acc
+            })
+            .reduce(&init, &merge)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MemoryChunk {
+    data: Vec<u8>,
+    timestamp: u32,
+}
+
+/// A [`WorldSource`] with chunks held in memory as uncompressed NBT, e.g. for test fixtures.
+#[derive(Debug)]
+pub struct MemorySource {
+    height_range: HeightRange,
+    chunks: BTreeMap<CCoords, MemoryChunk>,
+    errors: ErrorReport,
+}
+
+impl MemorySource {
+    pub fn new(height_range: HeightRange) -> Self {
+        Self {
+            height_range,
+            chunks: BTreeMap::new(),
+            errors: ErrorReport::default(),
+        }
+    }
+
+    /// Add a chunk from its uncompressed NBT data, replacing any existing chunk at `coords`.
+    pub fn insert_chunk(&mut self, coords: CCoords, data: Vec<u8>, timestamp: u32) {
+        self.chunks.insert(coords, MemoryChunk { data, timestamp });
+    }
+
+    /// Add a chunk by serializing `value` as NBT, e.g. a [`fastnbt::Value`] built with
+    /// [`fastnbt::nbt!`].
+    pub fn insert_chunk_nbt<T: Serialize>(
+        &mut self,
+        coords: CCoords,
+        value: &T,
+        timestamp: u32,
+    ) -> anyhow::Result<()> {
+        self.insert_chunk(coords, fastnbt::to_bytes(value)?, timestamp);
+        Ok(())
+    }
+
+    pub fn remove_chunk(&mut self, coords: CCoords) {
+        self.chunks.remove(&coords);
+    }
+}
+
+impl WorldSource for MemorySource {
+    fn region_coords(&self) -> Vec<RCoords> {
+        let mut region_coords: Vec<_> = self
+            .chunks
+            .keys()
+            .map(|coords| coords.to_region_coords().0)
+            .collect();
+        region_coords.sort();
+        region_coords.dedup();
+        region_coords
+    }
+
+    fn height_range(&self) -> HeightRange {
+        self.height_range
+    }
+
+    fn errors(&self) -> &ErrorReport {
+        &self.errors
+    }
+
+    fn get_raw_chunk(&self, chunk_coords: CCoords) -> anyhow::Result<Option<RawChunk>> {
+        let Some(chunk) = self.chunks.get(&chunk_coords) else {
+            return Ok(None);
+        };
+        Ok(Some(RawChunk {
+            index: chunk_coords.to_region_coords().1,
+            coords: chunk_coords,
+            data: chunk.data.clone(),
+            compression: CompressionMethod::Uncompressed,
+        }))
+    }
+
+    fn get_region_timestamps(
+        &self,
+        region_coords: RCoords,
+    ) -> anyhow::Result<Option<[u32; REGION_CHUNK_COUNT]>> {
+        let mut timestamps = [0; REGION_CHUNK_COUNT];
+        let mut found = false;
+        for (coords, chunk) in self.chunks.iter() {
+            let (chunk_region, index) = coords.to_region_coords();
+            if chunk_region == region_coords {
+                timestamps[index.to_flat_index()] = chunk.timestamp;
+                found = true;
+            }
+        }
+        Ok(found.then_some(timestamps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ChunkCache;
+    use crate::world::tests::test_settings;
+
+    fn test_chunk(x: i32, z: i32) -> fastnbt::Value {
+        fastnbt::nbt!({
+            "DataVersion": 3953_i32,
+            "xPos": x,
+            "zPos": z,
+            "yPos": -4_i32,
+            "Status": "minecraft:full",
+            "sections": [
+                {
+                    "Y": 0_i8,
+                    "block_states": {"palette": [{"Name": "minecraft:stone"}]},
+                    "biomes": {"palette": ["minecraft:plains"]},
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn test_memory_source() {
+        let settings = test_settings();
+        let mut source = MemorySource::new(HeightRange::OVERWORLD);
+        for (x, z) in [(0, 0), (31, 2), (32, -1)] {
+            source
+                .insert_chunk_nbt(CCoords((x, z).into()), &test_chunk(x, z), 100)
+                .unwrap();
+        }
+        source.insert_chunk(CCoords((1, 0).into()), b"garbage".to_vec(), 100);
+
+        assert_eq!(source.min_region_coords(), RCoords((0, -1).into()));
+        assert_eq!(source.max_region_coords(), RCoords((2, 1).into()));
+        let timestamps = source
+            .get_region_timestamps(RCoords((0, 0).into()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamps.iter().filter(|&&t| t == 100).count(), 3);
+        assert!(
+            source
+                .get_region_timestamps(RCoords((5, 5).into()))
+                .unwrap()
+                .is_none()
+        );
+
+        let source: &dyn WorldSource = &source;
+        let count = source.fold_chunks(&settings, || 0, |n, _| *n += 1, |a, b| a + b);
+        assert_eq!(count, 3);
+        assert_eq!(source.errors().len(), 1);
+
+        let mut cache = ChunkCache::new(source, &settings, Default::default(), 10);
+        let chunk = cache.get(CCoords((31, 2).into())).unwrap();
+        assert_eq!(chunk.sections.len(), 1);
+        assert!(cache.get(CCoords((2, 0).into())).is_none());
+    }
+}
@@ -0,0 +1,308 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::settings::Settings;
+use crate::util::csv_field;
+use crate::world::{CHUNK_SIZE, Chunk, Section, WorldSource};
+
+/// How block counts are grouped in [`BlockStats`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, derive_more::Display, clap::ValueEnum)]
+pub enum StatsGrouping {
+    /// One set of counts for the whole dimension.
+    #[default]
+    #[display("dimension")]
+    Dimension,
+    /// One set of counts for each region.
+    #[display("region")]
+    Region,
+    /// One set of counts for each chunk.
+    #[display("chunk")]
+    Chunk,
+    /// One set of counts for each Y level.
+    #[display("y")]
+    Y,
+}
+
+/// A set of counts in [`BlockStats`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsGroup {
+    Dimension,
+    Region(i32, i32),
+    Chunk(i32, i32),
+    Y(i32),
+}
+
+impl StatsGroup {
+    /// CSV column names for the group, matching [`Self::csv_values()`].
+    fn csv_columns(grouping: StatsGrouping) -> &'static [&'static str] {
+        match grouping {
+            StatsGrouping::Dimension => &[],
+            StatsGrouping::Region => &["region_x", "region_z"],
+            StatsGrouping::Chunk => &["chunk_x", "chunk_z"],
+            StatsGrouping::Y => &["y"],
+        }
+    }
+
+    fn csv_values(self) -> Vec<i32> {
+        match self {
+            StatsGroup::Dimension => vec![],
+            StatsGroup::Region(x, z) | StatsGroup::Chunk(x, z) => vec![x, z],
+            StatsGroup::Y(y) => vec![y],
+        }
+    }
+}
+
+/// What to count in [`BlockStats`].
+#[derive(Clone, Debug, Default)]
+pub struct StatsOptions {
+    pub grouping: StatsGrouping,
+    /// Also count the number of blocks in each biome.
+    pub biomes: bool,
+    /// Count blocks by name, ignoring block state properties.
+    pub names_only: bool,
+    /// Only count these blocks, by name with or without the namespace. Counts everything if empty.
+    pub blocks: Vec<String>,
+}
+
+impl StatsOptions {
+    fn includes_block(&self, name: &str) -> bool {
+        self.blocks.is_empty()
+            || self.blocks.iter().any(|block| {
+                block == name
+                    || name
+                        .split_once(':')
+                        .is_some_and(|(_, short)| block == short)
+            })
+    }
+}
+
+/// Block and biome counts.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StatsCounts {
+    /// Blocks by block state, e.g. `minecraft:hopper{enabled=true;facing=down}`. Unless the
+    /// chunks were read with [`Settings::keep_block_properties`], only the properties kept by the
+    /// block's asset rule are included.
+    pub blocks: BTreeMap<String, u64>,
+    /// Blocks by biome.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub biomes: BTreeMap<String, u64>,
+}
+
+impl StatsCounts {
+    fn merge(&mut self, other: StatsCounts) {
+        for (name, count) in other.blocks {
+            *self.blocks.entry(name).or_default() += count;
+        }
+        for (name, count) in other.biomes {
+            *self.biomes.entry(name).or_default() += count;
+        }
+    }
+}
+
+/// Counts of block states, and optionally biomes, across a dimension.
+#[derive(Debug, Default)]
+pub struct BlockStats {
+    pub options: StatsOptions,
+    pub groups: BTreeMap<StatsGroup, StatsCounts>,
+}
+
+#[derive(Serialize)]
+struct JsonGroup<'a> {
+    group: StatsGroup,
+    #[serde(flatten)]
+    counts: &'a StatsCounts,
+}
+
+impl BlockStats {
+    pub fn new(options: StatsOptions) -> Self {
+        BlockStats {
+            options,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Count the blocks in every chunk of `source`. Chunks that can't be read are recorded in
+    /// the source's [`ErrorReport`](crate::world::ErrorReport) and skipped.
+    pub fn collect(source: &dyn WorldSource, settings: &Settings, options: StatsOptions) -> Self {
+        source.fold_chunks(
+            settings,
+            || BlockStats::new(options.clone()),
+            |stats, chunk| stats.add_chunk(&chunk),
+            |mut a, b| {
+                a.merge(b);
+                a
+            },
+        )
+    }
+
+    pub fn add_chunk(&mut self, chunk: &Chunk) {
+        let group = match self.options.grouping {
+            StatsGrouping::Dimension | StatsGrouping::Y => StatsGroup::Dimension,
+            StatsGrouping::Region => {
+                let (region_coords, _) = chunk.coords.to_region_coords();
+                StatsGroup::Region(region_coords.x(), region_coords.z())
+            }
+            StatsGrouping::Chunk => StatsGroup::Chunk(chunk.coords.x(), chunk.coords.z()),
+        };
+        for section in chunk.sections.iter() {
+            if self.options.grouping == StatsGrouping::Y {
+                // Count each layer of the section separately
+                for y in 0..CHUNK_SIZE as usize {
+                    let group = StatsGroup::Y(section.base.y() + y as i32);
+                    self.add_blocks(group, section, y * 256..(y + 1) * 256);
+                }
+            } else {
+                self.add_blocks(group, section, 0..section.block_data.len());
+            }
+        }
+    }
+
+    /// Count the blocks of `section` in the flat index range `range`.
+    fn add_blocks(&mut self, group: StatsGroup, section: &Section, range: std::ops::Range<usize>) {
+        let mut state_counts = vec![0u64; section.block_palette.len()];
+        let mut biome_counts = vec![0u64; section.biome_palette.len()];
+        for data in section.block_data[range].iter() {
+            state_counts[data.state_index() as usize] += 1;
+            biome_counts[data.biome_index() as usize] += 1;
+        }
+
+        let mut counts = StatsCounts::default();
+        for ((state, _), count) in section.block_palette.iter().zip(state_counts) {
+            if count == 0 || !self.options.includes_block(&state.name) {
+                continue;
+            }
+            let name = if self.options.names_only {
+                state.name.to_string()
+            } else {
+                state.to_string()
+            };
+            *counts.blocks.entry(name).or_default() += count;
+        }
+        if self.options.biomes {
+            for (biome, count) in section.biome_palette.iter().zip(biome_counts) {
+                if count > 0 {
+                    *counts.biomes.entry(biome.to_string()).or_default() += count;
+                }
+            }
+        }
+        if !counts.blocks.is_empty() || !counts.biomes.is_empty() {
+            self.groups.entry(group).or_default().merge(counts);
+        }
+    }
+
+    pub fn merge(&mut self, other: BlockStats) {
+        for (group, counts) in other.groups {
+            self.groups.entry(group).or_default().merge(counts);
+        }
+    }
+
+    /// Write the counts as CSV, with one row per group and block state (or biome).
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let columns = StatsGroup::csv_columns(self.options.grouping);
+        let header = columns.iter().chain(&["kind", "name", "count"]);
+        writeln!(writer, "{}", header.cloned().collect::<Vec<_>>().join(","))?;
+        for (group, counts) in self.groups.iter() {
+            let mut prefix = String::new();
+            for value in group.csv_values() {
+                prefix.push_str(&value.to_string());
+                prefix.push(',');
+            }
+            for (kind, map) in [("block", &counts.blocks), ("biome", &counts.biomes)] {
+                for (name, count) in map.iter() {
+                    writeln!(writer, "{prefix}{kind},{},{count}", csv_field(name))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the counts as a JSON array of groups.
+    pub fn write_json<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        let groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(&group, counts)| JsonGroup { group, counts })
+            .collect();
+        serde_json::to_writer_pretty(writer, &groups)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::util::intern_str;
+    use crate::world::tests::test_settings;
+    use crate::world::{
+        BCoords, BlockData, BlockState, CCoords, Heightmaps, SECTION_BLOCK_COUNT,
+        convert_block_state,
+    };
+
+    fn test_chunk() -> Chunk {
+        let mut settings = test_settings();
+        settings.keep_block_properties = true;
+        let block_palette = [
+            BlockState::new(intern_str("minecraft:stone")),
+            BlockState::new(intern_str("minecraft:hopper"))
+                .with_property("facing", "down")
+                .with_property("enabled", "true"),
+        ]
+        .into_iter()
+        .map(|state| convert_block_state(state, &settings))
+        .collect();
+        let mut block_data = [BlockData::new(); SECTION_BLOCK_COUNT];
+        // Two hoppers on the bottom layer, and one on the top layer
+        for i in [0, 1, 15 * 256] {
+            block_data[i].set_state_index(1);
+        }
+        Chunk {
+            coords: CCoords((-1, 40).into()),
+            sections: vec![Section {
+                base: BCoords((-16, 640, -32).into()),
+                block_data,
+                block_palette,
+                biome_palette: vec![intern_str("minecraft:desert")],
+                block_entities: HashMap::new(),
+            }],
+            fully_generated: true,
+            status: None,
+            missing_light: false,
+            heightmaps: Heightmaps::default(),
+        }
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let mut stats = BlockStats::new(StatsOptions {
+            grouping: StatsGrouping::Region,
+            biomes: true,
+            ..Default::default()
+        });
+        stats.add_chunk(&test_chunk());
+        let counts = &stats.groups[&StatsGroup::Region(-1, 1)];
+        assert_eq!(counts.blocks["minecraft:stone"], 4093);
+        assert_eq!(
+            counts.blocks["minecraft:hopper{enabled=true;facing=down}"],
+            3
+        );
+        assert_eq!(counts.biomes["minecraft:desert"], 4096);
+
+        let mut stats = BlockStats::new(StatsOptions {
+            grouping: StatsGrouping::Y,
+            names_only: true,
+            blocks: vec!["hopper".to_owned()],
+            ..Default::default()
+        });
+        stats.add_chunk(&test_chunk());
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "y,kind,name,count\n-32,block,minecraft:hopper,2\n-17,block,minecraft:hopper,1\n"
+        );
+    }
+}
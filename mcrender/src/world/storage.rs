@@ -0,0 +1,503 @@
+/*
+World storage notes:
+
+- Paths given to a `WorldStorage` are full paths. For an archive they are the archive's own path
+  joined with the path inside the archive, e.g. `backups/world.zip/world/region/r.0.0.mca`, so
+  they can be shown in logs and error reports as-is.
+- Archives often contain the world directory itself rather than its contents, so the world root
+  is the shallowest directory in the archive with a `level.dat` or a `region` directory.
+- Zip entries and plain tar entries can be read in any order. A gzipped tar has to be
+  decompressed from the start to reach an entry, and region files are read in no particular
+  order, so it's decompressed once into a temporary file when it's opened, and entries are read
+  from that like a plain tar. That needs as much disk space as the uncompressed world, so it's
+  better to extract big worlds first.
+- Tar entries are read straight from the (uncompressed) archive file. Zip entries are usually
+  compressed, and region files need to be seekable, so they're read fully into memory instead.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use flate2::read::GzDecoder;
+use parking_lot::Mutex;
+
+/// A readable and seekable stream, e.g. a file.
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Access to the files of a world, wherever they are stored.
+pub trait WorldStorage: Debug + Send + Sync {
+    fn is_dir(&self, path: &Path) -> bool;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// List the paths of the files and directories directly inside the directory at `path`.
+    fn read_dir(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>>;
+
+    /// Get the size of the file at `path`, in bytes.
+    fn file_len(&self, path: &Path) -> anyhow::Result<u64>;
+
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn ReadSeek>>;
+
+    /// Whether [`Self::open()`] reads the whole file into memory, rather than reading it as it's
+    /// used, so fewer files should be kept open at once.
+    fn reads_into_memory(&self) -> bool {
+        false
+    }
+}
+
+/// Open the storage for a world at `path`, which may be a directory, a `.zip` archive (including
+/// Bedrock's `.mcworld`) or a `.tar`, `.tar.gz` or `.tgz` archive. Returns the storage and the path
+/// of the world's root directory within it.
+pub fn open_storage(path: &Path) -> anyhow::Result<(Arc<dyn WorldStorage>, PathBuf)> {
+    if path.is_dir() {
+        return Ok((Arc::new(FileSystem), path.to_owned()));
+    }
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let (storage, archive_root): (Arc<dyn WorldStorage>, _) =
+        if file_name.ends_with(".zip") || file_name.ends_with(".mcworld") {
+            let storage = ZipStorage::open(path)?;
+            let root = storage.index.world_root();
+            (Arc::new(storage), root)
+        } else if file_name.ends_with(".tar") {
+            let storage = TarStorage::open(path, false)?;
+            let root = storage.index.world_root();
+            (Arc::new(storage), root)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            let storage = TarStorage::open(path, true)?;
+            let root = storage.index.world_root();
+            (Arc::new(storage), root)
+        } else {
+            return Err(anyhow!("not a world directory or archive: {:?}", path));
+        };
+    let root = archive_root.ok_or_else(|| anyhow!("no world found in archive {:?}", path))?;
+    log::debug!("using world at {:?} in archive {:?}", root, path);
+    if root.as_os_str().is_empty() {
+        Ok((storage, path.to_owned()))
+    } else {
+        Ok((storage, path.join(root)))
+    }
+}
+
+/// Files read directly from the file system.
+#[derive(Debug, Default)]
+pub struct FileSystem;
+
+impl WorldStorage for FileSystem {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read_dir(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            paths.push(entry?.path());
+        }
+        Ok(paths)
+    }
+
+    fn file_len(&self, path: &Path) -> anyhow::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// The files and directories in an archive, with an entry of type `E` for each file.
+#[derive(Debug)]
+struct ArchiveIndex<E> {
+    /// The path of the archive itself.
+    path: PathBuf,
+    files: BTreeMap<PathBuf, E>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl<E> ArchiveIndex<E> {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::from([PathBuf::new()]),
+        }
+    }
+
+    /// Add a file, and all of the directories that contain it.
+    fn insert_file(&mut self, inner_path: PathBuf, entry: E) {
+        self.insert_dir(inner_path.parent().unwrap_or(Path::new("")));
+        self.files.insert(inner_path, entry);
+    }
+
+    fn insert_dir(&mut self, inner_path: &Path) {
+        for ancestor in inner_path.ancestors() {
+            if !self.dirs.insert(ancestor.to_owned()) {
+                break;
+            }
+        }
+    }
+
+    /// Get the path within the archive from a full path.
+    fn inner_path<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.path).ok()
+    }
+
+    fn get(&self, path: &Path) -> anyhow::Result<&E> {
+        self.inner_path(path)
+            .and_then(|inner_path| self.files.get(inner_path))
+            .ok_or_else(|| anyhow!("no such file in archive: {:?}", path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner_path(path)
+            .is_some_and(|inner_path| self.dirs.contains(inner_path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.inner_path(path)
+            .is_some_and(|inner_path| self.files.contains_key(inner_path))
+    }
+
+    fn read_dir(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let Some(dir) = self.inner_path(path).filter(|dir| self.dirs.contains(*dir)) else {
+            return Err(anyhow!("no such directory in archive: {:?}", path));
+        };
+        let children = self.dirs.iter().chain(self.files.keys());
+        Ok(children
+            .filter(|child| !child.as_os_str().is_empty() && child.parent() == Some(dir))
+            .map(|child| self.path.join(child))
+            .collect())
+    }
+
+    /// Find the shallowest directory that looks like a world.
+    fn world_root(&self) -> Option<PathBuf> {
+        let level_dats = self
+            .files
+            .keys()
+            .filter(|path| path.file_name() == Some("level.dat".as_ref()));
+        let region_dirs = self
+            .dirs
+            .iter()
+            .filter(|path| path.file_name() == Some("region".as_ref()));
+        level_dats
+            .chain(region_dirs)
+            .filter_map(|path| path.parent())
+            .min_by_key(|path| path.components().count())
+            .map(Path::to_owned)
+    }
+}
+
+/// Normalize a path from an archive, returning `None` if it isn't a safe relative path.
+fn archive_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// The most memory to reserve up front for a file read from an archive. The size comes from the
+/// archive itself, so it isn't trusted with more than this.
+const MAX_INITIAL_CAPACITY: u64 = 16 * 1024 * 1024;
+
+/// Files read from a zip archive.
+#[derive(derive_more::Debug)]
+#[debug("ZipStorage({:?})", index.path)]
+pub struct ZipStorage {
+    index: ArchiveIndex<usize>,
+    archive: Mutex<zip::ZipArchive<BufReader<File>>>,
+}
+
+impl ZipStorage {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let mut index = ArchiveIndex::new(path);
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            let Some(inner_path) = file.enclosed_name().as_deref().and_then(archive_entry_path)
+            else {
+                log::debug!("skipping zip entry with unsafe path: {:?}", file.name());
+                continue;
+            };
+            if file.is_dir() {
+                index.insert_dir(&inner_path);
+            } else {
+                index.insert_file(inner_path, i);
+            }
+        }
+        Ok(Self {
+            index,
+            archive: Mutex::new(archive),
+        })
+    }
+}
+
+impl WorldStorage for ZipStorage {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.index.is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.index.is_file(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        self.index.read_dir(path)
+    }
+
+    fn file_len(&self, path: &Path) -> anyhow::Result<u64> {
+        let i = *self.index.get(path)?;
+        Ok(self.archive.lock().by_index(i)?.size())
+    }
+
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn ReadSeek>> {
+        let i = *self.index.get(path)?;
+        let mut archive = self.archive.lock();
+        let mut file = archive.by_index(i)?;
+        let mut data = Vec::with_capacity(file.size().min(MAX_INITIAL_CAPACITY) as usize);
+        file.read_to_end(&mut data)?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn reads_into_memory(&self) -> bool {
+        true
+    }
+}
+
+/// Where a file's data is in the (uncompressed) tar stream.
+#[derive(Clone, Copy, Debug)]
+struct TarEntry {
+    offset: u64,
+    len: u64,
+}
+
+/// Files read from a tar archive, optionally gzipped.
+#[derive(derive_more::Debug)]
+#[debug("TarStorage({:?})", index.path)]
+pub struct TarStorage {
+    index: ArchiveIndex<TarEntry>,
+    /// The uncompressed archive: either the archive itself, or a temporary file holding the
+    /// contents of a gzipped archive.
+    file: Arc<Mutex<File>>,
+}
+
+impl TarStorage {
+    pub fn open(path: &Path, gzip: bool) -> anyhow::Result<Self> {
+        let mut index = ArchiveIndex::new(path);
+        let mut file = File::open(path)?;
+        if gzip {
+            log::warn!(
+                "decompressing {:?} to a temporary file, which can be slow and use a lot of disk \
+                 space; extract it and use the world directory instead to avoid this",
+                path
+            );
+            let mut temp_file = tempfile::tempfile()?;
+            io::copy(&mut GzDecoder::new(BufReader::new(file)), &mut temp_file)?;
+            temp_file.seek(SeekFrom::Start(0))?;
+            file = temp_file;
+        }
+        let mut archive = tar::Archive::new(BufReader::new(&file));
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let Some(inner_path) = archive_entry_path(&entry.path()?) else {
+                log::debug!("skipping tar entry with unsafe path: {:?}", entry.path());
+                continue;
+            };
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => index.insert_dir(&inner_path),
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let entry_info = TarEntry {
+                        offset: entry.raw_file_position(),
+                        len: entry.size(),
+                    };
+                    index.insert_file(inner_path, entry_info);
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            index,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+}
+
+/// Reads one entry of a tar archive straight from the archive file, which is shared with the
+/// other open entries.
+struct TarEntryReader {
+    file: Arc<Mutex<File>>,
+    entry: TarEntry,
+    /// The position within the entry.
+    pos: u64,
+}
+
+impl Read for TarEntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self
+            .entry
+            .len
+            .saturating_sub(self.pos)
+            .min(buf.len() as u64) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(self.entry.offset + self.pos))?;
+        let read_len = file.read(&mut buf[..len])?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+impl Seek for TarEntryReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.entry.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl WorldStorage for TarStorage {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.index.is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.index.is_file(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        self.index.read_dir(path)
+    }
+
+    fn file_len(&self, path: &Path) -> anyhow::Result<u64> {
+        Ok(self.index.get(path)?.len)
+    }
+
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn ReadSeek>> {
+        let entry = *self.index.get(path)?;
+        Ok(Box::new(BufReader::new(TarEntryReader {
+            file: self.file.clone(),
+            entry,
+            pos: 0,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_index() {
+        let mut index = ArchiveIndex::new(Path::new("backups/world.zip"));
+        index.insert_file(PathBuf::from("world/level.dat"), 0);
+        index.insert_file(PathBuf::from("world/region/r.0.0.mca"), 1);
+        index.insert_file(PathBuf::from("world/DIM-1/region/r.0.0.mca"), 2);
+        index.insert_dir(Path::new("world/data"));
+
+        assert_eq!(index.world_root(), Some(PathBuf::from("world")));
+        let world = Path::new("backups/world.zip/world");
+        assert!(index.is_dir(world));
+        assert!(index.is_dir(&world.join("DIM-1/region")));
+        assert!(index.is_file(&world.join("region/r.0.0.mca")));
+        assert!(!index.is_file(&world.join("region")));
+        assert_eq!(
+            *index.get(&world.join("DIM-1/region/r.0.0.mca")).unwrap(),
+            2
+        );
+
+        let mut children = index.read_dir(world).unwrap();
+        children.sort();
+        assert_eq!(
+            children,
+            vec![
+                world.join("DIM-1"),
+                world.join("data"),
+                world.join("level.dat"),
+                world.join("region"),
+            ]
+        );
+        assert!(index.read_dir(&world.join("level.dat")).is_err());
+    }
+
+    #[test]
+    fn test_archive_entry_path() {
+        assert_eq!(
+            archive_entry_path(Path::new("./world/region/")),
+            Some(PathBuf::from("world/region"))
+        );
+        assert_eq!(archive_entry_path(Path::new("../world")), None);
+        assert_eq!(archive_entry_path(Path::new("/world")), None);
+    }
+
+    #[test]
+    fn test_tar_gz_storage() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("world.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, data) in [
+            ("world/level.dat", &b"level"[..]),
+            ("world/region/r.0.0.mca", b"region 0"),
+            ("world/region/r.1.0.mca", b"region 1"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let (storage, root) = open_storage(&path).unwrap();
+        assert_eq!(root, path.join("world"));
+        let read = |name: &str| {
+            let mut data = Vec::new();
+            let mut file = storage.open(&root.join(name)).unwrap();
+            file.read_to_end(&mut data).unwrap();
+            data
+        };
+        // Entries can be read in any order, and at the same time
+        let mut region = storage.open(&root.join("region/r.1.0.mca")).unwrap();
+        assert_eq!(read("level.dat"), b"level");
+        assert_eq!(read("region/r.0.0.mca"), b"region 0");
+        let mut data = Vec::new();
+        region.seek(SeekFrom::Start(7)).unwrap();
+        region.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"1");
+        assert_eq!(region.seek(SeekFrom::End(-8)).unwrap(), 0);
+        assert!(region.seek(SeekFrom::Current(-1)).is_err());
+        assert_eq!(read("region/r.1.0.mca"), b"region 1");
+        assert_eq!(storage.file_len(&root.join("level.dat")).unwrap(), 5);
+        assert!(!storage.reads_into_memory());
+    }
+}
@@ -0,0 +1,601 @@
+/*
+Region writing notes:
+
+- Chunks are written in the 1.18+ format, with a section for every section in the chunk's height
+  range (like Minecraft does) so that the height range can be detected when the world is read.
+- Chunk data starts at sector 2, straight after the header, and each chunk is padded to a whole
+  number of sectors. The sector count in the header is a single byte, so a chunk can use at most
+  255 sectors. Minecraft moves bigger chunks to an external `c.X.Z.mcc` file, which isn't
+  supported here.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use arcstr::ArcStr;
+use byteorder::{BigEndian, WriteBytesExt};
+use serde::Serialize;
+
+use crate::util::intern_str;
+use crate::world::heightmap::AIR_BLOCKS;
+use crate::world::{
+    BIndex, BlockState, CCoords, CHUNK_SIZE, CIndex, CompressionMethod, HeightRange, LightLevel,
+    RCoords, REGION_CHUNK_COUNT, REGION_HEADER_SIZE, SECTION_BIOME_COUNT, SECTION_BLOCK_COUNT,
+    SECTOR_SIZE, block_index_bits,
+};
+
+/// The data version written to chunks by default (1.21).
+pub const DEFAULT_DATA_VERSION: i32 = 3953;
+
+const NIBBLE_ARRAY_LEN: usize = SECTION_BLOCK_COUNT / 2;
+
+/// Blocks, biomes and light for one section of a [`ChunkBuilder`].
+#[derive(Clone, Debug)]
+pub struct SectionBuilder {
+    block_palette: Vec<BlockState>,
+    blocks: Box<[u16; SECTION_BLOCK_COUNT]>,
+    biome_palette: Vec<ArcStr>,
+    biomes: [u8; SECTION_BIOME_COUNT],
+    block_light: Option<Box<[u8; NIBBLE_ARRAY_LEN]>>,
+    sky_light: Option<Box<[u8; NIBBLE_ARRAY_LEN]>>,
+}
+
+impl SectionBuilder {
+    /// Create a section filled with `state`, all in `biome`, without any light data.
+    pub fn new(state: BlockState, biome: ArcStr) -> Self {
+        Self {
+            block_palette: vec![state],
+            blocks: Box::new([0; SECTION_BLOCK_COUNT]),
+            biome_palette: vec![biome],
+            biomes: [0; SECTION_BIOME_COUNT],
+            block_light: None,
+            sky_light: None,
+        }
+    }
+
+    pub fn get_block(&self, index: BIndex) -> &BlockState {
+        &self.block_palette[self.blocks[index.to_flat_index()] as usize]
+    }
+
+    pub fn set_block(&mut self, index: BIndex, state: BlockState) {
+        self.blocks[index.to_flat_index()] = palette_index(&mut self.block_palette, state) as u16;
+    }
+
+    /// Set the biome of the 4x4x4 cell containing `index`.
+    pub fn set_biome(&mut self, index: BIndex, biome: ArcStr) {
+        self.biomes[index.to_biome_index()] = palette_index(&mut self.biome_palette, biome) as u8;
+    }
+
+    /// Set the light level of the block at `index`. Once any light is set, the rest of the
+    /// section has no block light and full sky light.
+    pub fn set_light(&mut self, index: BIndex, light: LightLevel) {
+        let block_light = self
+            .block_light
+            .get_or_insert_with(|| Box::new([0; NIBBLE_ARRAY_LEN]));
+        set_nibble(block_light, index.to_flat_index(), light.block());
+        let sky_light = self
+            .sky_light
+            .get_or_insert_with(|| Box::new([0xFF; NIBBLE_ARRAY_LEN]));
+        set_nibble(sky_light, index.to_flat_index(), light.sky());
+    }
+
+    fn to_nbt(&self, y: i8) -> SectionNbt<'_> {
+        let block_bits = block_index_bits(self.block_palette.len());
+        let biome_bits = u64::BITS - (self.biome_palette.len() as u64 - 1).leading_zeros();
+        SectionNbt {
+            y,
+            block_states: PalettedNbt {
+                palette: self
+                    .block_palette
+                    .iter()
+                    .map(|state| BlockStateNbt {
+                        name: &state.name,
+                        properties: state.properties.iter().collect(),
+                    })
+                    .collect(),
+                data: (self.block_palette.len() > 1).then(|| {
+                    fastnbt::LongArray::new(pack_bits(
+                        self.blocks.iter().map(|&v| v as u64),
+                        block_bits,
+                    ))
+                }),
+            },
+            biomes: PalettedNbt {
+                palette: self
+                    .biome_palette
+                    .iter()
+                    .map(|biome| biome.as_str())
+                    .collect(),
+                data: (self.biome_palette.len() > 1).then(|| {
+                    fastnbt::LongArray::new(pack_bits(
+                        self.biomes.iter().map(|&v| v as u64),
+                        biome_bits,
+                    ))
+                }),
+            },
+            block_light: self.block_light.as_deref().map(to_byte_array),
+            sky_light: self.sky_light.as_deref().map(to_byte_array),
+        }
+    }
+}
+
+/// Get the index of `value` in `palette`, adding it if it isn't there already.
+fn palette_index<T: PartialEq>(palette: &mut Vec<T>, value: T) -> usize {
+    match palette.iter().position(|existing| *existing == value) {
+        Some(index) => index,
+        None => {
+            palette.push(value);
+            palette.len() - 1
+        }
+    }
+}
+
+fn set_nibble(data: &mut [u8; NIBBLE_ARRAY_LEN], index: usize, value: u8) {
+    let byte = &mut data[index / 2];
+    if index.is_multiple_of(2) {
+        *byte = (*byte & 0xF0) | (value & 0xF);
+    } else {
+        *byte = (*byte & 0x0F) | (value << 4);
+    }
+}
+
+fn to_byte_array(data: &[u8; NIBBLE_ARRAY_LEN]) -> fastnbt::ByteArray {
+    fastnbt::ByteArray::new(data.iter().map(|&v| v as i8).collect())
+}
+
+/// Pack `values` into a long array, lowest bits first, without values spanning across longs. This
+/// is the inverse of [`PackedBits`](super::PackedBits) for 1.16+ chunks.
+pub(super) fn pack_bits(values: impl ExactSizeIterator<Item = u64>, bits: u32) -> Vec<i64> {
+    let per_long = (u64::BITS / bits) as usize;
+    let mut data = vec![0i64; values.len().div_ceil(per_long)];
+    for (i, v) in values.enumerate() {
+        let shift = (i % per_long) as u32 * bits;
+        data[i / per_long] |= (v << shift) as i64;
+    }
+    data
+}
+
+/// A chunk built in memory, to be encoded as NBT and written to a region file with
+/// [`RegionWriter`]. For building small worlds for tests and bug reports.
+#[derive(Clone, Debug)]
+pub struct ChunkBuilder {
+    coords: CCoords,
+    height_range: HeightRange,
+    data_version: i32,
+    status: String,
+    /// One for each section in the height range, from the bottom up.
+    sections: Vec<SectionBuilder>,
+    /// Block entity compounds, including their ID and position.
+    block_entities: Vec<fastnbt::Value>,
+}
+
+impl ChunkBuilder {
+    /// Create a fully generated chunk that is all air, in the plains biome.
+    pub fn new(coords: CCoords, height_range: HeightRange) -> Self {
+        let section = SectionBuilder::new(
+            BlockState::new(intern_str("minecraft:air")),
+            intern_str("minecraft:plains"),
+        );
+        Self {
+            coords,
+            height_range,
+            data_version: DEFAULT_DATA_VERSION,
+            status: "minecraft:full".to_owned(),
+            sections: vec![section; height_range.section_count()],
+            block_entities: Vec::new(),
+        }
+    }
+
+    pub fn with_data_version(mut self, data_version: i32) -> Self {
+        self.data_version = data_version;
+        self
+    }
+
+    /// Set the chunk's generation status, e.g. `minecraft:features`.
+    pub fn with_status(mut self, status: &str) -> Self {
+        self.status = status.to_owned();
+        self
+    }
+
+    pub fn coords(&self) -> CCoords {
+        self.coords
+    }
+
+    /// Get the section containing block Y coordinate `y`.
+    ///
+    /// Panics if `y` is outside the chunk's height range.
+    pub fn section_mut(&mut self, y: i32) -> &mut SectionBuilder {
+        assert!(
+            (self.height_range.min_y..self.height_range.max_y()).contains(&y),
+            "Y coordinate {y} outside of height range"
+        );
+        let index = (y - self.height_range.min_y) / CHUNK_SIZE as i32;
+        &mut self.sections[index as usize]
+    }
+
+    /// Get the section containing the block at chunk-relative `x` and `z` and absolute `y`, and
+    /// the block's index within that section.
+    fn locate(&mut self, x: u32, y: i32, z: u32) -> (&mut SectionBuilder, BIndex) {
+        assert!(
+            x < CHUNK_SIZE && z < CHUNK_SIZE,
+            "block ({x}, {z}) outside of chunk"
+        );
+        let section_y = y.rem_euclid(CHUNK_SIZE as i32) as u32;
+        (self.section_mut(y), BIndex((x, z, section_y).into()))
+    }
+
+    /// Set the block at chunk-relative `x` and `z` and absolute `y`.
+    pub fn set_block(&mut self, x: u32, y: i32, z: u32, state: BlockState) {
+        let (section, index) = self.locate(x, y, z);
+        section.set_block(index, state);
+    }
+
+    /// Fill whole layers of the chunk with `state`.
+    pub fn fill_layers(&mut self, y_range: Range<i32>, state: BlockState) {
+        for y in y_range {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    self.set_block(x, y, z, state.clone());
+                }
+            }
+        }
+    }
+
+    /// Set the biome of the 4x4x4 cell containing the block at chunk-relative `x` and `z` and
+    /// absolute `y`.
+    pub fn set_biome(&mut self, x: u32, y: i32, z: u32, biome: ArcStr) {
+        let (section, index) = self.locate(x, y, z);
+        section.set_biome(index, biome);
+    }
+
+    /// Set the light level of the block at chunk-relative `x` and `z` and absolute `y`. See
+    /// [`SectionBuilder::set_light()`].
+    pub fn set_light(&mut self, x: u32, y: i32, z: u32, light: LightLevel) {
+        let (section, index) = self.locate(x, y, z);
+        section.set_light(index, light);
+    }
+
+    /// Add a block entity with `id` and `data` for the block at chunk-relative `x` and `z` and
+    /// absolute `y`.
+    pub fn add_block_entity(
+        &mut self,
+        x: u32,
+        y: i32,
+        z: u32,
+        id: &str,
+        mut data: HashMap<String, fastnbt::Value>,
+    ) {
+        self.locate(x, y, z);
+        let base_x = self.coords.x() * CHUNK_SIZE as i32;
+        let base_z = self.coords.z() * CHUNK_SIZE as i32;
+        data.insert("id".to_owned(), fastnbt::Value::String(id.to_owned()));
+        data.insert("x".to_owned(), fastnbt::Value::Int(base_x + x as i32));
+        data.insert("y".to_owned(), fastnbt::Value::Int(y));
+        data.insert("z".to_owned(), fastnbt::Value::Int(base_z + z as i32));
+        self.block_entities.push(fastnbt::Value::Compound(data));
+    }
+
+    /// Get the `WORLD_SURFACE` heightmap values: the height above the bottom of the world of the
+    /// top of the highest non-air block in each column.
+    fn world_surface(&self) -> Vec<u64> {
+        let mut heights = vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize];
+        for (i, height) in heights.iter_mut().enumerate() {
+            let (x, z) = (i as u32 % CHUNK_SIZE, i as u32 / CHUNK_SIZE);
+            let top = (0..self.height_range.height).rev().find(|&y| {
+                let section = &self.sections[(y / CHUNK_SIZE) as usize];
+                let state = section.get_block(BIndex((x, z, y % CHUNK_SIZE).into()));
+                !AIR_BLOCKS.contains(&state.name.as_str())
+            });
+            *height = top.map_or(0, |y| y as u64 + 1);
+        }
+        heights
+    }
+
+    /// Encode the chunk as uncompressed NBT.
+    pub fn to_nbt(&self) -> anyhow::Result<Vec<u8>> {
+        let min_section = self.height_range.min_y.div_euclid(CHUNK_SIZE as i32);
+        let chunk_nbt = ChunkNbt {
+            data_version: self.data_version,
+            x_pos: self.coords.x(),
+            z_pos: self.coords.z(),
+            y_pos: min_section,
+            status: &self.status,
+            is_light_on: self
+                .sections
+                .iter()
+                .any(|section| section.block_light.is_some()) as i8,
+            sections: self
+                .sections
+                .iter()
+                .enumerate()
+                .map(|(i, section)| section.to_nbt((min_section + i as i32) as i8))
+                .collect(),
+            heightmaps: HeightmapsNbt {
+                world_surface: fastnbt::LongArray::new(pack_bits(
+                    self.world_surface().into_iter(),
+                    u32::BITS - self.height_range.height.leading_zeros(),
+                )),
+            },
+            block_entities: &self.block_entities,
+        };
+        Ok(fastnbt::to_bytes(&chunk_nbt)?)
+    }
+}
+
+#[derive(Serialize)]
+struct ChunkNbt<'a> {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    #[serde(rename = "yPos")]
+    y_pos: i32,
+    #[serde(rename = "Status")]
+    status: &'a str,
+    #[serde(rename = "isLightOn")]
+    is_light_on: i8,
+    sections: Vec<SectionNbt<'a>>,
+    #[serde(rename = "Heightmaps")]
+    heightmaps: HeightmapsNbt,
+    block_entities: &'a [fastnbt::Value],
+}
+
+#[derive(Serialize)]
+struct HeightmapsNbt {
+    #[serde(rename = "WORLD_SURFACE")]
+    world_surface: fastnbt::LongArray,
+}
+
+#[derive(Serialize)]
+struct SectionNbt<'a> {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: PalettedNbt<BlockStateNbt<'a>>,
+    biomes: PalettedNbt<&'a str>,
+    #[serde(rename = "BlockLight")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_light: Option<fastnbt::ByteArray>,
+    #[serde(rename = "SkyLight")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sky_light: Option<fastnbt::ByteArray>,
+}
+
+#[derive(Serialize)]
+struct PalettedNbt<T> {
+    palette: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<fastnbt::LongArray>,
+}
+
+#[derive(Serialize)]
+struct BlockStateNbt<'a> {
+    #[serde(rename = "Name")]
+    name: &'a str,
+    #[serde(rename = "Properties")]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<&'a str, &'a str>,
+}
+
+/// Packs chunks into a region file.
+#[derive(Clone, Debug)]
+pub struct RegionWriter {
+    coords: RCoords,
+    compression: CompressionMethod,
+    /// Uncompressed chunk NBT and timestamp, by flat chunk index.
+    chunks: BTreeMap<usize, (Vec<u8>, u32)>,
+}
+
+impl RegionWriter {
+    /// Create an empty region, compressing chunks with zlib like Minecraft does.
+    pub fn new(coords: RCoords) -> Self {
+        Self {
+            coords,
+            compression: CompressionMethod::Zlib,
+            chunks: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: CompressionMethod) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn coords(&self) -> RCoords {
+        self.coords
+    }
+
+    /// Add a chunk from its uncompressed NBT data (e.g. [`RawChunk::data`](super::RawChunk)),
+    /// replacing any chunk already at `index`. `timestamp` is the chunk's modification time in
+    /// seconds since the Unix epoch.
+    pub fn insert_chunk(&mut self, index: CIndex, data: Vec<u8>, timestamp: u32) {
+        self.chunks.insert(index.to_flat_index(), (data, timestamp));
+    }
+
+    /// Encode `chunk` and add it to the region.
+    pub fn add_chunk(&mut self, chunk: &ChunkBuilder, timestamp: u32) -> anyhow::Result<()> {
+        let (region_coords, index) = chunk.coords().to_region_coords();
+        if region_coords != self.coords {
+            return Err(anyhow!(
+                "chunk {} is not in region {}",
+                chunk.coords(),
+                self.coords
+            ));
+        }
+        self.insert_chunk(index, chunk.to_nbt()?, timestamp);
+        Ok(())
+    }
+
+    /// Write the region file, with the chunks packed in order of their index.
+    pub fn write<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let mut locations = [0u32; REGION_CHUNK_COUNT];
+        let mut timestamps = [0u32; REGION_CHUNK_COUNT];
+        let mut body = Vec::new();
+        let mut next_sector = (REGION_HEADER_SIZE / SECTOR_SIZE) as u32;
+        for (&index, (data, timestamp)) in self.chunks.iter() {
+            let compressed = self.compression.compress(data)?;
+            let start = body.len();
+            // The length includes the compression method byte
+            body.write_u32::<BigEndian>(compressed.len() as u32 + 1)?;
+            body.write_u8(self.compression.into())?;
+            body.extend_from_slice(&compressed);
+            let sector_count = (body.len() - start).div_ceil(SECTOR_SIZE);
+            if sector_count > 0xFF {
+                return Err(anyhow!(
+                    "chunk {} is too big for a region file ({} sectors)",
+                    CIndex::from_flat_index(index).to_chunk_coords(self.coords),
+                    sector_count
+                ));
+            }
+            body.resize(start + sector_count * SECTOR_SIZE, 0);
+            locations[index] = (next_sector << 8) | sector_count as u32;
+            timestamps[index] = *timestamp;
+            next_sector += sector_count as u32;
+        }
+
+        for value in locations.into_iter().chain(timestamps) {
+            writer.write_u32::<BigEndian>(value)?;
+        }
+        writer.write_all(&body)?;
+        Ok(())
+    }
+
+    /// Write the region file into `region_dir` as `r.X.Z.mca`, returning its path.
+    pub fn save(&self, region_dir: &Path) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(region_dir)?;
+        let path = region_dir.join(format!("r.{}.{}.mca", self.coords.x(), self.coords.z()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+/// Write `chunks` into region files in `region_dir` (e.g. the `region` directory of a world),
+/// all with the same `timestamp`. Returns the paths of the region files written.
+pub fn write_region_files<'a>(
+    region_dir: &Path,
+    chunks: impl IntoIterator<Item = &'a ChunkBuilder>,
+    timestamp: u32,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut regions = BTreeMap::new();
+    for chunk in chunks {
+        let (region_coords, _) = chunk.coords().to_region_coords();
+        regions
+            .entry(region_coords)
+            .or_insert_with(|| RegionWriter::new(region_coords))
+            .add_chunk(chunk, timestamp)?;
+    }
+    regions
+        .values()
+        .map(|region| region.save(region_dir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::world::scan::check_sectors;
+    use crate::world::tests::test_settings;
+    use crate::world::{Region, RegionInfo};
+
+    #[test]
+    fn test_pack_bits() {
+        // 5 bits per value leaves 4 bits of padding at the top of each long
+        let data = pack_bits((0..13u32).map(|i| i as u64 % 32), 5);
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0] & 0x1F, 0);
+        assert_eq!((data[0] >> 55) & 0x1F, 11);
+        assert_eq!(data[1], 12);
+    }
+
+    #[test]
+    fn test_region_writer() {
+        let settings = test_settings();
+        let coords = CCoords((-3, 40).into());
+        let leaves = BlockState::new(intern_str("minecraft:oak_leaves"))
+            .with_property("persistent", "true")
+            .with_property("distance", "7");
+        let mut chunk = ChunkBuilder::new(coords, HeightRange::OVERWORLD);
+        chunk.fill_layers(-64..-60, BlockState::new(intern_str("minecraft:stone")));
+        chunk.set_block(1, 100, 15, leaves);
+        chunk.set_biome(1, 100, 15, intern_str("minecraft:forest"));
+        chunk.set_light(1, 101, 15, LightLevel::new().with_block(9).with_sky(4));
+        let sign_text = fastnbt::Value::String("\"Hello\"".to_owned());
+        chunk.add_block_entity(
+            2,
+            -61,
+            3,
+            "minecraft:sign",
+            HashMap::from([("CustomName".to_owned(), sign_text)]),
+        );
+
+        let mut region = RegionWriter::new(RCoords((-1, 1).into()));
+        region.add_chunk(&chunk, 1234).unwrap();
+        assert!(
+            region
+                .add_chunk(
+                    &ChunkBuilder::new(CCoords((0, 0).into()), HeightRange::OVERWORLD),
+                    0
+                )
+                .is_err()
+        );
+        let mut file = Vec::new();
+        region.write(&mut file).unwrap();
+        assert_eq!(file.len() % SECTOR_SIZE, 0);
+
+        let info = RegionInfo::try_from_path(PathBuf::from("r.-1.1.mca")).unwrap();
+        let file_len = file.len() as u64;
+        let mut region = Region::from_stream(info, Cursor::new(file)).unwrap();
+        assert!(check_sectors(&region, file_len).is_empty());
+        let (_, index) = coords.to_region_coords();
+        assert_eq!(region.get_timestamp(index), 1234);
+
+        let chunk = region
+            .get_raw_chunk(index)
+            .unwrap()
+            .unwrap()
+            .parse(HeightRange::OVERWORLD, &settings)
+            .unwrap();
+        assert_eq!(chunk.coords, coords);
+        assert!(chunk.fully_generated);
+        assert_eq!(chunk.sections.len(), 24);
+        let section = chunk.get_section(-64).unwrap();
+        assert_eq!(
+            section.get_block(BIndex((5, 5, 3).into())).state.name,
+            "minecraft:stone"
+        );
+        assert_eq!(
+            section.get_block(BIndex((5, 5, 4).into())).state.name,
+            "minecraft:air"
+        );
+        let sign = section
+            .get_block(BIndex((2, 3, 3).into()))
+            .block_entity
+            .unwrap();
+        assert_eq!(sign.id, "minecraft:sign");
+        assert_eq!(sign.get_str("CustomName"), Some("\"Hello\""));
+        assert_eq!(sign.data.len(), 1);
+        assert!(
+            section
+                .get_block(BIndex((3, 2, 3).into()))
+                .block_entity
+                .is_none()
+        );
+        let section = chunk.get_section(96).unwrap();
+        let block = section.get_block(BIndex((1, 15, 4).into()));
+        assert_eq!(block.state.name, "minecraft:oak_leaves");
+        assert_eq!(block.biome, "minecraft:forest");
+        let block = section.get_block(BIndex((1, 15, 5).into()));
+        assert_eq!(block.state.name, "minecraft:air");
+        assert_eq!(block.lighting.block(), 9);
+        assert_eq!(block.lighting.sky(), 4);
+    }
+}